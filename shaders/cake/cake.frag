@@ -15,6 +15,16 @@ layout(push_constant) uniform PushConstants {
     int end_time;
     int screen_width;
     int screen_height;
+    float color_gamma;
+    float note_alpha;
+    float cap_height;
+    float cap_r;
+    float cap_g;
+    float cap_b;
+    float cap_mix;
+    int spawn_ticks;
+    int min_velocity;
+    int dither;
 } consts;
 
 layout(set = 0, binding = 0) readonly buffer BufferArray
@@ -24,6 +34,28 @@ layout(set = 0, binding = 0) readonly buffer BufferArray
 
 const float pi = 3.1415926535897;
 
+// A 4x4 Bayer matrix, for spreading the rounding to 8 bits over
+// neighboring pixels
+const float bayer[16] = float[](
+    0.0, 8.0, 2.0, 10.0,
+    12.0, 4.0, 14.0, 6.0,
+    3.0, 11.0, 1.0, 9.0,
+    15.0, 7.0, 13.0, 5.0
+);
+
+vec3 dither(vec3 color) {
+    ivec2 pixel = ivec2(gl_FragCoord.xy) % 4;
+    float offset = (bayer[pixel.y * 4 + pixel.x] + 0.5) / 16.0 - 0.5;
+    return max(color + offset / 255.0, vec3(0.0));
+}
+
+// The color of the top part of the notes. A cap alpha of 0 gives a lighter
+// version of the note color, and 1 gives the cap color itself.
+vec3 cap_color(vec3 base) {
+    vec3 lighter = mix(base, vec3(1.0), 0.5);
+    return mix(lighter, vec3(consts.cap_r, consts.cap_g, consts.cap_b), consts.cap_mix);
+}
+
 ivec4 getNoteAt(int time) {
     int nextIndex = buffers[buffer_index].BinTree[0].x;
 
@@ -62,7 +94,8 @@ void main()
 
     vec3 frag_color;
 
-    if (note.z == -1) {
+    // The velocity is stored in the top byte of the color
+    if (note.z == -1 || ((note.z >> 24) & 0x7F) < consts.min_velocity) {
         discard;
     } else {
         frag_color = vec3(((note.z >> 16) & 0xFF) / 255.0, ((note.z >> 8) & 0xFF) / 255.0, (note.z & 0xFF) / 255.0);
@@ -70,12 +103,6 @@ void main()
 
     vec3 color = frag_color;
 
-    // Adjust color
-
-    color *= (1.0 + cos(pi * 0.5 * v_uv.x)) * 0.5;
-
-    // Check borders
-
     float note_top = ticks_to_screen_y(note.x);
     float note_bottom = ticks_to_screen_y(note.y);
 
@@ -83,6 +110,18 @@ void main()
     float note_top_dist = (y - note_top);
     float note_bottom_dist = (note_bottom - y);
 
+    // Tint the top of the note
+
+    if (note_bottom_dist < consts.cap_height * (note_bottom - note_top)) {
+        color = cap_color(frag_color);
+    }
+
+    // Adjust color
+
+    color *= (1.0 + cos(pi * 0.5 * v_uv.x)) * 0.5;
+
+    // Check borders
+
     float note_left_dist = (screen_pos.x - left_right.x);
     float note_right_dist = (left_right.y - screen_pos.x);
 
@@ -94,7 +133,20 @@ void main()
         color = frag_color * 0.2;
     }
 
-    // Square for SRGB
-    color *= color;
-    fsout_Color = vec4(color, 1);
+    // Dithered before the conversion, as the framebuffer rounds the
+    // sRGB encoded values
+    if (consts.dither != 0) {
+        color = dither(color);
+    }
+
+    // The colors are sRGB encoded and the framebuffer is sRGB,
+    // so they need to be converted to linear
+    color = pow(color, vec3(consts.color_gamma));
+    // Premultiplied, as transparent notes get blended under the previous ones
+    // Fade in the notes that have just entered the top of the screen
+    float alpha = consts.note_alpha;
+    if (consts.spawn_ticks > 0) {
+        alpha *= clamp(float(consts.end_time - note.x) / float(consts.spawn_ticks), 0.0, 1.0);
+    }
+    fsout_Color = vec4(color * alpha, alpha);
 }
@@ -0,0 +1,35 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 out_color;
+
+layout(set = 0, binding = 0) uniform sampler2D tex;
+
+layout(push_constant) uniform PushConstants {
+    vec2 direction;
+    float threshold;
+    float intensity;
+} consts;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+// Only the bright parts of the image glow
+vec3 bright(vec2 uv) {
+    vec3 color = texture(tex, uv).rgb;
+    float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    return luma > consts.threshold ? color : vec3(0.0);
+}
+
+void main() {
+    vec3 color = bright(v_uv) * weights[0];
+
+    for (int i = 1; i < 5; i++) {
+        vec2 offset = consts.direction * float(i);
+        color += bright(v_uv + offset) * weights[i];
+        color += bright(v_uv - offset) * weights[i];
+    }
+
+    // The alpha is 0 so the glow gets added to the image as light
+    out_color = vec4(color * consts.intensity, 0.0);
+}
@@ -5,15 +5,56 @@ layout(location = 1) in vec2 frag_tex_coord;
 layout(location = 2) in vec2 v_note_size;
 layout(location = 3) in vec2 win_size;
 layout(location = 4) in flat uint border_width;
+layout(location = 5) in flat float spawn_alpha;
 
 layout(location = 0) out vec4 out_color;
 
+layout(push_constant) uniform PushConstants {
+    float height_time;
+    float win_width;
+    float win_height;
+    float color_gamma;
+    float note_alpha;
+    float cap_height;
+    float cap_r;
+    float cap_g;
+    float cap_b;
+    float cap_mix;
+    float spawn_time;
+    int dither;
+} consts;
+
 const float pi = 3.1415926535897;
 
+// A 4x4 Bayer matrix, for spreading the rounding to 8 bits over
+// neighboring pixels
+const float bayer[16] = float[](
+    0.0, 8.0, 2.0, 10.0,
+    12.0, 4.0, 14.0, 6.0,
+    3.0, 11.0, 1.0, 9.0,
+    15.0, 7.0, 13.0, 5.0
+);
+
+vec3 dither(vec3 color) {
+    ivec2 pixel = ivec2(gl_FragCoord.xy) % 4;
+    float offset = (bayer[pixel.y * 4 + pixel.x] + 0.5) / 16.0 - 0.5;
+    return max(color + offset / 255.0, vec3(0.0));
+}
+
+// The color of the top part of the notes. A cap alpha of 0 gives a lighter
+// version of the note color, and 1 gives the cap color itself.
+vec3 cap_color(vec3 base) {
+    vec3 lighter = mix(base, vec3(1.0), 0.5);
+    return mix(lighter, vec3(consts.cap_r, consts.cap_g, consts.cap_b), consts.cap_mix);
+}
+
 void main() {
     vec2 v_uv = frag_tex_coord;
     
     vec3 color = frag_color;
+    if (v_uv.y > 1.0 - consts.cap_height) {
+        color = cap_color(frag_color);
+    }
     float aspect = win_size.y / win_size.x;
 
     color *= (1.0 + cos(pi * 0.5 * v_uv.x)) * 0.5;
@@ -35,7 +76,16 @@ void main() {
         color = vec3(frag_color * 0.2);
     }
 
-    // Square for SRGB
-    color *= color;
-    out_color = vec4(color, 1.0);
+    // Dithered before the conversion, as the framebuffer rounds the
+    // sRGB encoded values
+    if (consts.dither != 0) {
+        color = dither(color);
+    }
+
+    // The colors are sRGB encoded and the framebuffer is sRGB,
+    // so they need to be converted to linear
+    color = pow(color, vec3(consts.color_gamma));
+    // Premultiplied, as transparent notes get blended under the previous ones
+    float alpha = consts.note_alpha * spawn_alpha;
+    out_color = vec4(color * alpha, alpha);
 }
@@ -1,8 +1,20 @@
-use crate::{renderer::Renderer, settings::WasabiSettings, state::WasabiState, utils};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    gui::window::ActiveNotesCallback,
+    renderer::Renderer,
+    settings::WasabiSettings,
+    state::{CaptureOptions, WasabiState},
+    utils,
+};
 use egui_winit::winit::event::WindowEvent;
 use winit::{
     application::ApplicationHandler,
-    event_loop::ActiveEventLoop,
+    dpi::{PhysicalPosition, PhysicalSize},
+    event_loop::{ActiveEventLoop, ControlFlow},
     window::{Icon, WindowAttributes, WindowId},
 };
 
@@ -13,12 +25,25 @@ pub struct WasabiApplication {
     state: WasabiState,
 
     renderer: Option<Renderer>,
+    startup_midi: Option<PathBuf>,
+
+    focused: bool,
+    last_redraw: Instant,
+    /// Whether saving the session already failed once, see
+    /// [`WasabiApplication::save_session`]
+    session_save_failed: bool,
 }
 
 impl WasabiApplication {
-    pub fn new() -> Self {
+    pub fn new(
+        startup_midi: Option<PathBuf>,
+        capture: Option<CaptureOptions>,
+        on_active_notes: Option<ActiveNotesCallback>,
+    ) -> Self {
         // Load the settings values
-        let state = WasabiState::new();
+        let mut state = WasabiState::new();
+        state.capture = capture;
+        state.on_active_notes = on_active_notes;
         let settings = WasabiSettings::new_or_load().unwrap_or_else(|e| {
             state.errors.error(&e);
             WasabiSettings::default()
@@ -35,6 +60,59 @@ impl WasabiApplication {
             settings,
             state,
             renderer: None,
+            startup_midi,
+            focused: true,
+            last_redraw: Instant::now(),
+            session_save_failed: false,
+        }
+    }
+
+    /// Returns the saved window position if the window would be visible there.
+    /// Otherwise (e.g. the monitor was disconnected) the window gets centered
+    /// on the primary monitor.
+    fn window_position(
+        &self,
+        event_loop: &ActiveEventLoop,
+        size: PhysicalSize<u32>,
+    ) -> Option<PhysicalPosition<i32>> {
+        let [x, y] = self.settings.gui.window_position?;
+
+        // Part of the title bar has to be on a monitor, so the window can be moved
+        let visible = event_loop.available_monitors().any(|monitor| {
+            let pos = monitor.position();
+            let monitor_size = monitor.size();
+            (pos.x..pos.x + monitor_size.width as i32).contains(&(x + 50))
+                && (pos.y..pos.y + monitor_size.height as i32).contains(&(y + 10))
+        });
+        if visible {
+            return Some(PhysicalPosition::new(x, y));
+        }
+
+        let monitor = event_loop
+            .primary_monitor()
+            .or_else(|| event_loop.available_monitors().next())?;
+        let pos = monitor.position();
+        let monitor_size = monitor.size();
+        Some(PhysicalPosition::new(
+            pos.x + (monitor_size.width as i32 - size.width as i32).max(0) / 2,
+            pos.y + (monitor_size.height as i32 - size.height as i32).max(0) / 2,
+        ))
+    }
+
+    /// Saves the session before quitting, and returns whether the window
+    /// can close. The first time saving fails, the window stays open so the
+    /// error can be seen, and closing it again quits anyway.
+    fn save_session(
+        settings: &WasabiSettings,
+        state: &WasabiState,
+        session_save_failed: &mut bool,
+    ) -> bool {
+        match settings.save_session() {
+            Ok(()) => true,
+            Err(e) => {
+                state.errors.error(&e);
+                std::mem::replace(session_save_failed, true)
+            }
         }
     }
 }
@@ -42,10 +120,18 @@ impl WasabiApplication {
 impl ApplicationHandler for WasabiApplication {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         if self.renderer.is_none() {
-            let win_attr = WindowAttributes::default()
+            let mut win_attr = WindowAttributes::default()
                 .with_window_icon(Some(Icon::from_rgba(ICON.to_vec(), 16, 16).unwrap()))
                 .with_inner_size(crate::WINDOW_SIZE)
                 .with_title("Wasabi");
+
+            if let Some([width, height]) = self.settings.gui.window_size {
+                let size = PhysicalSize::new(width.max(320), height.max(240));
+                win_attr = win_attr.with_inner_size(size);
+                if let Some(position) = self.window_position(event_loop, size) {
+                    win_attr = win_attr.with_position(position);
+                }
+            }
             let window = event_loop.create_window(win_attr).unwrap();
             self.renderer = Some(Renderer::new(
                 event_loop,
@@ -54,11 +140,36 @@ impl ApplicationHandler for WasabiApplication {
                 &self.state,
             ))
         }
+
+        if let (Some(renderer), Some(path)) = (self.renderer.as_mut(), self.startup_midi.take()) {
+            renderer
+                .gui_window()
+                .load_midi(path, &mut self.settings, &self.state);
+        }
     }
 
-    fn new_events(&mut self, _event_loop: &ActiveEventLoop, _cause: winit::event::StartCause) {
-        if let Some(renderer) = self.renderer.as_mut() {
-            renderer.window().request_redraw();
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, _cause: winit::event::StartCause) {
+        let Some(renderer) = self.renderer.as_mut() else {
+            return;
+        };
+
+        // Captured frames are always paused, but they must not be limited
+        let idle =
+            self.state.capture.is_none() && (!self.focused || !renderer.gui_window().is_playing());
+
+        match self.settings.gui.idle_fps.filter(|_| idle) {
+            Some(fps) => {
+                let next_redraw =
+                    self.last_redraw + Duration::from_secs_f64(1.0 / fps.max(1) as f64);
+                if Instant::now() >= next_redraw {
+                    renderer.window().request_redraw();
+                }
+                event_loop.set_control_flow(ControlFlow::WaitUntil(next_redraw));
+            }
+            None => {
+                event_loop.set_control_flow(ControlFlow::Poll);
+                renderer.window().request_redraw();
+            }
         }
     }
 
@@ -71,15 +182,41 @@ impl ApplicationHandler for WasabiApplication {
         if let Some(renderer) = self.renderer.as_mut() {
             let _pass_events_to_game = !renderer.gui().update(&event);
 
+            // Input gets shown right away, even if the framerate is limited
+            if !matches!(event, WindowEvent::RedrawRequested) {
+                renderer.window().request_redraw();
+            }
+
             match event {
                 WindowEvent::Resized(size) => {
                     renderer.resize(Some(size));
+                    if renderer.window().fullscreen().is_none() && size.width > 0 && size.height > 0
+                    {
+                        self.settings.gui.window_size = Some([size.width, size.height]);
+                    }
+                }
+                WindowEvent::Moved(position) => {
+                    if renderer.window().fullscreen().is_none() {
+                        self.settings.gui.window_position = Some([position.x, position.y]);
+                    }
                 }
                 WindowEvent::ScaleFactorChanged { .. } => {
                     renderer.resize(None);
                 }
+                WindowEvent::Focused(focused) => {
+                    self.focused = focused;
+                    renderer
+                        .gui_window()
+                        .set_focused(focused, &self.settings, &self.state);
+                }
                 WindowEvent::CloseRequested => {
-                    event_loop.exit();
+                    if Self::save_session(
+                        &self.settings,
+                        &self.state,
+                        &mut self.session_save_failed,
+                    ) {
+                        event_loop.exit();
+                    }
                 }
                 WindowEvent::DroppedFile(path) => {
                     renderer
@@ -87,6 +224,7 @@ impl ApplicationHandler for WasabiApplication {
                         .load_midi(path, &mut self.settings, &self.state);
                 }
                 WindowEvent::RedrawRequested => {
+                    self.last_redraw = Instant::now();
                     renderer.render(&mut self.settings, &mut self.state);
                 }
                 _ => (),
@@ -105,6 +243,13 @@ impl ApplicationHandler for WasabiApplication {
                 self.state.fullscreen = false;
             }
             renderer.set_vsync(self.settings.gui.vsync);
+
+            if self.state.quit_requested {
+                self.state.quit_requested = false;
+                if Self::save_session(&self.settings, &self.state, &mut self.session_save_failed) {
+                    event_loop.exit();
+                }
+            }
         }
     }
 }
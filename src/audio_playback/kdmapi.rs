@@ -1,7 +1,7 @@
 use crate::settings::{KdmapiSettings, WasabiSettings};
 use std::io::Write;
 
-use crate::{gui::window::WasabiError, utils};
+use crate::{error::WasabiError, utils};
 
 use super::*;
 use kdmapi_rs::{KDMAPIStream, KDMAPI};
@@ -33,6 +33,12 @@ impl KdmapiPlayer {
         self.stream.reset();
     }
 
+    pub fn full_reset(&mut self) {
+        let reset = utils::create_full_reset_midi_messages();
+        self.push_events(reset.into_iter());
+        self.stream.reset();
+    }
+
     pub fn push_events(&mut self, data: impl Iterator<Item = u32>) {
         for ev in data {
             self.stream.send_direct_data(ev);
@@ -49,7 +55,20 @@ impl KdmapiPlayer {
         errors: Arc<GuiMessageSystem>,
     ) {
         if !self.use_om_list {
-            let list = utils::create_om_sf_list(soundfonts);
+            let soundfonts: Vec<WasabiSoundfont> = soundfonts
+                .iter()
+                .filter(|sf| match check_soundfont_format(&sf.path) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        if sf.enabled {
+                            errors.error(&err);
+                        }
+                        false
+                    }
+                })
+                .cloned()
+                .collect();
+            let list = utils::create_om_sf_list(&soundfonts);
 
             let mut path = WasabiSettings::get_config_dir();
             path.push("wasabi-sflist.csflist");
@@ -1,16 +1,66 @@
-use std::thread;
+use std::{ops::RangeInclusive, thread};
 
-use crate::gui::window::WasabiError;
+use crate::{
+    error::WasabiError,
+    gui::window::GuiMessageSystem,
+    settings::{MidiDeviceOutput, MidiRouting},
+};
 
 use crossbeam_channel::Sender;
-use midir::MidiOutput;
+use midir::{MidiOutput, MidiOutputConnection};
 
-pub struct MidiDevicePlayer {
+struct DeviceOutput {
     sender: Sender<u32>,
+    channels: RangeInclusive<u8>,
+}
+
+impl DeviceOutput {
+    fn spawn(mut connection: MidiOutputConnection, channels: RangeInclusive<u8>) -> Self {
+        let (sender, receiver) = crossbeam_channel::bounded::<u32>(1000);
+
+        thread::spawn(move || {
+            for data in receiver {
+                // If the device disappears, its events just get dropped
+                // so the other devices keep playing
+                let message = data.to_le_bytes();
+                connection.send(&message).unwrap_or_default();
+            }
+        });
+
+        Self { sender, channels }
+    }
+}
+
+pub struct MidiDevicePlayer {
+    outputs: Vec<DeviceOutput>,
+    routing: MidiRouting,
 }
 
 impl MidiDevicePlayer {
-    pub fn new(device: String) -> Result<Self, WasabiError> {
+    pub fn new(
+        devices: &[MidiDeviceOutput],
+        routing: MidiRouting,
+        errors: &GuiMessageSystem,
+    ) -> Result<Self, WasabiError> {
+        let mut outputs = Vec::new();
+        for device in devices {
+            match Self::connect(Some(&device.name)) {
+                Ok(connection) => {
+                    outputs.push(DeviceOutput::spawn(connection, device.channels.clone()))
+                }
+                Err(e) => errors.warning(e.to_string()),
+            }
+        }
+
+        // Fall back to the first available device
+        if outputs.is_empty() {
+            outputs.push(DeviceOutput::spawn(Self::connect(None)?, 1..=16));
+        }
+
+        Ok(Self { outputs, routing })
+    }
+
+    fn connect(device: Option<&str>) -> Result<MidiOutputConnection, WasabiError> {
         let out = MidiOutput::new("wasabi")
             .map_err(|e| WasabiError::SynthError(format!("MIDI Out Error: {e}")))?;
         let ports = out.ports();
@@ -18,28 +68,30 @@ impl MidiDevicePlayer {
             return Err(WasabiError::SynthError("No MIDI devices available.".into()));
         }
 
-        let find = ports.iter().find(|d| {
-            if let Ok(name) = out.port_name(d) {
-                name == device
-            } else {
-                false
-            }
-        });
-        let found = find.unwrap_or(&ports[0]);
-        let mut connection = out
-            .connect(found, "wasabi")
-            .map_err(|e| WasabiError::SynthError(format!("MIDI Out Error: {e}")))?;
-
-        let (sender, receiver) = crossbeam_channel::bounded::<u32>(1000);
+        let found = match device {
+            Some(device) => ports
+                .iter()
+                .find(|d| {
+                    if let Ok(name) = out.port_name(d) {
+                        name == device
+                    } else {
+                        false
+                    }
+                })
+                .ok_or_else(|| {
+                    WasabiError::SynthError(format!("MIDI device {device:?} was not found."))
+                })?,
+            None => &ports[0],
+        };
 
-        thread::spawn(move || {
-            for data in receiver {
-                let message = data.to_le_bytes();
-                connection.send(&message).unwrap_or_default();
-            }
-        });
+        out.connect(found, "wasabi")
+            .map_err(|e| WasabiError::SynthError(format!("MIDI Out Error: {e}")))
+    }
 
-        Ok(Self { sender })
+    /// The events that were pushed but haven't been sent to the devices yet,
+    /// summed over all devices
+    pub fn queued_events(&self) -> usize {
+        self.outputs.iter().map(|output| output.sender.len()).sum()
     }
 
     pub fn reset(&mut self) {
@@ -47,9 +99,27 @@ impl MidiDevicePlayer {
         self.push_events(reset.into_iter());
     }
 
+    pub fn full_reset(&mut self) {
+        let reset = crate::utils::create_full_reset_midi_messages();
+        self.push_events(reset.into_iter());
+    }
+
     pub fn push_events(&mut self, data: impl Iterator<Item = u32>) {
         for ev in data {
-            self.sender.send(ev).unwrap();
+            // Channel messages get routed, system messages go to all devices
+            let status = (ev & 0xFF) as u8;
+            let channel = (status < 0xF0).then_some((status & 0x0F) + 1);
+
+            for output in self.outputs.iter() {
+                let send = match (self.routing, channel) {
+                    (MidiRouting::Channels, Some(channel)) => output.channels.contains(&channel),
+                    _ => true,
+                };
+
+                if send {
+                    output.sender.send(ev).unwrap_or_default();
+                }
+            }
         }
     }
 }
@@ -1,6 +1,16 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use atomic_float::AtomicF64;
+use thread_priority::ThreadPriority;
 
 use crate::{
+    error::WasabiError,
     gui::window::{GuiMessageSystem, LoadingStatus},
     settings::{Synth, SynthSettings, WasabiSoundfont},
 };
@@ -12,6 +22,20 @@ pub use kdmapi::*;
 mod midiout;
 pub use midiout::*;
 
+/// DLS soundbanks can be added to the list, but none of the synths can load
+/// them, so they get reported instead of silently producing no sound.
+pub(crate) fn check_soundfont_format(path: &Path) -> Result<(), WasabiError> {
+    let is_dls = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("dls"));
+
+    if is_dls {
+        Err(WasabiError::UnsupportedSoundFont(path.to_path_buf()))
+    } else {
+        Ok(())
+    }
+}
+
 enum MidiAudioPlayer {
     XSynth(XSynthPlayer),
     Kdmapi(KdmapiPlayer),
@@ -19,22 +43,149 @@ enum MidiAudioPlayer {
     None,
 }
 
-pub struct WasabiAudioPlayer(RwLock<MidiAudioPlayer>);
+/// How the threads that send the MIDI events to the synth get scheduled
+#[derive(Clone, Copy, Default)]
+struct PlaybackThreadOptions {
+    high_priority: bool,
+    core: Option<usize>,
+}
+
+pub struct WasabiAudioPlayer {
+    player: RwLock<MidiAudioPlayer>,
+    thread_options: RwLock<PlaybackThreadOptions>,
+    /// How far the played events are behind the timer, in seconds, see
+    /// [`WasabiAudioPlayer::report_played_position`]
+    audio_lag: AtomicF64,
+    /// See [`WasabiAudioPlayer::set_audio_suppressed`]
+    audio_suppressed: AtomicBool,
+    /// Where the playback threads report the thread settings that couldn't
+    /// be applied, set by [`WasabiAudioPlayer::switch`]
+    errors: RwLock<Option<Arc<GuiMessageSystem>>>,
+}
 
 impl WasabiAudioPlayer {
+    /// Creates the synth of `settings`, for using the engine without the
+    /// GUI. Unlike [`Self::switch`], the XSynth soundfonts get loaded before
+    /// returning, and anything that would have been shown in the GUI is
+    /// returned as an error instead.
+    pub fn new(settings: &SynthSettings) -> Result<Arc<Self>, WasabiError> {
+        let errors = GuiMessageSystem::new();
+        let mut synth = Self::create_synth(settings, &errors)?;
+        match &mut synth {
+            MidiAudioPlayer::XSynth(player) => player.load_soundfonts(&settings.soundfonts)?,
+            MidiAudioPlayer::Kdmapi(player) => {
+                player.configure(&settings.kdmapi);
+                player.set_soundfonts(&settings.soundfonts, errors.clone());
+            }
+            _ => {}
+        }
+        if let Some(message) = errors.take_messages().into_iter().next() {
+            return Err(WasabiError::SynthError(message));
+        }
+
+        let player = Self::empty();
+        *player.player.write().unwrap() = synth;
+        player.full_reset();
+        player.configure(settings);
+        Ok(player)
+    }
+
     pub fn empty() -> Arc<Self> {
-        Arc::new(Self(RwLock::new(MidiAudioPlayer::None)))
+        Arc::new(Self {
+            player: RwLock::new(MidiAudioPlayer::None),
+            thread_options: RwLock::new(Default::default()),
+            audio_lag: AtomicF64::new(0.0),
+            audio_suppressed: AtomicBool::new(false),
+            errors: RwLock::new(None),
+        })
+    }
+
+    /// Applies the priority and core settings to the current thread. Meant to
+    /// be called from the threads that feed the synth with events. Elevating
+    /// the priority isn't allowed on all systems, so failures are only shown
+    /// as warnings and the thread continues with the normal priority.
+    pub fn prepare_playback_thread(&self) {
+        let options = *self.thread_options.read().unwrap();
+
+        if options.high_priority {
+            if let Err(e) = thread_priority::set_current_thread_priority(ThreadPriority::Max) {
+                self.warn(format!(
+                    "Failed to raise the priority of the audio thread: {e:?}"
+                ));
+            }
+        }
+
+        if let Some(id) = options.core {
+            if !core_affinity::set_for_current(core_affinity::CoreId { id }) {
+                self.warn(format!("Failed to pin the audio thread to core {id}"));
+            }
+        }
+    }
+
+    fn warn(&self, message: String) {
+        if let Some(errors) = self.errors.read().unwrap().as_ref() {
+            errors.warning(message);
+        }
     }
 
     pub fn voice_count(&self) -> Option<u64> {
-        match &*self.0.read().unwrap() {
+        match &*self.player.read().unwrap() {
             MidiAudioPlayer::XSynth(player) => Some(player.voice_count()),
             _ => None,
         }
     }
 
+    /// The average time spent rendering an audio buffer, relative to the
+    /// length of the buffer. Values above 1.0 mean the synth can't keep up.
+    pub fn render_load(&self) -> Option<f32> {
+        match &*self.player.read().unwrap() {
+            MidiAudioPlayer::XSynth(player) => Some(player.render_load()),
+            _ => None,
+        }
+    }
+
+    /// The events waiting to be sent to the synth, if the synth exposes them.
+    /// A growing backlog means the events arrive late.
+    pub fn queued_events(&self) -> Option<usize> {
+        match &*self.player.read().unwrap() {
+            MidiAudioPlayer::MidiDevice(player) => Some(player.queued_events()),
+            _ => None,
+        }
+    }
+
+    /// Records the position of the audio. Meant to be called by the playback
+    /// threads right after pushing the events of `event_time`, with the time
+    /// of the timer at that moment.
+    pub fn report_played_position(&self, event_time: f64, timer_time: f64) {
+        self.audio_lag
+            .store((timer_time - event_time).max(0.0), Ordering::Relaxed);
+    }
+
+    /// How far the last played events were behind the timer, in seconds
+    pub fn audio_lag(&self) -> f64 {
+        self.audio_lag.load(Ordering::Relaxed)
+    }
+
+    /// Whether the audio plays at most `tolerance_ms` behind the visuals.
+    /// If it doesn't, the machine can't send the events fast enough.
+    pub fn is_synced(&self, tolerance_ms: f64) -> bool {
+        self.audio_lag() * 1000.0 <= tolerance_ms
+    }
+
+    /// Keeps the playback threads from playing any notes while the visuals
+    /// keep following the timer, e.g. while scrubbing. Once the audio isn't
+    /// suppressed anymore, the threads flush the synth and continue from the
+    /// current position.
+    pub fn set_audio_suppressed(&self, suppressed: bool) {
+        self.audio_suppressed.store(suppressed, Ordering::Relaxed);
+    }
+
+    pub fn is_audio_suppressed(&self) -> bool {
+        self.audio_suppressed.load(Ordering::Relaxed)
+    }
+
     pub fn push_events(&self, data: impl Iterator<Item = u32>) {
-        match &mut *self.0.write().unwrap() {
+        match &mut *self.player.write().unwrap() {
             MidiAudioPlayer::XSynth(player) => player.push_events(data),
             MidiAudioPlayer::Kdmapi(player) => player.push_events(data),
             MidiAudioPlayer::MidiDevice(player) => player.push_events(data),
@@ -43,7 +194,12 @@ impl WasabiAudioPlayer {
     }
 
     pub fn configure(&self, settings: &SynthSettings) {
-        match &mut *self.0.write().unwrap() {
+        *self.thread_options.write().unwrap() = PlaybackThreadOptions {
+            high_priority: settings.audio_thread_priority,
+            core: settings.audio_thread_core,
+        };
+
+        match &mut *self.player.write().unwrap() {
             MidiAudioPlayer::XSynth(player) => player.configure(&settings.xsynth),
             MidiAudioPlayer::Kdmapi(player) => player.configure(&settings.kdmapi),
             _ => {}
@@ -56,7 +212,7 @@ impl WasabiAudioPlayer {
         loading_status: Arc<LoadingStatus>,
         errors: Arc<GuiMessageSystem>,
     ) {
-        match &mut *self.0.write().unwrap() {
+        match &mut *self.player.write().unwrap() {
             MidiAudioPlayer::XSynth(player) => {
                 player.set_soundfonts(soundfonts, loading_status, errors)
             }
@@ -66,7 +222,10 @@ impl WasabiAudioPlayer {
     }
 
     pub fn reset(&self) {
-        match &mut *self.0.write().unwrap() {
+        // Reset on pauses and seeks, after which the lag starts over
+        self.audio_lag.store(0.0, Ordering::Relaxed);
+
+        match &mut *self.player.write().unwrap() {
             MidiAudioPlayer::XSynth(player) => player.reset(),
             MidiAudioPlayer::Kdmapi(player) => player.reset(),
             MidiAudioPlayer::MidiDevice(player) => player.reset(),
@@ -74,42 +233,64 @@ impl WasabiAudioPlayer {
         }
     }
 
+    /// Like [`Self::reset`], but also resets the controllers, centers the
+    /// pitch bend and selects the first program on every channel, so the
+    /// synth is in the same state as before any events were played
+    pub fn full_reset(&self) {
+        self.audio_lag.store(0.0, Ordering::Relaxed);
+
+        match &mut *self.player.write().unwrap() {
+            MidiAudioPlayer::XSynth(player) => player.full_reset(),
+            MidiAudioPlayer::Kdmapi(player) => player.full_reset(),
+            MidiAudioPlayer::MidiDevice(player) => player.full_reset(),
+            _ => {}
+        }
+    }
+
     pub fn switch(
         &self,
         settings: &SynthSettings,
         loading_status: Arc<LoadingStatus>,
         errors: Arc<GuiMessageSystem>,
     ) {
-        // First drop the previous synth to avoid any loading errors
-        *self.0.write().unwrap() = MidiAudioPlayer::None;
+        *self.errors.write().unwrap() = Some(errors.clone());
+
+        // Leave the previous synth clean, as MIDI devices keep their state,
+        // then drop it to avoid any loading errors
+        self.full_reset();
+        *self.player.write().unwrap() = MidiAudioPlayer::None;
 
         // Create the new synth object based on the settings
+        let synth = Self::create_synth(settings, &errors).unwrap_or_else(|e| {
+            errors.error(&e);
+            MidiAudioPlayer::None
+        });
+
+        // Apply the synth to the struct, starting from a clean state
+        *self.player.write().unwrap() = synth;
+        self.full_reset();
+
+        // Configure the synth and load the soundfont list
+        self.configure(settings);
+        self.set_soundfonts(&settings.soundfonts, loading_status, errors);
+    }
+    fn create_synth(
+        settings: &SynthSettings,
+        errors: &GuiMessageSystem,
+    ) -> Result<MidiAudioPlayer, WasabiError> {
         let synth = match settings.synth {
+            _ if !settings.audio_enabled => MidiAudioPlayer::None,
             Synth::XSynth => {
                 MidiAudioPlayer::XSynth(XSynthPlayer::new(settings.xsynth.config.clone()))
             }
-            Synth::Kdmapi => match KdmapiPlayer::new() {
-                Ok(kdmapi) => MidiAudioPlayer::Kdmapi(kdmapi),
-                Err(e) => {
-                    errors.error(&e);
-                    MidiAudioPlayer::None
-                }
-            },
-            Synth::MidiDevice => match MidiDevicePlayer::new(settings.midi_device.clone()) {
-                Ok(midiout) => MidiAudioPlayer::MidiDevice(midiout),
-                Err(e) => {
-                    errors.error(&e);
-                    MidiAudioPlayer::None
-                }
-            },
+            Synth::Kdmapi => MidiAudioPlayer::Kdmapi(KdmapiPlayer::new()?),
+            Synth::MidiDevice => MidiAudioPlayer::MidiDevice(MidiDevicePlayer::new(
+                &settings.midi_devices,
+                settings.midi_routing,
+                errors,
+            )?),
             Synth::None => MidiAudioPlayer::None,
         };
-
-        // Apply the synth to the struct
-        *self.0.write().unwrap() = synth;
-
-        // Configure the synth and load the soundfont list
-        self.configure(settings);
-        self.set_soundfonts(&settings.soundfonts, loading_status, errors);
+        Ok(synth)
     }
 }
@@ -5,7 +5,8 @@ use std::{
 };
 
 use crate::{
-    gui::window::{LoadingType, WasabiError},
+    error::WasabiError,
+    gui::window::LoadingType,
     settings::{WasabiSoundfont, XSynthSettings},
 };
 
@@ -66,6 +67,11 @@ impl XSynthPlayer {
         self.stats.voice_count()
     }
 
+    pub fn render_load(&self) -> f32 {
+        // XSynth already times every render and keeps a rolling average
+        self.stats.buffer().average_renderer_load() as f32
+    }
+
     pub fn push_events(&mut self, data: impl Iterator<Item = u32>) {
         for ev in data {
             self.sender.send_event_u32(ev);
@@ -76,6 +82,12 @@ impl XSynthPlayer {
         self.sender.reset_synth();
     }
 
+    pub fn full_reset(&mut self) {
+        // Cuts the voices right away, the messages clear the channel state
+        self.sender.reset_synth();
+        self.push_events(crate::utils::create_full_reset_midi_messages().into_iter());
+    }
+
     pub fn configure(&mut self, settings: &XSynthSettings) {
         let layers = if settings.limit_layers {
             Some(settings.layers)
@@ -92,6 +104,24 @@ impl XSynthPlayer {
             .set_ignore_range(settings.config.ignore_range.clone());
     }
 
+    /// Like [`Self::set_soundfonts`], but the soundfonts get loaded on the
+    /// calling thread, stopping at the first one that fails to load
+    pub fn load_soundfonts(&mut self, soundfonts: &[WasabiSoundfont]) -> Result<(), WasabiError> {
+        let mut out: Vec<Arc<dyn SoundfontBase>> = Vec::new();
+        for sf in soundfonts.iter().rev().filter(|sf| sf.enabled) {
+            check_soundfont_format(&sf.path)?;
+            let soundfont = SampleSoundfont::new(&sf.path, self.stream_params, sf.options)
+                .map_err(WasabiError::SoundFontLoadError)?;
+            out.push(Arc::new(soundfont));
+        }
+
+        self.sender
+            .send_event(SynthEvent::AllChannels(ChannelEvent::Config(
+                ChannelConfigEvent::SetSoundfonts(out),
+            )));
+        Ok(())
+    }
+
     pub fn set_soundfonts(
         &mut self,
         soundfonts: &[WasabiSoundfont],
@@ -118,6 +148,11 @@ impl XSynthPlayer {
                         sf.path.file_name().unwrap_or_default()
                     ));
 
+                    if let Err(err) = check_soundfont_format(&sf.path) {
+                        errors.error(&err);
+                        continue;
+                    }
+
                     match SampleSoundfont::new(&sf.path, stream_params, sf.options) {
                         Ok(sf) => out.push(Arc::new(sf)),
                         Err(err) => errors.error(&WasabiError::SoundFontLoadError(err)),
@@ -0,0 +1,20 @@
+//! The MIDI engine of the player, for using it without going through the
+//! GUI, as `wasabi::engine`. These re-exports are meant to stay stable,
+//! while the modules they come from can still be moved around.
+//!
+//! Loading a MIDI still takes an optional [`WasabiAudioPlayer`] for the
+//! audio, see [`WasabiAudioPlayer::new`], which can be left out for visuals
+//! only.
+
+pub use crate::{
+    audio_playback::WasabiAudioPlayer,
+    error::WasabiError,
+    midi::{
+        read_debug_events, Beat, CakeCacheWriter, CakeLoadHandle, CakeMIDIFile, DebugEvent,
+        DisplacedMIDINote, InRamMIDIFile, Instrument, LiveLoadMIDIFile, LoadCallback, LoadStats,
+        MIDIColor, MIDIFile, MIDIFileBase, MIDIFileStats, MIDIFileUnion, MIDIFileUniqueSignature,
+        MIDINoteColumnView, MIDINoteViews, MIDIViewRange, NoteColorFn, NoteDetails, NoteId,
+        NoteInfo, ParseReport, RenderFrame, TempoChange, TimeKeeper, VisibleNote,
+    },
+    settings::{MidiParsing, MidiSettings, NoteOverlap, SynthSettings},
+};
@@ -0,0 +1,48 @@
+use std::{fmt, path::PathBuf};
+
+use midi_toolkit::io::MIDILoadError;
+use xsynth_core::soundfont::LoadSfError;
+
+#[derive(Debug)]
+pub enum WasabiError {
+    MidiLoadError(MIDILoadError),
+    MidiFormatError(String),
+    SoundFontLoadError(LoadSfError),
+    UnsupportedSoundFont(PathBuf),
+    SynthError(String),
+    FilesystemError(std::io::Error),
+    SettingsError(String),
+    UpdaterError(String),
+    PaletteError(String),
+    /// The operation was stopped on request, e.g. loading a MIDI
+    Cancelled,
+    Other(String),
+}
+
+impl fmt::Display for WasabiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WasabiError::MidiLoadError(e) => match e {
+                MIDILoadError::CorruptChunks => write!(f, "MIDI Load Error: Corrupt Chunks"),
+                MIDILoadError::FilesystemError(fs) => {
+                    write!(f, "MIDI Load Error: Filesystem Error ({fs})")
+                }
+                MIDILoadError::FileTooBig => write!(f, "MIDI Load Error: File Too Big"),
+            },
+            WasabiError::MidiFormatError(e) => write!(f, "MIDI Load Error: {e}"),
+            WasabiError::SoundFontLoadError(e) => write!(f, "Error Parsing SoundFont: {e}"),
+            WasabiError::UnsupportedSoundFont(path) => write!(
+                f,
+                "Error Parsing SoundFont: {:?} is a DLS soundbank, which is not supported by the synth. Please convert it to SF2.",
+                path.file_name().unwrap_or_default()
+            ),
+            WasabiError::SynthError(e) => write!(f, "Synth Error: {e}"),
+            WasabiError::FilesystemError(e) => write!(f, "Filesystem Error: {e}"),
+            WasabiError::SettingsError(e) => write!(f, "Settings Error: {e}"),
+            WasabiError::UpdaterError(e) => write!(f, "Update Error: {e}"),
+            WasabiError::PaletteError(e) => write!(f, "Palette Load Error: {e}"),
+            WasabiError::Cancelled => write!(f, "Cancelled"),
+            WasabiError::Other(e) => write!(f, "Unknown Error: {e}"),
+        }
+    }
+}
@@ -5,46 +5,95 @@ mod scene;
 mod stats;
 
 mod about;
+mod debug;
 mod errors;
+mod file_info;
 mod loading;
+mod note_tooltip;
 mod playback_panel;
 mod settings;
 mod shortcuts;
+mod tap_tempo;
+mod view_state;
 pub use errors::*;
 
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::thread;
 
 use egui::FontFamily::{Monospace, Proportional};
 use egui::FontId;
 use egui::Frame;
 pub use loading::*;
+pub use scene::ActiveNote;
 use settings::SettingsWindow;
 use time::Duration;
 use tokio::sync::{oneshot, oneshot::Receiver};
 
 use crate::{
+    audio_playback::WasabiAudioPlayer,
+    error::WasabiError,
     gui::{
         window::{keyboard::GuiKeyboard, scene::GuiRenderScene},
         GuiRenderer, GuiState,
     },
-    midi::{CakeMIDIFile, InRamMIDIFile, LiveLoadMIDIFile, MIDIFileBase, MIDIFileUnion},
-    settings::{MidiParsing, WasabiSettings},
+    midi::{
+        estimate_note_count, is_stdin_path, load_soundfonts, read_audio_blocks, CakeLoadHandle,
+        CakeMIDIFile, DebugEvent, InRamMIDIFile, LiveLoadMIDIFile, MIDIFileBase, MIDIFileUnion,
+        OfflineRenderer, DRUM_LANE_KEYS,
+    },
+    settings::{CatchUp, EndBehavior, MidiParsing, MidiSettings, WasabiSettings},
     state::WasabiState,
-    utils::NOTE_SPEED_RANGE,
+    utils::{self, NOTE_SPEED_RANGE},
 };
 
+pub type ActiveNotesCallback = Box<dyn Fn(&[ActiveNote])>;
+
+/// How far the audio may fall behind before a desync warning is shown
+const SYNC_TOLERANCE_MS: f64 = 100.0;
+
 pub struct GuiWasabiWindow {
     render_scene: GuiRenderScene,
+    keyboard_params: keyboard_layout::KeyboardParams,
     keyboard_layout: keyboard_layout::KeyboardLayout,
     keyboard: GuiKeyboard,
     midi_file: Option<MIDIFileUnion>,
     fps: fps::Fps,
+    smoothed_stats: stats::SmoothedStats,
+    capture_frame: u64,
+    capture_start: Duration,
+    /// Renders the audio of the capture next to the frames
+    capture_audio: Option<thread::JoinHandle<Result<(), WasabiError>>>,
 
     settings_win: SettingsWindow,
     midi_picker: Option<Receiver<PathBuf>>,
     midi_loader: Option<Receiver<MIDIFileUnion>>,
+    /// The Cake MIDI that is loading, with the base message of the loading window
+    cake_loader: Option<(CakeLoadHandle, String)>,
+    /// A second MIDI that is drawn behind the loaded one for comparing them.
+    /// It follows the time of the loaded MIDI. There is only one overlay,
+    /// it always shows all of its tracks and has no audio, since hearing
+    /// both files would need a second synth.
+    overlay: Option<InRamMIDIFile>,
+    overlay_picker: Option<Receiver<PathBuf>>,
+    overlay_loader: Option<Receiver<InRamMIDIFile>>,
+    /// The path that the poster gets saved to, see [`GuiRenderScene::export_poster`]
+    poster_picker: Option<Receiver<PathBuf>>,
+    /// The time that the MIDI that is loading starts at
+    open_at: Option<Duration>,
+    /// Whether the playback was paused because the window lost focus
+    paused_on_unfocus: bool,
+    /// The events listed in the debug window, with the time range they
+    /// were read from
+    debug_events: Option<(f64, f64, Vec<DebugEvent>)>,
+    debug_events_loader: Option<Receiver<(f64, f64, Vec<DebugEvent>)>>,
+    tap_tempo: tap_tempo::TapTempo,
+    /// The part of the MIDI that gets looped, set with the bracket keys
+    loop_markers: view_state::LoopMarkers,
+
+    /// The keys passed to [`WasabiState::on_active_notes`] the last time
+    active_notes: Vec<ActiveNote>,
 }
 
 impl GuiWasabiWindow {
@@ -69,14 +118,32 @@ impl GuiWasabiWindow {
 
         GuiWasabiWindow {
             render_scene: GuiRenderScene::new(renderer),
+            keyboard_params: Default::default(),
             keyboard_layout: keyboard_layout::KeyboardLayout::new(&Default::default()),
             keyboard: GuiKeyboard::new(),
             midi_file: None,
             fps: fps::Fps::new(),
+            smoothed_stats: stats::SmoothedStats::new(),
+            capture_frame: 0,
+            capture_start: Duration::ZERO,
+            capture_audio: None,
 
             settings_win,
             midi_picker: None,
+            poster_picker: None,
             midi_loader: None,
+            cake_loader: None,
+            overlay: None,
+            overlay_picker: None,
+            overlay_loader: None,
+            open_at: None,
+            paused_on_unfocus: false,
+            debug_events: None,
+            debug_events_loader: None,
+            tap_tempo: tap_tempo::TapTempo::new(),
+            loop_markers: Default::default(),
+
+            active_notes: Vec::new(),
         }
     }
 
@@ -154,30 +221,168 @@ impl GuiWasabiWindow {
         // Check for MIDIs selected by the file picker
         if let Some(recv) = self.midi_picker.as_mut() {
             if let Ok(midi) = recv.try_recv() {
-                state.last_midi_location = midi.clone();
+                settings.gui.last_midi_location = midi.clone();
                 self.load_midi(midi, settings, state);
                 self.midi_picker = None;
             }
         }
 
+        if let Some(recv) = self.overlay_picker.as_mut() {
+            if let Ok(midi) = recv.try_recv() {
+                settings.gui.last_midi_location = midi.clone();
+                self.load_overlay(midi, settings, state);
+                self.overlay_picker = None;
+            }
+        }
+
+        if let Some(recv) = self.overlay_loader.as_mut() {
+            if let Ok(overlay) = recv.try_recv() {
+                self.overlay = Some(overlay);
+                self.overlay_loader = None;
+            }
+        }
+
+        // Cake MIDIs report their progress while loading
+        let mut loaded = None;
+        if let Some((handle, message)) = self.cake_loader.as_mut() {
+            match handle.poll() {
+                Some(result) => {
+                    match result {
+                        Ok(midi) => {
+                            // Writing the trees can take a while for large MIDIs
+                            if let Some(writer) = midi.cache_writer() {
+                                let errors = state.errors.clone();
+                                thread::spawn(move || {
+                                    if let Err(e) = writer.save(&CakeMIDIFile::cache_dir()) {
+                                        errors
+                                            .warning(format!("Failed to save the Cake cache: {e}"));
+                                    }
+                                });
+                            }
+                            loaded = Some(MIDIFileUnion::Cake(midi));
+                        }
+                        Err(WasabiError::Cancelled) => {}
+                        Err(e) => state.errors.error(&e),
+                    }
+                    state.loading_status.clear();
+                    self.cake_loader = None;
+                }
+                None => {
+                    state.loading_status.update_message(format!(
+                        "{message}\n{} parsed",
+                        utils::convert_seconds_to_time_string(handle.parsed_seconds())
+                    ));
+                    if let Some(fraction) = handle.fraction() {
+                        state.loading_status.set_progress(fraction);
+                    }
+                }
+            }
+        }
+
         // Check for MIDIs parsed by the MIDI loader and play
         if let Some(recv) = self.midi_loader.as_mut() {
-            if let Ok(mut midi) = recv.try_recv() {
-                midi.timer_mut().play();
-                self.midi_file = Some(midi);
+            if let Ok(midi) = recv.try_recv() {
+                loaded = Some(midi);
                 self.midi_loader = None;
             }
         }
 
+        if let Some(mut midi) = loaded {
+            if let Some(open_at) = self.open_at.take() {
+                match midi.midi_length() {
+                    Some(length) if open_at.as_seconds_f64() > length => {
+                        state.errors.warning(format!(
+                            "The start time ({}) is past the end of the MIDI ({}).",
+                            utils::convert_seconds_to_time_string(open_at.as_seconds_f64()),
+                            utils::convert_seconds_to_time_string(length)
+                        ));
+                    }
+                    _ => midi.seek(open_at),
+                }
+            } else if let Some(first_note) = midi
+                .first_note_time()
+                .filter(|_| settings.midi.skip_leading_silence)
+            {
+                // The start delay is kept as a lead before the first note
+                let start = first_note + midi.timer().start_time().as_seconds_f64();
+                if start > midi.timer().get_time().as_seconds_f64() {
+                    midi.seek(Duration::seconds_f64(start));
+                }
+            }
+            if let Some(capture) = state.capture.as_ref() {
+                // Captured MIDIs don't play, they get stepped frame by frame
+                if let Err(e) = std::fs::create_dir_all(&capture.dir) {
+                    state.errors.error(&WasabiError::FilesystemError(e));
+                    state.capture = None;
+                }
+                self.capture_frame = 0;
+                self.capture_start = midi.timer().get_time();
+            }
+            if let Some(capture) = state.capture.as_ref() {
+                let path = midi.signature().filepath.clone();
+                if is_stdin_path(&path) {
+                    state
+                        .errors
+                        .warning("The audio of piped MIDIs can't be captured, only the frames");
+                } else {
+                    self.capture_audio = Some(Self::spawn_capture_audio(
+                        path,
+                        capture.dir.join("audio.wav"),
+                        self.capture_start.as_seconds_f64(),
+                        settings,
+                    ));
+                }
+            }
+            if state.capture.is_none() {
+                midi.timer_mut().play();
+            }
+            self.tap_tempo.reset();
+            if let (Some(margin), Some((first, last))) =
+                (settings.scene.auto_zoom_keyboard, midi.key_range())
+            {
+                let first = first.saturating_sub(margin);
+                let last = last
+                    .saturating_add(margin)
+                    .clamp(first.saturating_add(1), 254);
+                settings.scene.key_range = first..=last;
+            }
+            self.loop_markers = Default::default();
+            if settings.gui.restore_view_state {
+                match view_state::ViewState::load(midi.signature()) {
+                    Ok(Some(view)) => {
+                        view.apply(&mut settings.scene);
+                        let mut markers = view.loop_markers;
+                        if let Some(length) = midi.midi_length() {
+                            if markers.keep_within(length) {
+                                state.errors.warning(
+                                    "A saved loop marker is outside of the MIDI, so it was removed.",
+                                );
+                            }
+                        }
+                        self.loop_markers = markers;
+                    }
+                    Ok(None) => {}
+                    Err(e) => state.errors.error(&e),
+                }
+            }
+            self.midi_file = Some(midi);
+        }
+
         // If something is loading, pause playback and hide all windows
         if state.loading_status.is_loading() {
             if let Some(midi) = self.midi_file.as_mut() {
                 midi.timer_mut().pause();
             }
-            state.loading_status.show(&ctx);
+            if state.loading_status.show(&ctx) {
+                if let Some((handle, _)) = self.cake_loader.as_ref() {
+                    handle.cancel();
+                }
+            }
             state.show_about = false;
             state.show_settings = false;
             state.show_shortcuts = false;
+            state.show_file_info = false;
+            state.show_debug = false;
         }
 
         // Render windows
@@ -193,6 +398,14 @@ impl GuiWasabiWindow {
             self.show_shortcuts(&ctx, state);
         }
 
+        if state.show_file_info {
+            self.show_file_info(&ctx, settings, state);
+        }
+
+        if state.show_debug {
+            self.show_debug(&ctx, settings, state);
+        }
+
         // Set global keyboard shortcuts
         ctx.input(|events| {
             for event in &events.events {
@@ -207,7 +420,13 @@ impl GuiWasabiWindow {
                         match key {
                             egui::Key::F => state.panel_pinned = !state.panel_pinned,
                             egui::Key::G => state.stats_visible = !state.stats_visible,
-                            egui::Key::O => self.open_midi_dialog(state),
+                            egui::Key::O => self.open_midi_dialog(settings, state),
+                            egui::Key::L => {
+                                if self.overlay.take().is_none() {
+                                    self.open_overlay_dialog(settings, state);
+                                }
+                            }
+                            egui::Key::D => state.show_debug = !state.show_debug,
                             _ => {}
                         }
                     }
@@ -227,24 +446,61 @@ impl GuiWasabiWindow {
         // Calculate available space left for keyboard and notes
         // We must render notes before keyboard because the notes
         // renderer tells us the key colors
+        let first_key = *settings.scene.key_range.start();
+        let mut last_key = *settings.scene.key_range.end();
+        // The drum lane is always shown next to the regular keys
+        if settings.midi.drum_lane {
+            last_key = last_key.max(*DRUM_LANE_KEYS.end());
+        }
+
         let available = ctx.available_rect();
         let height = available.height();
-        let keyboard_height =
-            (11.6 / settings.scene.key_range.len() as f32 * available.width()).min(height / 2.0);
+        let key_count = (first_key..=last_key).len();
+        let keyboard_height = (11.6 / key_count as f32 * available.width()).min(height / 2.0);
         let notes_height = height - keyboard_height;
 
-        let key_view = self.keyboard_layout.get_view_for_keys(
-            *settings.scene.key_range.start() as usize,
-            *settings.scene.key_range.end() as usize,
-        );
+        // The notes use the same layout, so they stay aligned to the keys
+        let keyboard_params =
+            keyboard_layout::KeyboardParams::classic(settings.scene.black_key_width_frac);
+        if keyboard_params != self.keyboard_params {
+            self.keyboard_layout = keyboard_layout::KeyboardLayout::new(&keyboard_params);
+            self.keyboard_params = keyboard_params;
+        }
+
+        let key_view = self
+            .keyboard_layout
+            .get_view_for_keys(first_key as usize, last_key as usize);
 
         let no_frame = Frame::default()
             .inner_margin(egui::Margin::same(0.0))
             .fill(settings.scene.bg_color);
 
+        let samples = self
+            .render_scene
+            .set_sample_count(gui_state.renderer, settings.scene.msaa_samples);
+        if samples != settings.scene.msaa_samples {
+            state.errors.warning(format!(
+                "{}x MSAA is not supported by this device. Using {}x instead.",
+                settings.scene.msaa_samples, samples
+            ));
+            settings.scene.msaa_samples = samples;
+        }
+
+        // When capturing, advance exactly one frame regardless of real time
+        let capture_time = match (state.capture.as_ref(), self.midi_file.as_mut()) {
+            (Some(capture), Some(midi_file)) if !state.loading_status.is_loading() => {
+                let time = self.capture_start
+                    + Duration::seconds_f64(self.capture_frame as f64 / capture.fps as f64);
+                midi_file.timer_mut().set_time(time);
+                Some(time)
+            }
+            _ => None,
+        };
+
         let mut stats = stats::GuiMidiStats::empty();
 
         let mut render_result_data: Option<scene::RenderResultData> = None;
+        let mut reached_end = false;
 
         // Render the notes
         egui::TopBottomPanel::top("Note panel")
@@ -253,64 +509,221 @@ impl GuiWasabiWindow {
             .show_separator_line(false)
             .show(&ctx, |ui| {
                 if let Some(midi_file) = self.midi_file.as_mut() {
-                    // Set playback keyboard shortcuts
-                    ui.input(|events| {
-                        for event in &events.events {
-                            if let egui::Event::Key { key, pressed, .. } = event {
-                                if pressed == &true {
-                                    let skip_dur = Duration::seconds_f64(settings.gui.skip_control);
-                                    let time = midi_file.timer().get_time();
-
-                                    match key {
-                                        egui::Key::ArrowRight => {
-                                            midi_file.timer_mut().seek(time + skip_dur)
-                                        }
-                                        egui::Key::ArrowLeft => {
-                                            if midi_file.allows_seeking_backward() {
-                                                midi_file.timer_mut().seek((time - skip_dur).max(
-                                                    Duration::seconds_f64(
-                                                        -settings.midi.start_delay,
-                                                    ),
-                                                ))
+                    // Captured frames have to land on their exact times
+                    let seek_ease = settings.gui.seek_ease.filter(|_| state.capture.is_none());
+                    midi_file
+                        .timer_mut()
+                        .set_seek_ease(seek_ease.map(|ease| (ease, settings.gui.seek_ease_curve)));
+                    let catchup = match state.capture {
+                        Some(_) => CatchUp::RealTime,
+                        None => settings.gui.catchup,
+                    };
+                    midi_file.timer_mut().set_catchup(catchup);
+                    midi_file
+                        .timer_mut()
+                        .next_frame(ui.input(|i| i.stable_dt) as f64);
+
+                    // Set playback keyboard shortcuts, unless the keys are
+                    // typed into a text field
+                    if !ui.ctx().wants_keyboard_input() {
+                        ui.input(|events| {
+                            for event in &events.events {
+                                if let egui::Event::Key {
+                                    key,
+                                    pressed,
+                                    modifiers,
+                                    ..
+                                } = event
+                                {
+                                    if pressed == &true {
+                                        let skip_dur =
+                                            Duration::seconds_f64(settings.gui.skip_control);
+                                        let time = midi_file.timer().get_time();
+
+                                        match key {
+                                            egui::Key::ArrowRight => {
+                                                midi_file.seek(time + skip_dur)
+                                            }
+                                            egui::Key::ArrowLeft => {
+                                                if midi_file.allows_seeking_backward() {
+                                                    let start = midi_file.timer().start_time();
+                                                    midi_file.seek((time - skip_dur).max(start))
+                                                }
+                                            }
+                                            egui::Key::ArrowUp => {
+                                                settings.scene.note_speed =
+                                                    (settings.scene.note_speed
+                                                        + settings.gui.speed_control)
+                                                        .min(*NOTE_SPEED_RANGE.start());
+                                            }
+                                            egui::Key::ArrowDown => {
+                                                settings.scene.note_speed =
+                                                    (settings.scene.note_speed
+                                                        - settings.gui.speed_control)
+                                                        .max(*NOTE_SPEED_RANGE.end());
+                                            }
+                                            egui::Key::Space => {
+                                                midi_file.timer_mut().toggle_pause()
+                                            }
+                                            egui::Key::T if modifiers.shift => {
+                                                self.tap_tempo.reset()
+                                            }
+                                            egui::Key::T => self.tap_tempo.tap(midi_file),
+                                            // Loops have to seek back to their start
+                                            egui::Key::OpenBracket
+                                                if midi_file.allows_seeking_backward() =>
+                                            {
+                                                self.loop_markers.start =
+                                                    Some(time.as_seconds_f64())
+                                            }
+                                            egui::Key::CloseBracket
+                                                if midi_file.allows_seeking_backward() =>
+                                            {
+                                                self.loop_markers.end = Some(time.as_seconds_f64())
+                                            }
+                                            egui::Key::Backslash => {
+                                                self.loop_markers = Default::default()
+                                            }
+                                            _ => {
+                                                if let Some(digit) = utils::number_key_digit(*key) {
+                                                    midi_file.seek_to_fraction(digit as f64 / 10.0);
+                                                }
                                             }
                                         }
-                                        egui::Key::ArrowUp => {
-                                            settings.scene.note_speed = (settings.scene.note_speed
-                                                + settings.gui.speed_control)
-                                                .min(*NOTE_SPEED_RANGE.start());
-                                        }
-                                        egui::Key::ArrowDown => {
-                                            settings.scene.note_speed = (settings.scene.note_speed
-                                                - settings.gui.speed_control)
-                                                .max(*NOTE_SPEED_RANGE.end());
-                                        }
-                                        egui::Key::Space => midi_file.timer_mut().toggle_pause(),
-                                        _ => {}
                                     }
                                 }
                             }
+                        });
+                    }
+
+                    if state.capture.is_none() {
+                        self.tap_tempo
+                            .update(midi_file, ui.input(|i| i.stable_dt) as f64);
+                    }
+
+                    if let Some((start, end)) = self.loop_markers.range() {
+                        let timer = midi_file.timer();
+                        if !timer.is_paused()
+                            && timer.get_time().as_seconds_f64() >= end
+                            && midi_file.allows_seeking_backward()
+                        {
+                            midi_file.seek(Duration::seconds_f64(start));
                         }
-                    });
+                    }
+
+                    // If song is finished, apply the end behavior after rendering.
+                    // The last frame is held first, so the timer is clamped at the end.
+                    if let Some(length) = midi_file.midi_length() {
+                        let hold = settings.gui.end_hold_seconds.max(0.0);
+                        let timer = midi_file.timer_mut();
+                        timer.set_end_clamp((hold > 0.0).then(|| Duration::seconds_f64(length)));
+                        let current = timer.unclamped_time().as_seconds_f64();
+                        reached_end = current > length + hold && !timer.is_paused();
+                    }
 
-                    // If song is finished, pause
-                    let length = midi_file.midi_length().unwrap_or(0.0);
-                    let current = midi_file.timer().get_time().as_seconds_f64();
-                    if current > length {
-                        midi_file.timer_mut().pause();
+                    if let Some(overlay) = self.overlay.as_mut() {
+                        overlay.timer_mut().set_time(midi_file.timer().get_time());
                     }
 
+                    let notes_rect = ui.available_rect_before_wrap();
                     let result = self.render_scene.draw(
                         gui_state,
                         ui,
                         &key_view,
                         midi_file,
-                        settings.scene.note_speed,
+                        self.overlay.as_mut(),
+                        &settings.scene,
                     );
                     stats.set_rendered_note_count(result.notes_rendered);
+                    stats.set_drawn_note_count(result.notes_drawn);
+                    stats.set_subsampled(result.subsampled);
                     render_result_data = Some(result);
+
+                    if midi_file.timer().is_paused() {
+                        note_tooltip::show_note_tooltip(
+                            ui,
+                            notes_rect,
+                            &key_view,
+                            midi_file,
+                            &settings.scene,
+                        );
+                    }
                 }
             });
 
+        if reached_end {
+            self.handle_midi_end(settings, state);
+        }
+
+        if let Some(recv) = self.poster_picker.as_mut() {
+            if let Ok(path) = recv.try_recv() {
+                self.poster_picker = None;
+                if let Some(MIDIFileUnion::Cake(midi_file)) = self.midi_file.as_mut() {
+                    self.render_scene
+                        .export_poster(
+                            gui_state.renderer,
+                            &key_view,
+                            midi_file,
+                            &settings.scene,
+                            settings.gui.poster_size,
+                            &path,
+                        )
+                        .unwrap_or_else(|e| state.errors.error(&e));
+                }
+            }
+        }
+
+        if let (Some(capture), Some(time)) = (state.capture.as_ref(), capture_time) {
+            let path = capture
+                .dir
+                .join(format!("frame_{:06}.png", self.capture_frame));
+            let result =
+                self.render_scene
+                    .save_frame(gui_state.renderer, settings.scene.bg_color, &path);
+            self.capture_frame += 1;
+
+            let finished = self
+                .midi_file
+                .as_ref()
+                .and_then(|midi_file| midi_file.midi_length())
+                .is_some_and(|length| {
+                    time.as_seconds_f64() > length + settings.gui.end_hold_seconds.max(0.0)
+                });
+
+            if let Err(e) = result {
+                state.errors.error(&e);
+                state.capture = None;
+                self.capture_audio = None;
+            } else if finished {
+                // The audio is rendered faster than the frames, so this
+                // rarely has to wait
+                let audio_error = match self.capture_audio.take().map(|handle| handle.join()) {
+                    Some(Ok(Err(e))) => Some(e),
+                    Some(Err(_)) => Some(WasabiError::Other(
+                        "The audio capture thread panicked".to_string(),
+                    )),
+                    _ => None,
+                };
+                state.capture = None;
+                match audio_error {
+                    // Stay open so the error can be seen
+                    Some(e) => state.errors.error(&e),
+                    None => state.quit_requested = true,
+                }
+            }
+        }
+
+        if let Some(callback) = state.on_active_notes.as_ref() {
+            let active_notes = render_result_data
+                .as_ref()
+                .map(|data| data.active_notes())
+                .unwrap_or_default();
+            if active_notes != self.active_notes {
+                callback(&active_notes);
+                self.active_notes = active_notes;
+            }
+        }
+
         // Render the keyboard
         egui::TopBottomPanel::top("Keyboard panel")
             .height_range(keyboard_height..=keyboard_height)
@@ -323,14 +736,35 @@ impl GuiWasabiWindow {
                     vec![None; 256]
                 };
 
-                self.keyboard
-                    .draw(ui, &key_view, &colors, &settings.scene.bar_color);
+                let time = self
+                    .midi_file
+                    .as_ref()
+                    .map(|midi_file| midi_file.timer().get_time().as_seconds_f64())
+                    .unwrap_or_default();
+                let colors = self.keyboard.animate(
+                    &key_view,
+                    &colors,
+                    time,
+                    settings.scene.key_attack,
+                    settings.scene.key_release,
+                    settings.scene.key_retain,
+                );
+
+                self.keyboard.draw(
+                    ui,
+                    &key_view,
+                    &colors,
+                    &settings.scene.bar_color,
+                    settings.scene.black_key_height_frac,
+                );
             });
 
         // Render the stats
         if state.stats_visible {
             let voice_count = state.synth.voice_count();
             stats.set_voice_count(voice_count);
+            stats.set_render_load(state.synth.render_load());
+            stats.set_desynced(self.is_playing() && !state.synth.is_synced(SYNC_TOLERANCE_MS));
 
             let pad = if settings.scene.statistics.floating {
                 12.0
@@ -347,21 +781,121 @@ impl GuiWasabiWindow {
         self.fps.update();
     }
 
-    pub fn open_midi_dialog(&mut self, state: &mut WasabiState) {
+    /// Whether a MIDI is loaded and playing
+    pub fn is_playing(&self) -> bool {
+        self.midi_file
+            .as_ref()
+            .is_some_and(|midi_file| !midi_file.timer().is_paused())
+    }
+
+    /// Pauses the playback when the window loses focus (if enabled in the
+    /// settings) and resumes it once the focus is back. Playback that was
+    /// already paused stays paused.
+    pub fn set_focused(&mut self, focused: bool, settings: &WasabiSettings, state: &WasabiState) {
+        let Some(midi_file) = self.midi_file.as_mut() else {
+            self.paused_on_unfocus = false;
+            return;
+        };
+
+        if focused {
+            if self.paused_on_unfocus {
+                self.paused_on_unfocus = false;
+                midi_file.timer_mut().play();
+            }
+        } else if settings.gui.auto_pause_on_unfocus
+            && state.capture.is_none()
+            && !midi_file.timer().is_paused()
+        {
+            self.paused_on_unfocus = true;
+            midi_file.timer_mut().pause();
+            state.synth.reset();
+        }
+    }
+
+    fn handle_midi_end(&mut self, settings: &mut WasabiSettings, state: &mut WasabiState) {
+        let Some(midi_file) = self.midi_file.as_mut() else {
+            return;
+        };
+        let current_path = midi_file.signature().filepath.clone();
+
+        match settings.gui.on_end {
+            EndBehavior::Stop => {
+                midi_file.timer_mut().pause();
+                state.synth.reset();
+            }
+            EndBehavior::Loop => {
+                if midi_file.allows_seeking_backward() {
+                    let start = midi_file.timer().start_time();
+                    midi_file.seek(start);
+                } else {
+                    // Streamed MIDIs can't go back, so they get reloaded instead
+                    self.load_midi_at(current_path, None, settings, state);
+                }
+            }
+            EndBehavior::Next => match utils::find_next_midi(&current_path) {
+                Some(next) => {
+                    settings.gui.last_midi_location = next.clone();
+                    self.load_midi(next, settings, state);
+                }
+                None => {
+                    midi_file.timer_mut().pause();
+                    state.synth.reset();
+                }
+            },
+            EndBehavior::Quit => {
+                midi_file.timer_mut().pause();
+                state.synth.reset();
+                state.quit_requested = true;
+            }
+        }
+    }
+
+    pub fn open_midi_dialog(&mut self, settings: &WasabiSettings, state: &mut WasabiState) {
         // Do not open if something is loading already
         if state.loading_status.is_loading() {
             return;
         }
 
+        self.midi_picker = Some(Self::pick_midi_file(settings));
+    }
+
+    /// Opens the file picker for the overlay MIDI, see [`GuiWasabiWindow::load_overlay`]
+    pub fn open_overlay_dialog(&mut self, settings: &WasabiSettings, state: &mut WasabiState) {
+        if state.loading_status.is_loading() {
+            return;
+        }
+
+        self.overlay_picker = Some(Self::pick_midi_file(settings));
+    }
+
+    /// Opens the save dialog for [`GuiRenderScene::export_poster`]
+    fn pick_poster_path() -> Receiver<PathBuf> {
+        let (tx, rx) = oneshot::channel();
+
+        thread::spawn(move || {
+            let path = rfd::FileDialog::new()
+                .add_filter("png", &["png"])
+                .set_title("Export the poster as...")
+                .set_file_name("poster.png")
+                .save_file();
+
+            if let Some(path) = path {
+                tx.send(path).unwrap_or_default();
+            }
+        });
+
+        rx
+    }
+
+    fn pick_midi_file(settings: &WasabiSettings) -> Receiver<PathBuf> {
         let (tx, rx) = oneshot::channel();
-        self.midi_picker = Some(rx);
-        let last_location = state.last_midi_location.clone();
+        let last_location = settings.gui.last_midi_location.clone();
 
         // Open the file picker in a thread so the main UI thread does not freeze
         // and send the selected path via crossbeam
         thread::spawn(move || {
             let midi_path = rfd::FileDialog::new()
-                .add_filter("mid", &["mid", "MID"])
+                .add_filter("mid", &["mid", "MID", "rmi", "RMI"])
                 .set_title("Pick a MIDI file...")
                 .set_directory(last_location.parent().unwrap_or(Path::new("./")))
                 .pick_file();
@@ -370,6 +904,43 @@ impl GuiWasabiWindow {
                 tx.send(midi_path).unwrap_or_default();
             }
         });
+
+        rx
+    }
+
+    /// Loads a MIDI that gets drawn behind the loaded one, tinted with
+    /// [`crate::settings::SceneSettings::overlay_tint`]. It always uses the
+    /// Standard (RAM) algorithm and never plays audio.
+    pub fn load_overlay(
+        &mut self,
+        midi_path: PathBuf,
+        settings: &WasabiSettings,
+        state: &WasabiState,
+    ) {
+        self.overlay = None;
+
+        let filename = midi_path.file_name().unwrap_or_default().to_os_string();
+        state.loading_status.create(
+            loading::LoadingType::Midi,
+            format!("Parsing {:?} as the muted overlay", filename),
+        );
+
+        let settings = settings.midi.clone();
+        let loading_status = state.loading_status.clone();
+        let errors = state.errors.clone();
+
+        let (tx, rx) = oneshot::channel();
+        self.overlay_loader = Some(rx);
+
+        thread::spawn(move || {
+            match InRamMIDIFile::load_from_file(midi_path, None, &settings) {
+                Ok(midi) => {
+                    tx.send(midi).ok();
+                }
+                Err(e) => errors.error(&e),
+            }
+            loading_status.clear();
+        });
     }
 
     pub fn load_midi(
@@ -378,23 +949,108 @@ impl GuiWasabiWindow {
         settings: &mut WasabiSettings,
         state: &WasabiState,
     ) {
+        // Reopening the loaded MIDI continues where it was
+        let open_at = self
+            .midi_file
+            .as_ref()
+            .filter(|midi_file| {
+                settings.gui.keep_position_on_reload
+                    && midi_file.signature().is_same_file(&midi_path)
+            })
+            .map(|midi_file| midi_file.timer().get_time());
+
+        self.load_midi_at(midi_path, open_at, settings, state);
+    }
+
+    /// Renders the audio of a captured MIDI from `start` on, with its own
+    /// synth, see [`OfflineRenderer`]
+    fn spawn_capture_audio(
+        midi_path: PathBuf,
+        wav_path: PathBuf,
+        start: f64,
+        settings: &WasabiSettings,
+    ) -> thread::JoinHandle<Result<(), WasabiError>> {
+        let midi_settings = settings.midi.clone();
+        let synth_settings = settings.synth.clone();
+        thread::spawn(move || {
+            let blocks = read_audio_blocks(&midi_path, &midi_settings)?;
+            let soundfonts = load_soundfonts(&synth_settings)?;
+            OfflineRenderer::new(&synth_settings, soundfonts)
+                .render_to_wav(&blocks, &wav_path, start)
+        })
+    }
+
+    /// Loads a MIDI that starts playing at `open_at` instead of the beginning
+    pub fn load_midi_at(
+        &mut self,
+        midi_path: PathBuf,
+        open_at: Option<Duration>,
+        settings: &mut WasabiSettings,
+        state: &WasabiState,
+    ) {
+        self.open_at = open_at;
+        self.paused_on_unfocus = false;
+        self.debug_events = None;
+        self.debug_events_loader = None;
+
+        if let Some((handle, _)) = self.cake_loader.take() {
+            handle.cancel();
+        }
+
         // Unload current MIDI to free resources while loading the new one
         if let Some(mut midi_file) = self.midi_file.take() {
             midi_file.timer_mut().pause();
         }
 
-        let filename = midi_path.file_name().unwrap_or_default().to_os_string();
+        let message = if is_stdin_path(&midi_path) {
+            "Parsing MIDI from stdin".to_owned()
+        } else {
+            let filename = midi_path.file_name().unwrap_or_default().to_os_string();
+            format!("Parsing {:?}", filename)
+        };
 
-        state.loading_status.create(
-            loading::LoadingType::Midi,
-            format!("Parsing {:?}", filename),
-        );
+        state
+            .loading_status
+            .create(loading::LoadingType::Midi, message.clone());
 
-        let synth = state.synth.clone();
+        // Without audio, the loaders skip building the audio blocks entirely
+        let synth = settings.synth.audio_enabled.then(|| state.synth.clone());
         let settings = settings.midi.clone();
         let loading_status = state.loading_status.clone();
         let errors = state.errors.clone();
 
+        let cake = match settings.parsing {
+            MidiParsing::Cake => true,
+            MidiParsing::Auto => estimate_note_count(&midi_path)
+                .is_some_and(|notes| notes >= settings.auto_cake_threshold),
+            MidiParsing::Ram | MidiParsing::Live => false,
+        };
+
+        if cake {
+            state.loading_status.set_cancellable();
+            self.cake_loader = Some((
+                CakeMIDIFile::load_async(midi_path, synth, &settings, None, None),
+                message,
+            ));
+            return;
+        }
+
+        type LoadFn = fn(
+            &str,
+            Option<Arc<WasabiAudioPlayer>>,
+            &MidiSettings,
+        ) -> Result<MIDIFileUnion, WasabiError>;
+        // Auto uses Standard (RAM) for the MIDIs that are too small for Cake
+        let load: LoadFn = if settings.parsing == MidiParsing::Live {
+            |path, synth, settings| {
+                LiveLoadMIDIFile::load_from_file(path, synth, settings).map(MIDIFileUnion::Live)
+            }
+        } else {
+            |path, synth, settings| {
+                InRamMIDIFile::load_from_file(path, synth, settings).map(MIDIFileUnion::InRam)
+            }
+        };
+
         let (tx, rx) = oneshot::channel();
         self.midi_loader = Some(rx);
 
@@ -402,38 +1058,13 @@ impl GuiWasabiWindow {
         // via crossbeam
         thread::spawn(move || {
             if let Some(midi_path) = midi_path.to_str() {
-                match settings.parsing {
-                    MidiParsing::Ram => {
-                        match InRamMIDIFile::load_from_file(midi_path, synth, &settings) {
-                            Ok(midi) => {
-                                let midi_file = MIDIFileUnion::InRam(midi);
-                                tx.send(midi_file).ok();
-                            }
-                            Err(e) => errors.error(&e),
-                        }
-                        loading_status.clear();
-                    }
-                    MidiParsing::Live => {
-                        match LiveLoadMIDIFile::load_from_file(midi_path, synth, &settings) {
-                            Ok(midi) => {
-                                let midi_file = MIDIFileUnion::Live(midi);
-                                tx.send(midi_file).ok();
-                            }
-                            Err(e) => errors.error(&e),
-                        }
-                        loading_status.clear();
-                    }
-                    MidiParsing::Cake => {
-                        match CakeMIDIFile::load_from_file(midi_path, synth, &settings) {
-                            Ok(midi) => {
-                                let midi_file = MIDIFileUnion::Cake(midi);
-                                tx.send(midi_file).ok();
-                            }
-                            Err(e) => errors.error(&e),
-                        }
-                        loading_status.clear();
+                match load(midi_path, synth, &settings) {
+                    Ok(midi_file) => {
+                        tx.send(midi_file).ok();
                     }
+                    Err(e) => errors.error(&e),
                 }
+                loading_status.clear();
             }
         });
     }
@@ -1,7 +1,7 @@
-use crate::{state::WasabiState, utils};
+use crate::{error::WasabiError, state::WasabiState, utils};
 use std::env::consts::{ARCH, OS};
 
-use super::{GuiWasabiWindow, WasabiError};
+use super::GuiWasabiWindow;
 
 impl GuiWasabiWindow {
     pub fn show_about(&mut self, ctx: &egui::Context, state: &mut WasabiState) {
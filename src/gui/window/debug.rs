@@ -0,0 +1,221 @@
+use std::thread;
+
+use tokio::sync::oneshot;
+
+use crate::{
+    midi::{read_debug_events, DebugEvent, MIDIFileBase, MIDIFileUnion},
+    settings::WasabiSettings,
+    state::WasabiState,
+    utils::{self, convert_seconds_to_time_string},
+};
+
+use super::GuiWasabiWindow;
+
+fn format_event(event: &DebugEvent) -> String {
+    format!(
+        "{:>10.4}s  tick {:>10}  track {:>5}  {}{}",
+        event.time,
+        event.tick,
+        event.track,
+        event.description,
+        if event.skipped { " (skipped)" } else { "" }
+    )
+}
+
+impl GuiWasabiWindow {
+    pub fn show_debug(
+        &mut self,
+        ctx: &egui::Context,
+        settings: &WasabiSettings,
+        state: &mut WasabiState,
+    ) {
+        if let Some(recv) = self.debug_events_loader.as_mut() {
+            match recv.try_recv() {
+                Ok(events) => {
+                    self.debug_events = Some(events);
+                    self.debug_events_loader = None;
+                }
+                // Reading failed, the error was already shown
+                Err(oneshot::error::TryRecvError::Closed) => self.debug_events_loader = None,
+                Err(oneshot::error::TryRecvError::Empty) => {}
+            }
+        }
+
+        let frame = utils::create_window_frame(ctx);
+        let size = [360.0, 200.0];
+
+        egui::Window::new("Debug Info")
+            .collapsible(false)
+            .title_bar(true)
+            .scroll([false, true])
+            .enabled(true)
+            .frame(frame)
+            .fixed_size(size)
+            .open(&mut state.show_debug)
+            .show(ctx, |ui| {
+                let Some(midi_file) = self.midi_file.as_ref() else {
+                    ui.vertical_centered(|ui| ui.label("No MIDI is loaded."));
+                    return;
+                };
+
+                let col_width = size[0] / 2.0;
+                ui.heading("Timing");
+                egui::Grid::new("debug_timing_grid")
+                    .num_columns(2)
+                    .min_col_width(col_width)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        let timer = midi_file.timer();
+                        let midi_time = timer.get_time().as_seconds_f64();
+                        let wall_time = timer.wall_elapsed().as_seconds_f64();
+                        let start_time = timer.start_time().as_seconds_f64();
+
+                        ui.label("MIDI Time:");
+                        ui.monospace(convert_seconds_to_time_string(midi_time));
+                        ui.end_row();
+
+                        ui.label("Real Time Elapsed:");
+                        ui.monospace(convert_seconds_to_time_string(wall_time));
+                        ui.end_row();
+
+                        ui.label("Drift:")
+                            .on_hover_text("Only accurate if playback wasn't paused or seeked");
+                        ui.monospace(format!("{:.3}s", midi_time - start_time - wall_time));
+                        ui.end_row();
+
+                        ui.label("Playback Speed:")
+                            .on_hover_text("Set by tapping the tempo with T");
+                        ui.monospace(format!("{:.3}x", timer.speed()));
+                        ui.end_row();
+                    });
+
+                ui.add_space(8.0);
+                ui.heading("Audio");
+                egui::Grid::new("debug_audio_grid")
+                    .num_columns(2)
+                    .min_col_width(col_width)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Queued Events:").on_hover_text(
+                            "Only available for MIDI devices, other synths queue events internally",
+                        );
+                        ui.monospace(
+                            state
+                                .synth
+                                .queued_events()
+                                .map(|events| events.to_string())
+                                .unwrap_or("-".into()),
+                        );
+                        ui.end_row();
+
+                        ui.label("Audio Lag:")
+                            .on_hover_text("How far the last played events were behind the timer");
+                        ui.monospace(format!("{:.3}s", state.synth.audio_lag()));
+                        ui.end_row();
+                    });
+
+                if let Some(report) = midi_file.parse_report() {
+                    ui.add_space(8.0);
+                    ui.heading("Parsing");
+                    egui::Grid::new("debug_parsing_grid")
+                        .num_columns(2)
+                        .min_col_width(col_width)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.label("SMPTE Division:").on_hover_text(
+                                "The timing of the file is read as ticks per quarter note anyway",
+                            );
+                            ui.monospace(if report.smpte_division { "Yes" } else { "No" });
+                            ui.end_row();
+
+                            ui.label("Velocity 0 Note Ons:")
+                                .on_hover_text("These get loaded as note offs");
+                            ui.monospace(report.zero_velocity_note_ons.to_string());
+                            ui.end_row();
+
+                            ui.label("Overlapping Notes:").on_hover_text(
+                                "Notes that start while the same key is already playing \
+                                on the same track and channel",
+                            );
+                            ui.monospace(report.overlapping_notes.to_string());
+                            ui.end_row();
+
+                            ui.label("Unmatched Note Offs:").on_hover_text(
+                                "Note offs without a playing note, they are ignored",
+                            );
+                            ui.monospace(report.unmatched_note_offs.to_string());
+                            ui.end_row();
+
+                            ui.label("Unended Notes:")
+                                .on_hover_text("Notes that get ended at the end of the MIDI");
+                            ui.monospace(report.unended_notes.to_string());
+                            ui.end_row();
+                        });
+                }
+
+                if let MIDIFileUnion::Cake(midi_file) = midi_file {
+                    ui.add_space(8.0);
+                    ui.heading("Cake Trees");
+                    egui::Grid::new("debug_cake_grid")
+                        .num_columns(2)
+                        .min_col_width(col_width)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            let stats = midi_file.tree_stats();
+
+                            ui.label("Max Tree Depth:").on_hover_text(
+                                "The renderer stops searching a tree after 100 levels",
+                            );
+                            ui.monospace(stats.depth.to_string());
+                            ui.end_row();
+
+                            ui.label("Tree Nodes:");
+                            ui.monospace(stats.nodes.to_string());
+                            ui.end_row();
+                        });
+                }
+
+                ui.add_space(8.0);
+                ui.heading("Events");
+                ui.label("Lists all the events of the notes on screen.");
+                let loading = self.debug_events_loader.is_some();
+                if ui
+                    .add_enabled(!loading, egui::Button::new("Dump Visible Events"))
+                    .clicked()
+                {
+                    let start = midi_file.timer().get_time().as_seconds_f64();
+                    let end = start + settings.scene.note_speed;
+                    let signature = midi_file.signature().clone();
+                    let errors = state.errors.clone();
+
+                    let (tx, rx) = oneshot::channel();
+                    self.debug_events_loader = Some(rx);
+                    thread::spawn(move || match read_debug_events(&signature, start, end) {
+                        Ok(events) => {
+                            tx.send((start, end, events)).ok();
+                        }
+                        Err(e) => errors.error(&e),
+                    });
+                }
+
+                if loading {
+                    ui.label("Reading the events...");
+                } else if let Some((start, end, events)) = self.debug_events.as_ref() {
+                    ui.label(format!(
+                        "{} events between {start:.3}s and {end:.3}s:",
+                        events.len()
+                    ));
+
+                    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                    egui::ScrollArea::both()
+                        .id_salt("debug_events")
+                        .max_height(200.0)
+                        .show_rows(ui, row_height, events.len(), |ui, rows| {
+                            for event in &events[rows] {
+                                ui.monospace(format_event(event));
+                            }
+                        });
+                }
+            });
+    }
+}
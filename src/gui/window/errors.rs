@@ -1,47 +1,11 @@
 use std::{
     env::consts::{ARCH, OS},
-    fmt,
     sync::{Arc, Mutex},
 };
 
 use egui::{Context, Id, OpenUrl, WidgetText};
-use midi_toolkit::io::MIDILoadError;
-use xsynth_core::soundfont::LoadSfError;
-
-use crate::utils;
-
-#[derive(Debug)]
-pub enum WasabiError {
-    MidiLoadError(MIDILoadError),
-    SoundFontLoadError(LoadSfError),
-    SynthError(String),
-    FilesystemError(std::io::Error),
-    SettingsError(String),
-    UpdaterError(String),
-    PaletteError(String),
-    Other(String),
-}
 
-impl fmt::Display for WasabiError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            WasabiError::MidiLoadError(e) => match e {
-                MIDILoadError::CorruptChunks => write!(f, "MIDI Load Error: Corrupt Chunks"),
-                MIDILoadError::FilesystemError(fs) => {
-                    write!(f, "MIDI Load Error: Filesystem Error ({fs})")
-                }
-                MIDILoadError::FileTooBig => write!(f, "MIDI Load Error: File Too Big"),
-            },
-            WasabiError::SoundFontLoadError(e) => write!(f, "Error Parsing SoundFont: {e}"),
-            WasabiError::SynthError(e) => write!(f, "Synth Error: {e}"),
-            WasabiError::FilesystemError(e) => write!(f, "Filesystem Error: {e}"),
-            WasabiError::SettingsError(e) => write!(f, "Settings Error: {e}"),
-            WasabiError::UpdaterError(e) => write!(f, "Update Error: {e}"),
-            WasabiError::PaletteError(e) => write!(f, "Palette Load Error: {e}"),
-            WasabiError::Other(e) => write!(f, "Unknown Error: {e}"),
-        }
-    }
-}
+use crate::{error::WasabiError, utils};
 
 enum MessageType {
     Warning,
@@ -92,6 +56,17 @@ impl GuiMessageSystem {
         });
     }
 
+    /// Removes all the messages and returns their text, for the callers
+    /// that don't show them
+    pub fn take_messages(&self) -> Vec<String> {
+        self.errors
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|message| message.message.text().to_string())
+            .collect()
+    }
+
     pub fn new_update(&self, version: impl Into<String>) {
         let version: String = version.into();
 
@@ -0,0 +1,315 @@
+use std::thread;
+
+use crate::{
+    midi::{export_tempo_changes, MIDIColor, MIDIFileBase},
+    settings::WasabiSettings,
+    state::WasabiState,
+    utils::{self, convert_seconds_to_time_string},
+};
+
+use super::GuiWasabiWindow;
+
+/// The legend can have an entry for every track and channel, so the list
+/// gets cut off to keep the window responsive
+const MAX_LEGEND_ENTRIES: usize = 256;
+/// The number of bars of the velocity chart
+const VELOCITY_BINS: usize = 8;
+
+impl GuiWasabiWindow {
+    pub fn show_file_info(
+        &mut self,
+        ctx: &egui::Context,
+        settings: &mut WasabiSettings,
+        state: &mut WasabiState,
+    ) {
+        let frame = utils::create_window_frame(ctx);
+        let size = [400.0, 300.0];
+
+        egui::Window::new("File Info")
+            .collapsible(false)
+            .title_bar(true)
+            .scroll([false, true])
+            .enabled(true)
+            .frame(frame)
+            .fixed_size(size)
+            .open(&mut state.show_file_info)
+            .show(ctx, |ui| {
+                let Some(midi_file) = self.midi_file.as_ref() else {
+                    ui.vertical_centered(|ui| ui.label("No MIDI is loaded."));
+                    return;
+                };
+
+                let col_width = size[0] / 2.0;
+                egui::Grid::new("file_info_grid")
+                    .num_columns(2)
+                    .min_col_width(col_width)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        let signature = midi_file.signature();
+
+                        ui.label("File:");
+                        ui.label(
+                            signature
+                                .filepath
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy(),
+                        );
+                        ui.end_row();
+
+                        ui.label("Algorithm:");
+                        ui.label(midi_file.parsing().as_str());
+                        ui.end_row();
+
+                        ui.label("Size:");
+                        ui.label(format!("{} bytes", signature.length_in_bytes));
+                        ui.end_row();
+
+                        ui.label("PPQ:");
+                        ui.label(match signature.ppq_override {
+                            Some(ppq) => format!("{} (overridden to {ppq})", midi_file.ppq()),
+                            None => midi_file.ppq().to_string(),
+                        });
+                        ui.end_row();
+
+                        ui.label("Length:");
+                        ui.label(
+                            midi_file
+                                .midi_length()
+                                .map(convert_seconds_to_time_string)
+                                .unwrap_or("-".into()),
+                        );
+                        ui.end_row();
+
+                        ui.label("Note Count:");
+                        ui.label(
+                            midi_file
+                                .stats()
+                                .total_notes
+                                .map(|n| n.to_string())
+                                .unwrap_or("-".into()),
+                        );
+                        ui.end_row();
+
+                        ui.label("Note Range:");
+                        ui.label(
+                            midi_file
+                                .key_range()
+                                .map(|(first, last)| format!("{first} - {last}"))
+                                .unwrap_or("-".into()),
+                        );
+                        ui.end_row();
+
+                        if let Some(load_stats) = midi_file.load_stats() {
+                            ui.label("Load Time:");
+                            ui.label(format!("{:.0} ms", load_stats.parse_ms));
+                            ui.end_row();
+
+                            ui.label("Note Build Time:");
+                            ui.label(format!("{:.0} ms", load_stats.tree_build_ms));
+                            ui.end_row();
+
+                            ui.label("Audio Build Time:");
+                            ui.label(
+                                load_stats
+                                    .audio_build_ms
+                                    .map(|ms| format!("{ms:.0} ms"))
+                                    .unwrap_or("-".into()),
+                            );
+                            ui.end_row();
+                        }
+                    });
+
+                if let Some(tracks) = midi_file.notes_per_track() {
+                    ui.add_space(8.0);
+                    egui::CollapsingHeader::new(format!("Notes per Track ({})", tracks.len()))
+                        .show(ui, |ui| {
+                            egui::Grid::new("track_notes_grid")
+                                .num_columns(2)
+                                .min_col_width(col_width)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for (track, notes) in tracks.iter().enumerate() {
+                                        ui.label(format!("Track {}:", track));
+                                        ui.horizontal(|ui| {
+                                            ui.label(notes.to_string());
+
+                                            let mut opacity =
+                                                settings.scene.track_opacity(track as u32);
+                                            let slider = ui
+                                                .add(egui::Slider::new(&mut opacity, 0.0..=1.0))
+                                                .on_hover_text("The opacity of the notes");
+                                            if slider.changed() {
+                                                let opacities = &mut settings.scene.track_opacity;
+                                                if opacities.len() <= track {
+                                                    opacities.resize(track + 1, 1.0);
+                                                }
+                                                opacities[track] = opacity;
+                                            }
+                                        });
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+
+                let histogram = midi_file.velocity_histogram(VELOCITY_BINS);
+                if !histogram.is_empty() {
+                    ui.add_space(8.0);
+                    egui::CollapsingHeader::new("Velocities").show(ui, |ui| {
+                        let (rect, _) = ui.allocate_exact_size(
+                            egui::vec2(ui.available_width(), 60.0),
+                            egui::Sense::hover(),
+                        );
+                        let painter = ui.painter_at(rect);
+                        let max = histogram.iter().copied().max().unwrap_or(0).max(1);
+                        let bar_width = rect.width() / histogram.len() as f32;
+                        let bin_size = 128 / histogram.len();
+
+                        for (i, count) in histogram.iter().enumerate() {
+                            let height = *count as f32 / max as f32 * rect.height();
+                            let bar = egui::Rect::from_min_max(
+                                egui::pos2(
+                                    rect.left() + i as f32 * bar_width + 1.0,
+                                    rect.bottom() - height,
+                                ),
+                                egui::pos2(
+                                    rect.left() + (i + 1) as f32 * bar_width - 1.0,
+                                    rect.bottom(),
+                                ),
+                            );
+                            painter.rect_filled(bar, 0.0, ui.visuals().selection.bg_fill);
+
+                            let start = i * bin_size;
+                            if ui.rect_contains_pointer(bar.with_min_y(rect.top())) {
+                                egui::show_tooltip_at_pointer(
+                                    ui.ctx(),
+                                    ui.layer_id(),
+                                    egui::Id::new("velocity_bin_tooltip"),
+                                    |ui| {
+                                        ui.label(format!(
+                                            "Velocity {} - {}: {} notes",
+                                            start,
+                                            start + bin_size - 1,
+                                            count
+                                        ))
+                                    },
+                                );
+                            }
+                        }
+                    });
+                }
+
+                if let Some(instruments) = midi_file.instruments() {
+                    ui.add_space(8.0);
+                    egui::CollapsingHeader::new(format!("Instruments ({})", instruments.len()))
+                        .show(ui, |ui| {
+                            egui::Grid::new("instruments_grid")
+                                .num_columns(2)
+                                .min_col_width(col_width)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for instrument in instruments {
+                                        ui.label(format!("Channel {}:", instrument.channel + 1));
+                                        ui.label(instrument.name());
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+
+                let legend = midi_file.color_legend();
+                if !legend.is_empty() {
+                    ui.add_space(8.0);
+                    egui::CollapsingHeader::new(format!("Color Legend ({})", legend.len())).show(
+                        ui,
+                        |ui| {
+                            for (color, label) in legend.iter().take(MAX_LEGEND_ENTRIES) {
+                                ui.horizontal(|ui| {
+                                    let (rect, _) = ui.allocate_exact_size(
+                                        egui::vec2(12.0, 12.0),
+                                        egui::Sense::hover(),
+                                    );
+                                    ui.painter().rect_filled(
+                                        rect,
+                                        2.0,
+                                        egui::Color32::from_rgb(
+                                            color.red(),
+                                            color.green(),
+                                            color.blue(),
+                                        ),
+                                    );
+                                    ui.label(label);
+                                });
+                            }
+                            if legend.len() > MAX_LEGEND_ENTRIES {
+                                ui.label(format!(
+                                    "... and {} more",
+                                    legend.len() - MAX_LEGEND_ENTRIES
+                                ));
+                            }
+                        },
+                    );
+                }
+
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if let Some((first, last)) = midi_file.key_range() {
+                        if ui.button("Fit Keyboard to Notes").clicked() {
+                            settings.scene.key_range = first..=last.max(first.saturating_add(1));
+                        }
+                    }
+
+                    if ui
+                        .button("Export Colors")
+                        .on_hover_text("Saves the color of each track and channel as a PNG")
+                        .clicked()
+                    {
+                        let colors = midi_file.track_colors().to_vec();
+                        let errors = state.errors.clone();
+
+                        // Pick the path in a thread so the UI doesn't freeze
+                        thread::spawn(move || {
+                            let path = rfd::FileDialog::new()
+                                .add_filter("png", &["png"])
+                                .set_title("Export the colors as...")
+                                .set_file_name("colors.png")
+                                .save_file();
+
+                            if let Some(path) = path {
+                                MIDIColor::export_swatches(&colors, &path)
+                                    .unwrap_or_else(|e| errors.error(&e));
+                            }
+                        });
+                    }
+
+                    if let Some(changes) = midi_file.tempo_changes() {
+                        if ui
+                            .button("Export Tempo Map")
+                            .on_hover_text(
+                                "Saves the tempo and time signature changes as a CSV or JSON file",
+                            )
+                            .clicked()
+                        {
+                            let changes = changes.to_vec();
+                            let errors = state.errors.clone();
+
+                            thread::spawn(move || {
+                                let path = rfd::FileDialog::new()
+                                    .add_filter("csv", &["csv"])
+                                    .add_filter("json", &["json"])
+                                    .set_title("Export the tempo map as...")
+                                    .set_file_name("tempo_map.csv")
+                                    .save_file();
+
+                                if let Some(path) = path {
+                                    export_tempo_changes(&changes, &path)
+                                        .unwrap_or_else(|e| errors.error(&e));
+                                }
+                            });
+                        }
+                    }
+                });
+            });
+    }
+}
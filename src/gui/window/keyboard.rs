@@ -4,11 +4,99 @@ use crate::midi::MIDIColor;
 
 use super::keyboard_layout::KeyboardView;
 
-pub struct GuiKeyboard {}
+/// How long a jump in time has to be to skip the key animations, in seconds
+const MAX_ANIMATION_STEP: f64 = 0.5;
+
+/// How much of the last note color a released key keeps while it's retained
+const RETAIN_TINT: f32 = 0.25;
+
+#[derive(Clone, Copy)]
+struct KeyAnimation {
+    color: MIDIColor,
+    amount: f32,
+    /// How long the key keeps a tint of its last color, in seconds
+    retain: f32,
+}
+
+pub struct GuiKeyboard {
+    keys: Vec<KeyAnimation>,
+    last_time: Option<f64>,
+}
 
 impl GuiKeyboard {
     pub fn new() -> GuiKeyboard {
-        GuiKeyboard {}
+        GuiKeyboard {
+            keys: vec![
+                KeyAnimation {
+                    color: MIDIColor::new(0, 0, 0),
+                    amount: 0.0,
+                    retain: 0.0,
+                };
+                256
+            ],
+            last_time: None,
+        }
+    }
+
+    /// Fades the pressed keys in over `attack` seconds and out over `release`
+    /// seconds of MIDI time and returns the colors that should be drawn.
+    /// Released keys keep a faint tint of their last color for `retain`
+    /// seconds before fading out completely.
+    /// The animations are skipped when the time jumps, e.g. after seeking.
+    pub fn animate(
+        &mut self,
+        key_view: &KeyboardView,
+        colors: &[Option<MIDIColor>],
+        time: f64,
+        attack: f32,
+        release: f32,
+        retain: f32,
+    ) -> Vec<Option<MIDIColor>> {
+        let delta = time - self.last_time.unwrap_or(time);
+        self.last_time = Some(time);
+        let skip = !(0.0..=MAX_ANIMATION_STEP).contains(&delta);
+
+        let step = |duration: f32| {
+            if skip || duration <= 0.0 {
+                1.0
+            } else {
+                delta as f32 / duration
+            }
+        };
+        let (attack_step, release_step) = (step(attack), step(release));
+
+        self.keys
+            .iter_mut()
+            .zip(colors.iter())
+            .enumerate()
+            .map(|(i, (key, color))| {
+                if let Some(color) = color {
+                    key.color = *color;
+                    key.amount = (key.amount + attack_step).min(1.0);
+                    key.retain = retain;
+                } else if key.retain > 0.0 && !skip {
+                    key.retain -= delta as f32;
+                    let tint = RETAIN_TINT.min(key.amount);
+                    key.amount = (key.amount - release_step).max(tint);
+                } else {
+                    key.retain = 0.0;
+                    key.amount = (key.amount - release_step).max(0.0);
+                }
+
+                if key.amount <= 0.0 {
+                    return None;
+                }
+
+                // Fade from the color of the unpressed key
+                let base = if key_view.key(i).black { 20.0 } else { 255.0 };
+                let mix = |c: u8| (base + (c as f32 - base) * key.amount) as u8;
+                Some(MIDIColor::new(
+                    mix(key.color.red()),
+                    mix(key.color.green()),
+                    mix(key.color.blue()),
+                ))
+            })
+            .collect()
     }
 
     pub fn draw(
@@ -17,6 +105,7 @@ impl GuiKeyboard {
         key_view: &KeyboardView,
         colors: &[Option<MIDIColor>],
         bar_color: &Color32,
+        black_key_height: f32,
     ) {
         let (rect, _) = ui.allocate_exact_size(ui.available_size(), Sense::click());
         let mut mesh = Mesh::default();
@@ -30,7 +119,7 @@ impl GuiKeyboard {
         let black_key_overlap = bar / 2.35;
         let top = rect.top() + bar;
         let bottom = rect.bottom();
-        let black_bottom = rect.bottom() - rect.height() * 0.34;
+        let black_bottom = rect.top() + rect.height() * black_key_height;
         let map_x = |num: f32| rect.left() + num * rect.width();
         fn map_color(col: MIDIColor) -> Color32 {
             Color32::from_rgb(col.red(), col.green(), col.blue())
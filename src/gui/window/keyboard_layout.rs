@@ -12,16 +12,24 @@ pub enum KeyboardParams {
     },
 }
 
-impl Default for KeyboardParams {
-    fn default() -> Self {
+impl KeyboardParams {
+    /// The classic layout with the given black key width (relative to the
+    /// width of a white key)
+    pub fn classic(black_key_scale: f32) -> Self {
         KeyboardParams::Classic {
             black_key_2_set_offset: 0.35,
             black_key_3_set_offset: 0.45,
-            black_key_scale: 0.74,
+            black_key_scale,
         }
     }
 }
 
+impl Default for KeyboardParams {
+    fn default() -> Self {
+        Self::classic(0.74)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct KeyPosition {
     pub black: bool,
@@ -272,4 +280,14 @@ impl<'a> KeyboardView<'a> {
     pub fn iter_all_notes(&self) -> impl '_ + Iterator<Item = KeyPosition> {
         (0..257).map(|i| self.note(i))
     }
+
+    /// The note column at `x`, from 0 (left) to 1 (right). Black notes are
+    /// drawn over the white ones, so they are checked first.
+    pub fn note_at_x(&self, x: f32) -> Option<usize> {
+        let contains = |note: &KeyPosition| note.left <= x && x < note.right;
+        self.iter_visible_notes()
+            .find(|(_, note)| note.black && contains(note))
+            .or_else(|| self.iter_visible_notes().find(|(_, note)| contains(note)))
+            .map(|(key, _)| key)
+    }
 }
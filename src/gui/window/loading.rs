@@ -8,6 +8,9 @@ use crate::utils;
 struct StatusInfoHolder {
     title: String,
     message: String,
+    cancellable: bool,
+    /// Shown as a progress bar, from 0 to 1
+    progress: Option<f32>,
 }
 
 pub enum LoadingType {
@@ -35,6 +38,8 @@ impl LoadingStatus {
         *self.0.write().unwrap() = Some(StatusInfoHolder {
             title: loading_type.to_string(),
             message,
+            cancellable: false,
+            progress: None,
         });
     }
 
@@ -48,11 +53,27 @@ impl LoadingStatus {
         }
     }
 
+    /// Shows a cancel button, see [`LoadingStatus::show`]
+    pub fn set_cancellable(&self) {
+        if let Some(info) = self.0.write().unwrap().as_mut() {
+            info.cancellable = true;
+        }
+    }
+
+    /// Shows a progress bar below the message, see [`LoadingStatus::show`]
+    pub fn set_progress(&self, progress: f32) {
+        if let Some(info) = self.0.write().unwrap().as_mut() {
+            info.progress = Some(progress.clamp(0.0, 1.0));
+        }
+    }
+
     pub fn clear(&self) {
         *self.0.write().unwrap() = None;
     }
 
-    pub fn show(&self, ctx: &Context) {
+    /// Returns true if the cancel button was clicked
+    pub fn show(&self, ctx: &Context) -> bool {
+        let mut cancel = false;
         if let Some(info) = self.0.read().unwrap().as_ref() {
             let frame = utils::create_window_frame(ctx);
 
@@ -74,7 +95,14 @@ impl LoadingStatus {
                         );
                         ui.label(&info.message);
                     });
+                    if let Some(progress) = info.progress {
+                        ui.add(egui::ProgressBar::new(progress).show_percentage());
+                    }
+                    if info.cancellable {
+                        ui.vertical_centered(|ui| cancel = ui.button("Cancel").clicked());
+                    }
                 });
         }
+        cancel
     }
 }
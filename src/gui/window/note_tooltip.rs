@@ -0,0 +1,81 @@
+use egui::Rect;
+
+use crate::{
+    midi::{key_name, MIDIFileBase, MIDIFileUnion},
+    settings::SceneSettings,
+};
+
+use super::keyboard_layout::KeyboardView;
+
+/// Shows the details of the note under the cursor. The notes only hold
+/// still while the playback is paused, so this is only called then.
+pub fn show_note_tooltip(
+    ui: &egui::Ui,
+    rect: Rect,
+    key_view: &KeyboardView,
+    midi_file: &MIDIFileUnion,
+    settings: &SceneSettings,
+) {
+    let Some(pos) = ui.input(|i| i.pointer.hover_pos()) else {
+        return;
+    };
+    if !rect.contains(pos) || rect.width() <= 0.0 || rect.height() <= 0.0 {
+        return;
+    }
+
+    let x = (pos.x - rect.left()) / rect.width();
+    let Some(lane) = key_view.note_at_x(x) else {
+        return;
+    };
+
+    // The keyboard is at the bottom, so the time increases upwards
+    let y = ((rect.bottom() - pos.y) / rect.height()) as f64;
+    let time = midi_file.timer().get_time().as_seconds_f64() + y * settings.note_speed;
+
+    // With a key remap, several keys can be drawn on the lane
+    let Some(note) = (0..256)
+        .filter(|key| settings.key_lane(*key) == lane)
+        .find_map(|key| midi_file.note_at(key as u8, time))
+    else {
+        return;
+    };
+
+    egui::show_tooltip_at_pointer(
+        ui.ctx(),
+        ui.layer_id(),
+        egui::Id::new("note_tooltip"),
+        |ui| {
+            egui::Grid::new("note_tooltip_grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Key:");
+                    ui.label(key_name(note.key));
+                    ui.end_row();
+
+                    ui.label("Start:");
+                    ui.label(format!("{:.3}s", note.start));
+                    ui.end_row();
+
+                    ui.label("Length:");
+                    ui.label(format!("{:.3}s", note.length));
+                    ui.end_row();
+
+                    ui.label("Velocity:");
+                    ui.label(note.velocity.to_string());
+                    ui.end_row();
+
+                    if let Some(track) = note.track {
+                        ui.label("Track:");
+                        ui.label(track.to_string());
+                        ui.end_row();
+                    }
+
+                    if let Some(channel) = note.channel {
+                        ui.label("Channel:");
+                        ui.label((channel + 1).to_string());
+                        ui.end_row();
+                    }
+                });
+        },
+    );
+}
@@ -1,11 +1,11 @@
-use super::GuiWasabiWindow;
+use super::{view_state::ViewState, GuiWasabiWindow};
 
 use time::Duration;
 
 use egui::{popup_below_widget, PopupCloseBehavior};
 
 use crate::{
-    midi::MIDIFileBase,
+    midi::{MIDIFileBase, MIDIFileUnion},
     settings::WasabiSettings,
     state::WasabiState,
     utils::{self, convert_seconds_to_time_string},
@@ -46,6 +46,7 @@ impl GuiWasabiWindow {
 
         // If panel is collapsed, do not render it.
         if height < f32::EPSILON {
+            state.synth.set_audio_suppressed(false);
             return 0.0;
         }
 
@@ -75,7 +76,7 @@ impl GuiWasabiWindow {
                         .on_hover_text("Open MIDI")
                         .clicked()
                     {
-                        self.open_midi_dialog(state);
+                        self.open_midi_dialog(settings, state);
                     }
 
                     // Unload button
@@ -142,14 +143,16 @@ impl GuiWasabiWindow {
                     ui.add_space(SPACE);
 
                     // Progress bar
-                    let (time_passed, time_total) = if let Some(midi) = self.midi_file.as_ref() {
-                        (
-                            midi.timer().get_time().as_seconds_f64(),
-                            midi.midi_length().unwrap_or(0.0),
-                        )
-                    } else {
-                        (0.0, 0.0)
-                    };
+                    let (time_passed, position, time_total) =
+                        if let Some(midi) = self.midi_file.as_ref() {
+                            (
+                                midi.timer().get_time().as_seconds_f64(),
+                                midi.timer().position().as_seconds_f64(),
+                                midi.midi_length().unwrap_or(0.0),
+                            )
+                        } else {
+                            (0.0, 0.0, 0.0)
+                        };
 
                     let mut timeid = ui
                         .style()
@@ -158,7 +161,7 @@ impl GuiWasabiWindow {
                         .unwrap()
                         .clone();
                     timeid.size = 16.0;
-                    let time_text = convert_seconds_to_time_string(time_passed);
+                    let time_text = convert_seconds_to_time_string(position);
                     let time_galley = ui.painter().layout_no_wrap(
                         time_text.clone(),
                         timeid.clone(),
@@ -189,23 +192,42 @@ impl GuiWasabiWindow {
                         if let Some(length) = midi_file.midi_length() {
                             let mut time = midi_file.timer().get_time().as_seconds_f64();
                             let time_prev = time;
+                            let allows_seeking_backward = midi_file.allows_seeking_backward();
+                            let start = midi_file.timer().start_time().as_seconds_f64();
 
-                            ui.add(
-                                egui::Slider::new(&mut time, -settings.midi.start_delay..=length)
-                                    .show_value(false),
+                            let slider = ui.add(
+                                egui::Slider::new(&mut time, start..=length).show_value(false),
                             );
-                            if (time_prev != time)
-                                && (midi_file.allows_seeking_backward() || time_prev < time)
-                            {
-                                midi_file.timer_mut().seek(Duration::seconds_f64(time));
+                            let scrubbing = settings.gui.mute_while_scrubbing && slider.dragged();
+                            if !allows_seeking_backward {
+                                slider.on_hover_text(
+                                    "This MIDI loading algorithm can only seek forward",
+                                );
+                                // Keep the handle from jumping back while dragging
+                                time = time.max(time_prev);
                             }
+                            if time_prev != time {
+                                midi_file.seek(Duration::seconds_f64(time));
+                            }
+                            // Only cleared after the last seek of the drag, so
+                            // the audio resumes from where the slider got
+                            // released instead of from the previous position
+                            state.synth.set_audio_suppressed(scrubbing);
                         } else {
                             empty_slider();
                         }
                     } else {
                         empty_slider();
                     }
-                    ui.label(egui::RichText::new(remaining_text).font(timeid.clone()));
+                    let remaining_label =
+                        ui.label(egui::RichText::new(remaining_text).font(timeid.clone()));
+                    if let Some(notes) = self
+                        .midi_file
+                        .as_ref()
+                        .and_then(|midi| midi.stats().remaining_notes())
+                    {
+                        remaining_label.on_hover_text(format!("{notes} notes remaining"));
+                    }
 
                     ui.add_space(SPACE);
                     ui.separator();
@@ -234,6 +256,37 @@ impl GuiWasabiWindow {
                             if ui.button("Settings").clicked() {
                                 state.show_settings = true;
                             }
+                            if ui.button("File Info").clicked() {
+                                state.show_file_info = true;
+                            }
+                            if let Some(MIDIFileUnion::Cake(midi_file)) = self.midi_file.as_mut() {
+                                if ui.button("Jump to Densest Part").clicked() {
+                                    let time = midi_file.peak_density_time();
+                                    midi_file.seek(time);
+                                }
+                                if ui
+                                    .button("Export Poster")
+                                    .on_hover_text("Saves the densest part as a PNG")
+                                    .clicked()
+                                {
+                                    self.poster_picker = Some(Self::pick_poster_path());
+                                }
+                            }
+                            if let Some(midi_file) = self.midi_file.as_ref() {
+                                if ui
+                                    .button("Save View State")
+                                    .on_hover_text(
+                                        "Saves the loop markers, the note speed and the \
+                                        keyboard range for this MIDI",
+                                    )
+                                    .clicked()
+                                {
+                                    let view = ViewState::new(self.loop_markers, &settings.scene);
+                                    if let Err(e) = view.save(midi_file.signature()) {
+                                        state.errors.error(&e);
+                                    }
+                                }
+                            }
                             if ui.button("Shortcuts").clicked() {
                                 state.show_shortcuts = true;
                             }
@@ -1,14 +1,31 @@
 mod cake_system;
+mod capture;
+mod glow;
 mod note_list_system;
+mod render_targets;
 
-use egui::{Image, Ui};
+use std::{path::Path, sync::Arc};
+
+use egui::{Align2, Color32, FontId, Image, Mesh, Pos2, Rect, Stroke, Ui};
+use vulkano::{
+    image::{view::ImageView, Image, ImageCreateInfo, ImageUsage, SampleCount},
+    memory::allocator::StandardMemoryAllocator,
+};
 
 use crate::{
-    midi::{MIDIColor, MIDIFileUnion},
+    error::WasabiError,
+    midi::{
+        CakeMIDIFile, InRamMIDIFile, MIDIColor, MIDIFileBase, MIDIFileUnion, NoteId, DRUM_LANE_KEYS,
+    },
     scenes::SceneSwapchain,
+    settings::{GridConfig, GridMode, HitLineConfig, SceneSettings},
+    utils::convert_seconds_to_time_string,
 };
 
-use self::{cake_system::CakeRenderer, note_list_system::NoteRenderer};
+use self::{
+    cake_system::CakeRenderer, capture::FrameCapture, glow::GlowPass,
+    note_list_system::NoteRenderer,
+};
 
 use super::{keyboard_layout::KeyboardView, GuiRenderer, GuiState};
 
@@ -19,11 +36,16 @@ enum CurrentRenderer {
 }
 
 impl CurrentRenderer {
-    fn get_note_renderer(&mut self, renderer: &GuiRenderer) -> &mut NoteRenderer {
+    fn get_note_renderer(
+        &mut self,
+        renderer: &GuiRenderer,
+        samples: SampleCount,
+        transparent: bool,
+    ) -> &mut NoteRenderer {
         match self {
             CurrentRenderer::Note(renderer) => renderer,
             _ => {
-                let renderer = NoteRenderer::new(renderer);
+                let renderer = NoteRenderer::new(renderer, samples, transparent);
                 *self = CurrentRenderer::Note(renderer);
                 match self {
                     CurrentRenderer::Note(renderer) => renderer,
@@ -33,11 +55,16 @@ impl CurrentRenderer {
         }
     }
 
-    fn get_cake_renderer(&mut self, renderer: &GuiRenderer) -> &mut CakeRenderer {
+    fn get_cake_renderer(
+        &mut self,
+        renderer: &GuiRenderer,
+        samples: SampleCount,
+        transparent: bool,
+    ) -> &mut CakeRenderer {
         match self {
             CurrentRenderer::Cake(renderer) => renderer,
             _ => {
-                let renderer = CakeRenderer::new(renderer);
+                let renderer = CakeRenderer::new(renderer, samples, transparent);
                 *self = CurrentRenderer::Cake(renderer);
                 match self {
                     CurrentRenderer::Cake(renderer) => renderer,
@@ -51,11 +78,68 @@ impl CurrentRenderer {
 pub struct GuiRenderScene {
     swap_chain: SceneSwapchain,
     draw_system: CurrentRenderer,
+    samples: SampleCount,
+    transparent: bool,
+    glow: Option<GlowPass>,
+    /// Draws the overlay MIDI, which is always single sampled and blended
+    /// behind the notes that were already drawn
+    overlay_renderer: Option<NoteRenderer>,
+    capture: Option<FrameCapture>,
+    last_frame: Option<Arc<ImageView>>,
 }
 
 pub struct RenderResultData {
     pub notes_rendered: u64,
+    /// The notes that were submitted to the GPU and are visible, without the
+    /// empty spaces of hidden notes
+    pub notes_drawn: u64,
+    /// Whether only part of the notes were drawn, see
+    /// [`SceneSettings::max_visible_notes`]
+    pub subsampled: bool,
     pub key_colors: Vec<Option<MIDIColor>>,
+    pub key_note_ids: Vec<Option<NoteId>>,
+}
+
+/// A key that is currently pressed, with the color of the note playing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveNote {
+    pub key: u8,
+    pub color: MIDIColor,
+    pub note_id: NoteId,
+}
+
+/// Moves the values of each key onto the lane it is drawn on, see
+/// [`SceneSettings::key_remap`]. When keys share a lane, the lowest one wins.
+fn remap_to_lanes<T: Copy>(values: Vec<Option<T>>, settings: &SceneSettings) -> Vec<Option<T>> {
+    if settings.key_remap.is_none() {
+        return values;
+    }
+
+    let mut lanes = vec![None; values.len()];
+    for (key, value) in values.into_iter().enumerate() {
+        let lane = &mut lanes[settings.key_lane(key)];
+        if lane.is_none() {
+            *lane = value;
+        }
+    }
+    lanes
+}
+
+impl RenderResultData {
+    pub fn active_notes(&self) -> Vec<ActiveNote> {
+        self.key_colors
+            .iter()
+            .zip(self.key_note_ids.iter())
+            .enumerate()
+            .filter_map(|(key, (color, note_id))| {
+                Some(ActiveNote {
+                    key: key as u8,
+                    color: (*color)?,
+                    note_id: (*note_id)?,
+                })
+            })
+            .collect()
+    }
 }
 
 impl GuiRenderScene {
@@ -63,43 +147,320 @@ impl GuiRenderScene {
         Self {
             swap_chain: SceneSwapchain::new(renderer.device.clone()),
             draw_system: CurrentRenderer::None,
+            samples: SampleCount::Sample1,
+            transparent: false,
+            glow: None,
+            overlay_renderer: None,
+            capture: None,
+            last_frame: None,
         }
     }
 
+    /// Sets the MSAA sample count, falling back to the closest one supported
+    /// by the device. The renderers get recreated if the count has changed.
+    /// Returns the sample count that is actually used.
+    pub fn set_sample_count(&mut self, renderer: &GuiRenderer, requested: u32) -> u32 {
+        let samples = render_targets::supported_sample_count(&renderer.device, requested);
+        if samples != self.samples {
+            self.samples = samples;
+            self.draw_system = CurrentRenderer::None;
+        }
+
+        samples as u32
+    }
+
     pub fn draw(
         &mut self,
         state: &mut GuiState,
         ui: &mut Ui,
         key_view: &KeyboardView,
         midi_file: &mut MIDIFileUnion,
-        view_range: f64,
+        overlay: Option<&mut InRamMIDIFile>,
+        settings: &SceneSettings,
     ) -> RenderResultData {
         let size = ui.available_size();
         let size = [size.x as u32, size.y as u32];
 
+        // Transparent notes need different pipelines
+        let transparent =
+            settings.has_transparent_notes(matches!(midi_file, MIDIFileUnion::Cake(_)));
+        if transparent != self.transparent {
+            self.transparent = transparent;
+            self.draw_system = CurrentRenderer::None;
+        }
+
         let scene_image = self.swap_chain.get_next_image(state, size);
         let frame = scene_image.image.clone();
+        let glow_frame = frame.clone();
+        let overlay_frame = frame.clone();
+
+        let mut result = match midi_file {
+            MIDIFileUnion::InRam(file) => {
+                let notes = file.render_frame(settings.note_speed);
+                self.draw_system
+                    .get_note_renderer(state.renderer, self.samples, self.transparent)
+                    .draw(key_view, frame, &notes, settings, true, None)
+            }
+
+            MIDIFileUnion::Live(file) => {
+                let notes = file.render_frame(settings.note_speed);
+                self.draw_system
+                    .get_note_renderer(state.renderer, self.samples, self.transparent)
+                    .draw(key_view, frame, &notes, settings, true, None)
+            }
 
-        let result = match midi_file {
-            MIDIFileUnion::InRam(file) => self
-                .draw_system
-                .get_note_renderer(state.renderer)
-                .draw(key_view, frame, file, view_range),
-
-            MIDIFileUnion::Live(file) => self
-                .draw_system
-                .get_note_renderer(state.renderer)
-                .draw(key_view, frame, file, view_range),
-
-            MIDIFileUnion::Cake(file) => self
-                .draw_system
-                .get_cake_renderer(state.renderer)
-                .draw(key_view, frame, file, view_range),
+            MIDIFileUnion::Cake(file) => {
+                let time = file.current_time().as_seconds_f64();
+                self.draw_system
+                    .get_cake_renderer(state.renderer, self.samples, self.transparent)
+                    .draw(key_view, frame, file, settings, time)
+            }
         };
 
+        if let Some(overlay) = overlay {
+            let tint = settings.overlay_tint;
+            let notes = overlay.render_frame(settings.note_speed);
+            let overlay_result = self
+                .overlay_renderer
+                .get_or_insert_with(|| {
+                    NoteRenderer::new(state.renderer, SampleCount::Sample1, true)
+                })
+                .draw(
+                    key_view,
+                    overlay_frame,
+                    &notes,
+                    settings,
+                    false,
+                    Some(MIDIColor::new(tint.r(), tint.g(), tint.b())),
+                );
+            result.notes_rendered += overlay_result.notes_rendered;
+            result.notes_drawn += overlay_result.notes_drawn;
+            result.subsampled |= overlay_result.subsampled;
+        }
+
+        if settings.glow {
+            self.glow
+                .get_or_insert_with(|| GlowPass::new(state.renderer))
+                .draw(glow_frame, settings);
+        }
+
+        self.last_frame = Some(scene_image.image.clone());
+
+        // The notes are drawn over a transparent image, so anything painted
+        // before it ends up behind them
+        let rect = Rect::from_min_size(ui.cursor().min, ui.available_size());
+        if settings.octave_lines {
+            Self::draw_octave_lines(ui, rect, key_view, settings);
+        }
+
+        let grid_lines = settings.time_grid.as_ref().map(|grid| {
+            let start = midi_file.timer().get_time().as_seconds_f64();
+            let lines =
+                Self::time_grid_lines(midi_file, grid, start, start + settings.note_speed, rect);
+
+            let painter = ui.painter_at(rect);
+            for (y, _) in lines.iter() {
+                painter.hline(rect.x_range(), *y, Stroke::new(1.0, grid.color));
+            }
+            lines
+        });
+
         let img = Image::new((scene_image.id, [size[0] as f32, size[1] as f32].into()));
         ui.add(img);
 
+        // The labels go over the notes so they stay readable
+        if let (Some(grid), Some(lines)) = (settings.time_grid.as_ref(), grid_lines) {
+            let painter = ui.painter_at(rect);
+            for (y, label) in lines {
+                painter.text(
+                    Pos2::new(rect.left() + 4.0, y - 1.0),
+                    Align2::LEFT_BOTTOM,
+                    label,
+                    FontId::monospace(12.0),
+                    grid.color.to_opaque(),
+                );
+            }
+        }
+
+        if let Some(hit_line) = settings.hit_line.as_ref() {
+            Self::draw_hit_line(ui, rect, hit_line);
+        }
+
         result
     }
+
+    /// Draws the hit line along the bottom of `rect`, where the notes reach
+    /// the keyboard
+    fn draw_hit_line(ui: &Ui, rect: Rect, hit_line: &HitLineConfig) {
+        let painter = ui.painter_at(rect);
+        let thickness = hit_line.thickness.max(0.5);
+        let line_top = rect.bottom() - thickness;
+
+        if hit_line.glow {
+            // A vertical gradient is a lot cheaper than an actual blur
+            let glow_top = line_top - thickness * 8.0;
+            let [r, g, b, _] = hit_line.color.to_array();
+            let mut mesh = Mesh::default();
+            mesh.colored_vertex(Pos2::new(rect.left(), glow_top), Color32::TRANSPARENT);
+            mesh.colored_vertex(Pos2::new(rect.right(), glow_top), Color32::TRANSPARENT);
+            let glow_color = Color32::from_rgba_unmultiplied(r, g, b, 96);
+            mesh.colored_vertex(Pos2::new(rect.left(), line_top), glow_color);
+            mesh.colored_vertex(Pos2::new(rect.right(), line_top), glow_color);
+            mesh.add_triangle(0, 1, 2);
+            mesh.add_triangle(1, 2, 3);
+            painter.add(mesh);
+        }
+
+        painter.rect_filled(
+            Rect::from_min_max(Pos2::new(rect.left(), line_top), rect.right_bottom()),
+            0.0,
+            hit_line.color,
+        );
+    }
+
+    /// The screen positions and labels of the time grid lines between `start`
+    /// and `end` (in seconds), mapped the same way as the notes
+    fn time_grid_lines(
+        midi_file: &MIDIFileUnion,
+        grid: &GridConfig,
+        start: f64,
+        end: f64,
+        rect: Rect,
+    ) -> Vec<(f32, String)> {
+        let to_y =
+            |time: f64| rect.bottom() - ((time - start) / (end - start)) as f32 * rect.height();
+
+        let mut lines = Vec::new();
+        match grid.mode {
+            GridMode::Seconds => {
+                // Keeps the number of lines reasonable on slow note speeds
+                let interval = grid.interval.max(0.01);
+                let mut index = (start.max(0.0) / interval).ceil() as u64;
+                while (index as f64 * interval) <= end && lines.len() < 1000 {
+                    let time = index as f64 * interval;
+                    lines.push((to_y(time), convert_seconds_to_time_string(time)));
+                    index += 1;
+                }
+            }
+            GridMode::Bars => {
+                let interval = (grid.interval.round() as u64).max(1);
+                let Some(beats) = midi_file.beats() else {
+                    return lines;
+                };
+
+                let mut bar = 0;
+                for beat in beats.iter().filter(|beat| beat.downbeat) {
+                    bar += 1;
+                    if beat.time > end {
+                        break;
+                    }
+                    if beat.time >= start && (bar - 1) % interval == 0 {
+                        lines.push((to_y(beat.time), format!("Bar {}", bar)));
+                    }
+                }
+            }
+        }
+
+        lines
+    }
+
+    /// Draws a line at the left edge of every C and every marked key
+    fn draw_octave_lines(ui: &Ui, rect: Rect, key_view: &KeyboardView, settings: &SceneSettings) {
+        let painter = ui.painter_at(rect);
+        let width =
+            crate::utils::calculate_border_width(rect.width(), key_view.visible_range.len() as f32)
+                .max(1.0);
+
+        for (key, position) in key_view.iter_visible_keys() {
+            // The drum lane keys aren't pitches
+            if key >= *DRUM_LANE_KEYS.start() as usize {
+                break;
+            }
+
+            let width = if settings.marked_keys.contains(&(key as u8)) {
+                width * 2.0
+            } else if key % 12 == 0 {
+                width
+            } else {
+                continue;
+            };
+
+            let x = rect.left() + position.left * rect.width();
+            painter.vline(
+                x,
+                rect.y_range(),
+                Stroke::new(width, settings.octave_line_color),
+            );
+        }
+    }
+
+    /// Renders the MIDI at the start of its densest second into a new image
+    /// of `size` and saves it as a PNG. The timer isn't touched, so playback
+    /// carries on as before. Only Cake MIDIs know their note density.
+    pub fn export_poster(
+        &mut self,
+        renderer: &GuiRenderer,
+        key_view: &KeyboardView,
+        midi_file: &mut CakeMIDIFile,
+        settings: &SceneSettings,
+        size: [u32; 2],
+        path: &Path,
+    ) -> Result<(), WasabiError> {
+        // The image has to match the format that the pipelines were made for
+        let Some(format) = self.last_frame.as_ref().map(|frame| frame.format()) else {
+            return Err(WasabiError::Other(
+                "The poster can only be exported after the first frame".to_string(),
+            ));
+        };
+
+        let allocator = Arc::new(StandardMemoryAllocator::new_default(
+            renderer.device.clone(),
+        ));
+        let image = Image::new(
+            allocator,
+            ImageCreateInfo {
+                format,
+                extent: [size[0].max(1), size[1].max(1), 1],
+                usage: ImageUsage::SAMPLED
+                    | ImageUsage::COLOR_ATTACHMENT
+                    | ImageUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            Default::default(),
+        )
+        .map_err(|e| WasabiError::Other(format!("Failed to create the poster image: {e}")))?;
+        let image = ImageView::new_default(image)
+            .map_err(|e| WasabiError::Other(format!("Failed to create the poster image: {e}")))?;
+
+        let time = midi_file.peak_density_time().as_seconds_f64();
+        self.draw_system
+            .get_cake_renderer(renderer, self.samples, self.transparent)
+            .draw(key_view, image.clone(), midi_file, settings, time);
+
+        if settings.glow {
+            self.glow
+                .get_or_insert_with(|| GlowPass::new(renderer))
+                .draw(image.clone(), settings);
+        }
+
+        self.capture
+            .get_or_insert_with(|| FrameCapture::new(renderer))
+            .save(image, settings.bg_color, path)
+    }
+
+    /// Saves the last drawn frame as a PNG
+    pub fn save_frame(
+        &mut self,
+        renderer: &GuiRenderer,
+        bg_color: Color32,
+        path: &Path,
+    ) -> Result<(), WasabiError> {
+        let Some(frame) = self.last_frame.clone() else {
+            return Ok(());
+        };
+
+        self.capture
+            .get_or_insert_with(|| FrameCapture::new(renderer))
+            .save(frame, bg_color, path)
+    }
 }
@@ -11,13 +11,10 @@ use vulkano::{
         allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
     },
     device::{Device, Queue},
-    format::Format,
-    image::{view::ImageView, Image, ImageCreateInfo, ImageUsage},
+    image::{view::ImageView, SampleCount},
     memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
     pipeline::{
         graphics::{
-            color_blend::{ColorBlendAttachmentState, ColorBlendState},
-            depth_stencil::{DepthState, DepthStencilState},
             input_assembly::{InputAssemblyState, PrimitiveTopology},
             multisample::MultisampleState,
             rasterization::RasterizationState,
@@ -35,13 +32,17 @@ use vulkano::{
 
 use crate::{
     gui::{
-        window::keyboard_layout::{KeyPosition, KeyboardView},
+        window::{
+            keyboard_layout::{KeyPosition, KeyboardView},
+            scene::render_targets::{create_render_pass, note_blend_states, RenderTargets},
+        },
         GuiRenderer,
     },
-    midi::{CakeBlock, CakeMIDIFile, CakeSignature, IntVector4},
+    midi::{CakeBlock, CakeMIDIFile, CakeSignature, IntVector4, NoteId},
+    settings::SceneSettings,
 };
 
-use super::RenderResultData;
+use super::{remap_to_lanes, RenderResultData};
 
 const BUFFER_ARRAY_LEN: u64 = 256;
 
@@ -117,7 +118,7 @@ pub struct CakeRenderer {
     pipeline_clear: Arc<GraphicsPipeline>,
     render_pass_clear: Arc<RenderPass>,
     allocator: Arc<StandardMemoryAllocator>,
-    depth_buffer: Arc<ImageView>,
+    targets: RenderTargets,
     cb_allocator: StandardCommandBufferAllocator,
     sd_allocator: StandardDescriptorSetAllocator,
     buffers_init: Subbuffer<[CakeNoteColumn]>,
@@ -125,52 +126,17 @@ pub struct CakeRenderer {
 }
 
 impl CakeRenderer {
-    pub fn new(renderer: &GuiRenderer) -> CakeRenderer {
+    pub fn new(renderer: &GuiRenderer, samples: SampleCount, transparent: bool) -> CakeRenderer {
         let allocator = Arc::new(StandardMemoryAllocator::new_default(
             renderer.device.clone(),
         ));
 
         let gfx_queue = renderer.queue.clone();
 
-        let render_pass_clear = vulkano::ordered_passes_renderpass!(gfx_queue.device().clone(),
-            attachments: {
-                final_color: {
-                    format: renderer.format,
-                    samples: 1,
-                    load_op: Clear,
-                    store_op: Store,
-                },
-                depth: {
-                    format: Format::D16_UNORM,
-                    samples: 1,
-                    load_op: Clear,
-                    store_op: Store,
-                }
-            },
-            passes: [
-                {
-                    color: [final_color],
-                    depth_stencil: {depth},
-                    input: []
-                }
-            ]
-        )
-        .unwrap();
+        let render_pass_clear =
+            create_render_pass(gfx_queue.device().clone(), renderer.format, samples, true);
 
-        let depth_buffer = ImageView::new_default(
-            Image::new(
-                allocator.clone(),
-                ImageCreateInfo {
-                    extent: [1, 1, 1],
-                    format: Format::D16_UNORM,
-                    usage: ImageUsage::SAMPLED | ImageUsage::DEPTH_STENCIL_ATTACHMENT,
-                    ..Default::default()
-                },
-                Default::default(),
-            )
-            .unwrap(),
-        )
-        .unwrap();
+        let targets = RenderTargets::new(allocator.clone(), renderer.format, samples);
 
         let vs = vs::load(gfx_queue.device().clone())
             .expect("failed to create shader module")
@@ -201,6 +167,8 @@ impl CakeRenderer {
         )
         .unwrap();
         let subpass = Subpass::from(render_pass_clear.clone(), 0).unwrap();
+        let (color_blend_state, depth_stencil_state) =
+            note_blend_states(subpass.num_color_attachments(), transparent);
 
         let pipeline_clear = GraphicsPipeline::new(
             renderer.device.clone(),
@@ -215,15 +183,12 @@ impl CakeRenderer {
                 viewport_state: Some(Default::default()),
                 dynamic_state: [DynamicState::Viewport].into_iter().collect(),
                 rasterization_state: Some(RasterizationState::default()),
-                multisample_state: Some(MultisampleState::default()),
-                color_blend_state: Some(ColorBlendState::with_attachment_states(
-                    subpass.num_color_attachments(),
-                    ColorBlendAttachmentState::default(),
-                )),
-                depth_stencil_state: Some(DepthStencilState {
-                    depth: Some(DepthState::simple()),
+                multisample_state: Some(MultisampleState {
+                    rasterization_samples: samples,
                     ..Default::default()
                 }),
+                color_blend_state: Some(color_blend_state),
+                depth_stencil_state: Some(depth_stencil_state),
                 subpass: Some(subpass.into()),
                 ..GraphicsPipelineCreateInfo::layout(layout)
             },
@@ -249,7 +214,7 @@ impl CakeRenderer {
             buffers: BufferSet::new(&renderer.device),
             pipeline_clear,
             render_pass_clear,
-            depth_buffer,
+            targets,
             allocator,
             cb_allocator: StandardCommandBufferAllocator::new(
                 renderer.device.clone(),
@@ -269,25 +234,12 @@ impl CakeRenderer {
         key_view: &KeyboardView,
         final_image: Arc<ImageView>,
         midi_file: &mut CakeMIDIFile,
-        view_range: f64,
+        settings: &SceneSettings,
+        midi_time: f64,
     ) -> RenderResultData {
+        let view_range = settings.note_speed;
         let img_dims = final_image.image().extent();
-        if self.depth_buffer.image().extent() != img_dims {
-            self.depth_buffer = ImageView::new_default(
-                Image::new(
-                    self.allocator.clone(),
-                    ImageCreateInfo {
-                        extent: [img_dims[0], img_dims[1], 1],
-                        format: Format::D16_UNORM,
-                        usage: ImageUsage::SAMPLED | ImageUsage::DEPTH_STENCIL_ATTACHMENT,
-                        ..Default::default()
-                    },
-                    Default::default(),
-                )
-                .unwrap(),
-            )
-            .unwrap();
-        }
+        self.targets.resize([img_dims[0], img_dims[1], 1]);
 
         let curr_signature = midi_file.cake_signature();
         if self.current_file_signature.as_ref() != Some(&curr_signature) {
@@ -299,15 +251,31 @@ impl CakeRenderer {
             }
         }
 
-        let midi_time = midi_file.current_time().as_seconds_f64();
-        let screen_start = (midi_time * midi_file.ticks_per_second() as f64) as i32;
-        let screen_end = ((midi_time + view_range) * midi_file.ticks_per_second() as f64) as i32;
+        let screen_start = midi_file.seconds_to_tick(midi_time);
+        let screen_end = midi_file.seconds_to_tick(midi_time + view_range);
 
+        let (cap_height, cap_color) = settings.note_cap.unwrap_or_default();
         let push_constants = gs::PushConstants {
             start_time: screen_start,
             end_time: screen_end,
             screen_width: img_dims[0] as i32,
             screen_height: img_dims[1] as i32,
+            color_gamma: settings.color_gamma,
+            note_alpha: settings.note_alpha,
+            cap_height,
+            cap_r: cap_color[0],
+            cap_g: cap_color[1],
+            cap_b: cap_color[2],
+            cap_mix: cap_color[3],
+            spawn_ticks: if settings.spawn_animation {
+                screen_end
+                    - midi_file
+                        .seconds_to_tick(midi_time + view_range - settings.spawn_duration as f64)
+            } else {
+                0
+            },
+            min_velocity: settings.min_visible_velocity as i32,
+            dither: settings.dither as i32,
         };
 
         let border_width = crate::utils::calculate_border_width(
@@ -319,7 +287,7 @@ impl CakeRenderer {
         let mut written_instances = 0;
         // Black keys first, as they stencil out in the depth buffer
         for (i, buffer) in self.buffers.buffers.iter().enumerate() {
-            let key = key_view.note(i);
+            let key = key_view.note(settings.key_lane(i));
             if key.black {
                 buffer_instances[written_instances] = CakeNoteColumn {
                     buffer_index: i as i32,
@@ -334,7 +302,7 @@ impl CakeRenderer {
         }
         // White keys second
         for (i, buffer) in self.buffers.buffers.iter().enumerate() {
-            let key = key_view.note(i);
+            let key = key_view.note(settings.key_lane(i));
             if !key.black {
                 buffer_instances[written_instances] = CakeNoteColumn {
                     buffer_index: i as i32,
@@ -357,7 +325,7 @@ impl CakeRenderer {
         .unwrap();
 
         let (clears, pipeline, render_pass) = (
-            vec![Some([0.0, 0.0, 0.0, 0.0].into()), Some(1.0f32.into())],
+            self.targets.clear_values(true),
             &self.pipeline_clear,
             &self.render_pass_clear,
         );
@@ -365,7 +333,7 @@ impl CakeRenderer {
         let framebuffer = Framebuffer::new(
             render_pass.clone(),
             FramebufferCreateInfo {
-                attachments: vec![final_image.clone(), self.depth_buffer.clone()],
+                attachments: self.targets.attachments(final_image.clone()),
                 ..Default::default()
             },
         )
@@ -441,19 +409,36 @@ impl CakeRenderer {
 
         // Calculate the metadata before awaiting the future
         // to keep this more efficient
-        let colors = midi_file
+        let current_notes: Vec<_> = midi_file
             .key_blocks()
             .iter()
-            .map(|block| block.get_note_at(screen_start as u32).map(|n| n.color))
+            .map(|block| {
+                block
+                    .get_note_at(screen_start as u32)
+                    .filter(|note| note.velocity >= settings.min_visible_velocity)
+            })
+            .collect();
+        let colors = current_notes
+            .iter()
+            .map(|note| note.as_ref().map(|n| n.color))
+            .collect();
+        let note_ids = current_notes
+            .iter()
+            .enumerate()
+            .map(|(key, note)| {
+                note.as_ref()
+                    .map(|n| NoteId::new(key as u8, (n.tree_index as u64, 0)))
+            })
             .collect();
         let rendered_notes = midi_file
             .key_blocks()
             .iter()
-            .map(|block| {
+            .zip(current_notes.iter())
+            .map(|(block, note)| {
                 let passed =
                     block.get_notes_passed_at(screen_end) - block.get_notes_passed_at(screen_start);
 
-                if block.get_note_at(screen_start as u32).is_some() {
+                if note.is_some() {
                     passed as u64 + 1
                 } else {
                     passed as u64
@@ -469,7 +454,11 @@ impl CakeRenderer {
 
         RenderResultData {
             notes_rendered: rendered_notes,
-            key_colors: colors,
+            // The trees get searched on the GPU, so these are the same
+            notes_drawn: rendered_notes,
+            subsampled: false,
+            key_colors: remap_to_lanes(colors, settings),
+            key_note_ids: remap_to_lanes(note_ids, settings),
         }
     }
 }
@@ -0,0 +1,135 @@
+use std::{path::Path, sync::Arc};
+
+use egui::Color32;
+use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer},
+    command_buffer::{
+        allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, CommandBufferUsage,
+        CopyImageToBufferInfo,
+    },
+    device::Queue,
+    format::Format,
+    image::view::ImageView,
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+    sync::{self, GpuFuture},
+};
+
+use crate::{error::WasabiError, gui::GuiRenderer};
+
+/// Copies rendered scene images back to the CPU and saves them as PNGs
+pub struct FrameCapture {
+    gfx_queue: Arc<Queue>,
+    allocator: Arc<StandardMemoryAllocator>,
+    cb_allocator: StandardCommandBufferAllocator,
+    buffer: Option<Subbuffer<[u8]>>,
+}
+
+impl FrameCapture {
+    pub fn new(renderer: &GuiRenderer) -> Self {
+        let device = renderer.device.clone();
+
+        Self {
+            gfx_queue: renderer.queue.clone(),
+            allocator: Arc::new(StandardMemoryAllocator::new_default(device.clone())),
+            cb_allocator: StandardCommandBufferAllocator::new(device, Default::default()),
+            buffer: None,
+        }
+    }
+
+    /// Recreates the readback buffer if the size of the image has changed
+    fn buffer(&mut self, len: u64) -> Subbuffer<[u8]> {
+        match self.buffer.as_ref() {
+            Some(buffer) if buffer.len() == len => buffer.clone(),
+            _ => {
+                let buffer = Buffer::new_slice(
+                    self.allocator.clone(),
+                    BufferCreateInfo {
+                        usage: BufferUsage::TRANSFER_DST,
+                        ..Default::default()
+                    },
+                    AllocationCreateInfo {
+                        memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                            | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                        ..Default::default()
+                    },
+                    len,
+                )
+                .unwrap();
+                self.buffer = Some(buffer.clone());
+                buffer
+            }
+        }
+    }
+
+    /// Saves the image to `path`, composited over the background color so
+    /// that the frame looks the same as on screen
+    pub fn save(
+        &mut self,
+        image: Arc<ImageView>,
+        bg_color: Color32,
+        path: &Path,
+    ) -> Result<(), WasabiError> {
+        let format = image.format();
+        let swap_rb = match format {
+            Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB => true,
+            Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SRGB => false,
+            _ => {
+                return Err(WasabiError::Other(format!(
+                    "Frame capture doesn't support the {format:?} image format"
+                )))
+            }
+        };
+
+        let [width, height, _] = image.image().extent();
+        let buffer = self.buffer(width as u64 * height as u64 * 4);
+
+        let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
+            &self.cb_allocator,
+            self.gfx_queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        command_buffer_builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                image.image().clone(),
+                buffer.clone(),
+            ))
+            .unwrap();
+
+        let command_buffer = command_buffer_builder.build().unwrap();
+
+        sync::now(self.gfx_queue.device().clone())
+            .then_execute(self.gfx_queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .expect("Failed to signal fence and flush")
+            .wait(None)
+            .map_err(|e| WasabiError::Other(format!("Frame capture failed: {e}")))?;
+
+        let data = buffer
+            .read()
+            .map_err(|e| WasabiError::Other(format!("Frame capture failed: {e}")))?;
+
+        // The scene is drawn with premultiplied alpha over a transparent image
+        let bg = [bg_color.r(), bg_color.g(), bg_color.b()];
+        let mut pixels = Vec::with_capacity(data.len());
+        for pixel in data.chunks_exact(4) {
+            let (r, g, b) = if swap_rb {
+                (pixel[2], pixel[1], pixel[0])
+            } else {
+                (pixel[0], pixel[1], pixel[2])
+            };
+            let under = 255 - pixel[3] as u32;
+            for (c, bg) in [r, g, b].into_iter().zip(bg) {
+                pixels.push((c as u32 + bg as u32 * under / 255).min(255) as u8);
+            }
+            pixels.push(255);
+        }
+
+        image::RgbaImage::from_raw(width, height, pixels)
+            .expect("Frame buffer has the size of the image")
+            .save(path)
+            .map_err(|e| WasabiError::Other(format!("Failed to save frame: {e}")))
+    }
+}
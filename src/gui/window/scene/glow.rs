@@ -0,0 +1,342 @@
+use std::sync::Arc;
+
+use vulkano::{
+    command_buffer::{
+        allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, CommandBufferUsage,
+        RenderPassBeginInfo, SubpassBeginInfo, SubpassContents,
+    },
+    descriptor_set::{
+        allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
+    },
+    device::{Device, Queue},
+    format::Format,
+    image::{
+        sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo},
+        view::ImageView,
+        Image, ImageCreateInfo, ImageUsage,
+    },
+    memory::allocator::StandardMemoryAllocator,
+    pipeline::{
+        graphics::{
+            color_blend::{AttachmentBlend, ColorBlendAttachmentState, ColorBlendState},
+            input_assembly::InputAssemblyState,
+            multisample::MultisampleState,
+            rasterization::RasterizationState,
+            vertex_input::VertexInputState,
+            viewport::Viewport,
+            GraphicsPipelineCreateInfo,
+        },
+        layout::PipelineDescriptorSetLayoutCreateInfo,
+        DynamicState, GraphicsPipeline, Pipeline, PipelineBindPoint, PipelineLayout,
+        PipelineShaderStageCreateInfo,
+    },
+    render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass},
+    sync::{self, GpuFuture},
+};
+
+use crate::{gui::GuiRenderer, settings::SceneSettings};
+
+/// Colors darker than this (in linear luminance) don't glow
+const GLOW_THRESHOLD: f32 = 0.3;
+
+/// The distance between the blur samples, in pixels of the blur image
+const GLOW_SPREAD: f32 = 1.5;
+
+/// A bloom post process for the rendered notes.
+///
+/// The bright parts of the image get blurred horizontally into a half sized
+/// image, which then gets blurred vertically and added back onto the final
+/// image. Both passes only depend on the size of the image, so the cost is
+/// the same no matter how many notes are on screen.
+pub struct GlowPass {
+    gfx_queue: Arc<Queue>,
+    allocator: Arc<StandardMemoryAllocator>,
+    format: Format,
+    render_pass_blur: Arc<RenderPass>,
+    render_pass_composite: Arc<RenderPass>,
+    pipeline_blur: Arc<GraphicsPipeline>,
+    pipeline_composite: Arc<GraphicsPipeline>,
+    sampler: Arc<Sampler>,
+    blur_image: Option<Arc<ImageView>>,
+    cb_allocator: StandardCommandBufferAllocator,
+    sd_allocator: StandardDescriptorSetAllocator,
+}
+
+impl GlowPass {
+    pub fn new(renderer: &GuiRenderer) -> GlowPass {
+        let device = renderer.device.clone();
+        let allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
+
+        let render_pass_blur = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    format: renderer.format,
+                    samples: 1,
+                    load_op: DontCare,
+                    store_op: Store,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        )
+        .unwrap();
+
+        let render_pass_composite = vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    format: renderer.format,
+                    samples: 1,
+                    load_op: Load,
+                    store_op: Store,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        )
+        .unwrap();
+
+        let pipeline_blur = Self::create_pipeline(device.clone(), &render_pass_blur, None);
+        let pipeline_composite = Self::create_pipeline(
+            device.clone(),
+            &render_pass_composite,
+            Some(AttachmentBlend::additive()),
+        );
+
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        GlowPass {
+            gfx_queue: renderer.queue.clone(),
+            allocator,
+            format: renderer.format,
+            render_pass_blur,
+            render_pass_composite,
+            pipeline_blur,
+            pipeline_composite,
+            sampler,
+            blur_image: None,
+            cb_allocator: StandardCommandBufferAllocator::new(device.clone(), Default::default()),
+            sd_allocator: StandardDescriptorSetAllocator::new(device, Default::default()),
+        }
+    }
+
+    fn create_pipeline(
+        device: Arc<Device>,
+        render_pass: &Arc<RenderPass>,
+        blend: Option<AttachmentBlend>,
+    ) -> Arc<GraphicsPipeline> {
+        let vs = vs::load(device.clone())
+            .expect("failed to create shader module")
+            .entry_point("main")
+            .unwrap();
+        let fs = fs::load(device.clone())
+            .expect("failed to create shader module")
+            .entry_point("main")
+            .unwrap();
+
+        let stages = [
+            PipelineShaderStageCreateInfo::new(vs),
+            PipelineShaderStageCreateInfo::new(fs),
+        ];
+        let layout = PipelineLayout::new(
+            device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+                .into_pipeline_layout_create_info(device.clone())
+                .unwrap(),
+        )
+        .unwrap();
+        let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
+
+        GraphicsPipeline::new(
+            device,
+            None,
+            GraphicsPipelineCreateInfo {
+                stages: stages.into_iter().collect(),
+                vertex_input_state: Some(VertexInputState::default()),
+                input_assembly_state: Some(InputAssemblyState::default()),
+                viewport_state: Some(Default::default()),
+                dynamic_state: [DynamicState::Viewport].into_iter().collect(),
+                rasterization_state: Some(RasterizationState::default()),
+                multisample_state: Some(MultisampleState::default()),
+                color_blend_state: Some(ColorBlendState::with_attachment_states(
+                    subpass.num_color_attachments(),
+                    ColorBlendAttachmentState {
+                        blend,
+                        ..Default::default()
+                    },
+                )),
+                subpass: Some(subpass.into()),
+                ..GraphicsPipelineCreateInfo::layout(layout)
+            },
+        )
+        .unwrap()
+    }
+
+    /// Recreates the blur image if the size of the final image has changed
+    fn blur_image(&mut self, final_extent: [u32; 3]) -> Arc<ImageView> {
+        let extent = [
+            (final_extent[0] / 2).max(1),
+            (final_extent[1] / 2).max(1),
+            1,
+        ];
+
+        match self.blur_image.as_ref() {
+            Some(image) if image.image().extent() == extent => image.clone(),
+            _ => {
+                let image = ImageView::new_default(
+                    Image::new(
+                        self.allocator.clone(),
+                        ImageCreateInfo {
+                            extent,
+                            format: self.format,
+                            usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+                            ..Default::default()
+                        },
+                        Default::default(),
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+                self.blur_image = Some(image.clone());
+                image
+            }
+        }
+    }
+
+    pub fn draw(&mut self, final_image: Arc<ImageView>, settings: &SceneSettings) {
+        let final_extent = final_image.image().extent();
+        let blur_image = self.blur_image(final_extent);
+        let blur_extent = blur_image.image().extent();
+
+        let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
+            &self.cb_allocator,
+            self.gfx_queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        let passes = [
+            (
+                &self.render_pass_blur,
+                &self.pipeline_blur,
+                final_image.clone(),
+                blur_image.clone(),
+                fs::PushConstants {
+                    direction: [GLOW_SPREAD / blur_extent[0] as f32, 0.0],
+                    threshold: GLOW_THRESHOLD,
+                    intensity: 1.0,
+                },
+            ),
+            (
+                &self.render_pass_composite,
+                &self.pipeline_composite,
+                blur_image,
+                final_image,
+                fs::PushConstants {
+                    direction: [0.0, GLOW_SPREAD / blur_extent[1] as f32],
+                    threshold: 0.0,
+                    intensity: settings.glow_intensity,
+                },
+            ),
+        ];
+
+        for (render_pass, pipeline, source, target, push_constants) in passes {
+            let target_extent = target.image().extent();
+
+            let framebuffer = Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![target],
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+            let pipeline_layout = pipeline.layout();
+            let desc_layout = pipeline_layout.set_layouts().first().unwrap();
+            let set = PersistentDescriptorSet::new(
+                &self.sd_allocator,
+                desc_layout.clone(),
+                [WriteDescriptorSet::image_view_sampler(
+                    0,
+                    source,
+                    self.sampler.clone(),
+                )],
+                [],
+            )
+            .unwrap();
+
+            command_buffer_builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![None],
+                        ..RenderPassBeginInfo::framebuffer(framebuffer)
+                    },
+                    SubpassBeginInfo {
+                        contents: SubpassContents::Inline,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+                .bind_pipeline_graphics(pipeline.clone())
+                .unwrap()
+                .set_viewport(
+                    0,
+                    vec![Viewport {
+                        offset: [0.0, 0.0],
+                        extent: [target_extent[0] as f32, target_extent[1] as f32],
+                        depth_range: 0.0..=1.0,
+                    }]
+                    .into(),
+                )
+                .unwrap()
+                .push_constants(pipeline_layout.clone(), 0, push_constants)
+                .unwrap()
+                .bind_descriptor_sets(PipelineBindPoint::Graphics, pipeline_layout.clone(), 0, set)
+                .unwrap()
+                .draw(3, 1, 0, 0)
+                .unwrap()
+                .end_render_pass(Default::default())
+                .unwrap();
+        }
+
+        let command_buffer = command_buffer_builder.build().unwrap();
+
+        let future = sync::now(self.gfx_queue.device().clone())
+            .then_execute(self.gfx_queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .expect("Failed to signal fence and flush");
+
+        match future.wait(None) {
+            Ok(x) => x,
+            Err(err) => println!("err: {err:?}"),
+        }
+    }
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "shaders/glow/glow.vert",
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/glow/glow.frag",
+    }
+}
@@ -1,23 +1,29 @@
 mod notes_render_pass;
 
-use std::{cell::UnsafeCell, sync::Arc};
+use std::{cell::UnsafeCell, iter::StepBy, slice, sync::Arc};
 
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
-use vulkano::image::view::ImageView;
+use vulkano::image::{view::ImageView, SampleCount};
 
 use crate::{
     gui::{window::keyboard_layout::KeyboardView, GuiRenderer},
-    midi::{DisplacedMIDINote, MIDIColor, MIDIFile, MIDINoteColumnView, MIDINoteViews},
+    midi::{note_playing, MIDIColor, NoteId, RenderFrame, VisibleNote},
+    settings::SceneSettings,
     utils,
 };
 
 use self::notes_render_pass::{NotePassStatus, NoteRenderPass, NoteVertex};
 
-use super::RenderResultData;
+use super::{remap_to_lanes, RenderResultData};
+
+/// How much the overlay tint replaces the colors of the overlay notes
+const OVERLAY_TINT_AMOUNT: f32 = 0.5;
 
 pub struct NoteRenderer {
     render_pass: NoteRenderPass,
     thrad_pool: rayon::ThreadPool,
+    /// How many notes were playing on each key in the previous frame
+    key_polyphony: Vec<u32>,
 }
 
 struct UnsafeSyncCell<T>(UnsafeCell<T>);
@@ -37,34 +43,51 @@ unsafe impl<T> Sync for UnsafeSyncCell<T> {}
 unsafe impl<T> Send for UnsafeSyncCell<T> {}
 
 impl NoteRenderer {
-    pub fn new(renderer: &GuiRenderer) -> NoteRenderer {
+    pub fn new(renderer: &GuiRenderer, samples: SampleCount, transparent: bool) -> NoteRenderer {
         NoteRenderer {
-            render_pass: NoteRenderPass::new(renderer),
+            render_pass: NoteRenderPass::new(renderer, samples, transparent),
             thrad_pool: rayon::ThreadPoolBuilder::new().build().unwrap(),
+            key_polyphony: vec![0; 256],
         }
     }
 
+    /// Draws the notes of the frame. If `clear` is false, they get drawn over
+    /// the current contents of the image, and `tint` is mixed into the colors
+    /// of all the notes.
     pub fn draw(
         &mut self,
         key_view: &KeyboardView,
         final_image: Arc<ImageView>,
-        midi_file: &mut impl MIDIFile,
-        view_range: f64,
+        frame: &RenderFrame,
+        settings: &SceneSettings,
+        clear: bool,
+        tint: Option<MIDIColor>,
     ) -> RenderResultData {
-        let note_views = midi_file.get_current_column_views(view_range);
-
-        struct ColumnViewInfo<Iter: ExactSizeIterator<Item = DisplacedMIDINote> + Send> {
+        struct ColumnViewInfo<'a> {
             offset: usize,
-            iter: Iter,
+            iter: StepBy<slice::Iter<'a, VisibleNote>>,
             key: u8,
+            /// The key the notes are drawn on, see [`SceneSettings::key_remap`]
+            lane: u8,
             remaining: usize,
             color: Option<MIDIColor>,
+            note_id: Option<NoteId>,
             border_width: f32,
+            playing: u32,
+            saturation: f32,
+            drawn: u64,
         }
 
         let mut total_notes = 0;
 
-        let columns: Vec<_> = (0..256).map(|i| note_views.get_column(i)).collect();
+        let columns: Vec<_> = (0..256).map(|i| frame.key_notes(i as u8)).collect();
+
+        // Past the limit, every column only draws every Nth note
+        let culled_notes = frame.notes.len();
+        let stride = match settings.max_visible_notes {
+            Some(max) if culled_notes as u64 > max => culled_notes.div_ceil(max.max(1) as usize),
+            _ => 1,
+        };
 
         let mut columns_view_info = Vec::new();
 
@@ -73,18 +96,33 @@ impl NoteRenderer {
             key_view.visible_range.len() as f32,
         );
 
+        // The playing notes of a key only get counted while they are written,
+        // so the saturation uses the counts of the previous frame
+        let saturation = |key: usize| match settings.polyphony_saturation {
+            Some(full) => {
+                self.key_polyphony[key].saturating_sub(1) as f32 / (full.max(2) - 1) as f32
+            }
+            None => 0.0,
+        };
+
         // Black keys first
         for (i, column) in columns.iter().enumerate() {
-            if key_view.key(i).black {
-                let iter = column.iterate_displaced_notes();
+            let lane = settings.key_lane(i);
+            if key_view.key(lane).black {
+                let iter = column.iter().step_by(stride);
                 let length = iter.len();
                 columns_view_info.push(ColumnViewInfo {
                     offset: total_notes,
                     iter,
                     key: i as u8,
+                    lane: lane as u8,
                     remaining: length,
                     color: None,
+                    note_id: None,
                     border_width,
+                    playing: 0,
+                    saturation: saturation(i),
+                    drawn: 0,
                 });
                 total_notes += length;
             }
@@ -92,16 +130,22 @@ impl NoteRenderer {
 
         // Then white keys after
         for (i, column) in columns.iter().enumerate() {
-            if !key_view.key(i).black {
-                let iter = column.iterate_displaced_notes();
+            let lane = settings.key_lane(i);
+            if !key_view.key(lane).black {
+                let iter = column.iter().step_by(stride);
                 let length = iter.len();
                 columns_view_info.push(ColumnViewInfo {
                     offset: total_notes,
                     iter,
                     key: i as u8,
+                    lane: lane as u8,
                     remaining: length,
                     color: None,
+                    note_id: None,
                     border_width,
+                    playing: 0,
+                    saturation: saturation(i),
+                    drawn: 0,
                 });
                 total_notes += length;
             }
@@ -111,10 +155,15 @@ impl NoteRenderer {
 
         let mut cycle = 0;
 
-        let view_range = note_views.range().length() as f32;
+        let view_range = frame.view_range as f32;
 
-        self.render_pass
-            .draw(final_image, key_view, view_range, |buffer| {
+        self.render_pass.draw(
+            final_image,
+            key_view,
+            view_range,
+            settings,
+            clear,
+            |buffer| {
                 let buffer_length = buffer.len() as usize;
 
                 let buffer_writer = UnsafeSyncCell::new(buffer.write().unwrap());
@@ -150,19 +199,48 @@ impl NoteRenderer {
                                 for i in 0..allowed_to_write {
                                     let next_note = column.iter.next();
                                     if let Some(note) = next_note {
+                                        // The space of the hidden notes was already
+                                        // counted, so they become empty notes
+                                        let opacity = note
+                                            .track
+                                            .map_or(1.0, |track| settings.track_opacity(track));
+                                        if note.velocity < settings.min_visible_velocity
+                                            || opacity <= 0.0
+                                        {
+                                            buffer[i + offset] = NoteVertex::default();
+                                            continue;
+                                        }
+
+                                        // The notes are relative to the current time
+                                        let playing =
+                                            note_playing(note.start as f64, note.end as f64, 0.0);
+                                        let color = match tint {
+                                            Some(tint) => note.color.mix(tint, OVERLAY_TINT_AMOUNT),
+                                            None => note.color,
+                                        };
+                                        let color = if playing && column.saturation > 0.0 {
+                                            color.saturate(column.saturation)
+                                        } else {
+                                            color
+                                        };
+
+                                        // The shader places the notes in seconds
                                         buffer[i + offset] = NoteVertex::new(
-                                            note.start,
-                                            note.len,
-                                            column.key,
-                                            note.color.as_u32(),
+                                            note.start * view_range,
+                                            (note.end - note.start) * view_range,
+                                            column.lane,
+                                            color.as_u32(),
                                             column.border_width as u32,
-                                        );
+                                        )
+                                        .with_opacity(opacity);
+                                        column.drawn += 1;
 
-                                        if note.start <= 0.0
-                                            && column.color.is_none()
-                                            && note.start + note.len > 0.0
-                                        {
-                                            column.color = Some(note.color);
+                                        if playing {
+                                            column.playing += 1;
+                                        }
+                                        if playing && column.color.is_none() {
+                                            column.color = Some(color);
+                                            column.note_id = Some(note.id);
                                         }
                                     } else {
                                         panic!("Invalid iterator length");
@@ -189,17 +267,34 @@ impl NoteRenderer {
                 } else {
                     NotePassStatus::HasMoreNotes
                 }
-            });
+            },
+        );
 
         // Sort for output metrics
         columns_view_info.sort_unstable_by_key(|k| k.key);
 
+        for column in columns_view_info.iter() {
+            self.key_polyphony[column.key as usize] = column.playing;
+        }
+
         RenderResultData {
             notes_rendered: notes_pushed as u64,
-            key_colors: columns_view_info
-                .iter()
-                .map(|column| column.color)
-                .collect(),
+            notes_drawn: columns_view_info.iter().map(|column| column.drawn).sum(),
+            subsampled: stride > 1,
+            key_colors: remap_to_lanes(
+                columns_view_info
+                    .iter()
+                    .map(|column| column.color)
+                    .collect(),
+                settings,
+            ),
+            key_note_ids: remap_to_lanes(
+                columns_view_info
+                    .iter()
+                    .map(|column| column.note_id)
+                    .collect(),
+                settings,
+            ),
         }
     }
 }
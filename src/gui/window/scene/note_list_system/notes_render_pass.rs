@@ -11,13 +11,10 @@ use vulkano::{
         allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
     },
     device::{Device, Queue},
-    format::Format,
-    image::{view::ImageView, Image, ImageCreateInfo, ImageUsage},
+    image::{view::ImageView, SampleCount},
     memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
     pipeline::{
         graphics::{
-            color_blend::{ColorBlendAttachmentState, ColorBlendState},
-            depth_stencil::{DepthState, DepthStencilState},
             input_assembly::{InputAssemblyState, PrimitiveTopology},
             multisample::MultisampleState,
             rasterization::RasterizationState,
@@ -34,7 +31,16 @@ use vulkano::{
     sync::{self, future::FenceSignalFuture, GpuFuture},
 };
 
-use crate::gui::{window::keyboard_layout::KeyboardView, GuiRenderer};
+use crate::{
+    gui::{
+        window::{
+            keyboard_layout::KeyboardView,
+            scene::render_targets::{create_render_pass, note_blend_states, RenderTargets},
+        },
+        GuiRenderer,
+    },
+    settings::SceneSettings,
+};
 
 const NOTE_BUFFER_SIZE: u64 = 25000000;
 
@@ -57,6 +63,14 @@ impl NoteVertex {
             border_width,
         }
     }
+
+    /// Makes the note partly transparent. The transparency is stored in the
+    /// upper bits of the border width, so 0 stays fully opaque.
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        let transparency = ((1.0 - opacity.clamp(0.0, 1.0)) * 255.0) as u32;
+        self.border_width = (self.border_width & 0xFFFF) | (transparency << 16);
+        self
+    }
 }
 
 struct BufferSet {
@@ -120,84 +134,25 @@ pub struct NoteRenderPass {
     render_pass_clear: Arc<RenderPass>,
     render_pass_draw_over: Arc<RenderPass>,
     key_locations: Subbuffer<[[KeyPosition; 256]]>,
-    depth_buffer: Arc<ImageView>,
-    allocator: Arc<StandardMemoryAllocator>,
+    targets: RenderTargets,
     cb_allocator: StandardCommandBufferAllocator,
     sd_allocator: StandardDescriptorSetAllocator,
 }
 
 impl NoteRenderPass {
-    pub fn new(renderer: &GuiRenderer) -> NoteRenderPass {
+    pub fn new(renderer: &GuiRenderer, samples: SampleCount, transparent: bool) -> NoteRenderPass {
         let allocator = Arc::new(StandardMemoryAllocator::new_default(
             renderer.device.clone(),
         ));
 
         let gfx_queue = renderer.queue.clone();
 
-        let render_pass_clear = vulkano::ordered_passes_renderpass!(gfx_queue.device().clone(),
-            attachments: {
-                final_color: {
-                    format: renderer.format,
-                    samples: 1,
-                    load_op: Clear,
-                    store_op: Store,
-                },
-                depth: {
-                    format: Format::D16_UNORM,
-                    samples: 1,
-                    load_op: Clear,
-                    store_op: Store,
-                }
-            },
-            passes: [
-                {
-                    color: [final_color],
-                    depth_stencil: {depth},
-                    input: []
-                }
-            ]
-        )
-        .unwrap();
+        let render_pass_clear =
+            create_render_pass(gfx_queue.device().clone(), renderer.format, samples, true);
+        let render_pass_draw_over =
+            create_render_pass(gfx_queue.device().clone(), renderer.format, samples, false);
 
-        let render_pass_draw_over = vulkano::ordered_passes_renderpass!(gfx_queue.device().clone(),
-            attachments: {
-                final_color: {
-                    format: renderer.format,
-                    samples: 1,
-                    load_op: DontCare,
-                    store_op: Store,
-                },
-                depth: {
-                    format: Format::D16_UNORM,
-                    samples: 1,
-                    load_op: DontCare,
-                    store_op: Store,
-                }
-            },
-            passes: [
-                {
-                    color: [final_color],
-                    depth_stencil: {depth},
-                    input: []
-                }
-            ]
-        )
-        .unwrap();
-
-        let depth_buffer = ImageView::new_default(
-            Image::new(
-                allocator.clone(),
-                ImageCreateInfo {
-                    extent: [1, 1, 1],
-                    format: Format::D16_UNORM,
-                    usage: ImageUsage::SAMPLED | ImageUsage::DEPTH_STENCIL_ATTACHMENT,
-                    ..Default::default()
-                },
-                Default::default(),
-            )
-            .unwrap(),
-        )
-        .unwrap();
+        let targets = RenderTargets::new(allocator.clone(), renderer.format, samples);
 
         let key_locations = Buffer::from_iter(
             allocator.clone(),
@@ -242,6 +197,8 @@ impl NoteRenderPass {
         )
         .unwrap();
         let subpass = Subpass::from(render_pass_clear.clone(), 0).unwrap();
+        let (color_blend_state, depth_stencil_state) =
+            note_blend_states(subpass.num_color_attachments(), transparent);
 
         let mut create_info = GraphicsPipelineCreateInfo {
             stages: stages.into_iter().collect(),
@@ -253,15 +210,12 @@ impl NoteRenderPass {
             viewport_state: Some(Default::default()),
             dynamic_state: [DynamicState::Viewport].into_iter().collect(),
             rasterization_state: Some(RasterizationState::default()),
-            multisample_state: Some(MultisampleState::default()),
-            color_blend_state: Some(ColorBlendState::with_attachment_states(
-                subpass.num_color_attachments(),
-                ColorBlendAttachmentState::default(),
-            )),
-            depth_stencil_state: Some(DepthStencilState {
-                depth: Some(DepthState::simple()),
+            multisample_state: Some(MultisampleState {
+                rasterization_samples: samples,
                 ..Default::default()
             }),
+            color_blend_state: Some(color_blend_state),
+            depth_stencil_state: Some(depth_stencil_state),
             subpass: Some(subpass.into()),
             ..GraphicsPipelineCreateInfo::layout(layout)
         };
@@ -282,9 +236,8 @@ impl NoteRenderPass {
             pipeline_draw_over,
             render_pass_clear,
             render_pass_draw_over,
-            depth_buffer,
+            targets,
             key_locations,
-            allocator,
             cb_allocator: StandardCommandBufferAllocator::new(
                 renderer.device.clone(),
                 Default::default(),
@@ -301,25 +254,12 @@ impl NoteRenderPass {
         final_image: Arc<ImageView>,
         key_view: &KeyboardView,
         view_range: f32,
+        settings: &SceneSettings,
+        clear: bool,
         mut fill_buffer: impl FnMut(&Subbuffer<[NoteVertex]>) -> NotePassStatus,
     ) {
         let img_dims = final_image.image().extent();
-        if self.depth_buffer.image().extent() != img_dims {
-            self.depth_buffer = ImageView::new_default(
-                Image::new(
-                    self.allocator.clone(),
-                    ImageCreateInfo {
-                        extent: [img_dims[0], img_dims[1], 1],
-                        format: Format::D16_UNORM,
-                        usage: ImageUsage::SAMPLED | ImageUsage::DEPTH_STENCIL_ATTACHMENT,
-                        ..Default::default()
-                    },
-                    Default::default(),
-                )
-                .unwrap(),
-            )
-            .unwrap();
-        }
+        self.targets.resize([img_dims[0], img_dims[1], 1]);
 
         {
             let mut keys = self.key_locations.write().unwrap();
@@ -336,7 +276,7 @@ impl NoteRenderPass {
 
         let mut status = NotePassStatus::HasMoreNotes;
 
-        let mut first_pass = true;
+        let mut first_pass = clear;
 
         while status == NotePassStatus::HasMoreNotes {
             let buffer = self.buffer_set.next();
@@ -361,13 +301,13 @@ impl NoteRenderPass {
             let (clears, pipeline, render_pass) = if first_pass {
                 first_pass = false;
                 (
-                    vec![Some([0.0, 0.0, 0.0, 0.0].into()), Some(1.0f32.into())],
+                    self.targets.clear_values(true),
                     &self.pipeline_clear,
                     &self.render_pass_clear,
                 )
             } else {
                 (
-                    vec![None, None],
+                    self.targets.clear_values(false),
                     &self.pipeline_draw_over,
                     &self.render_pass_draw_over,
                 )
@@ -376,7 +316,7 @@ impl NoteRenderPass {
             let framebuffer = Framebuffer::new(
                 render_pass.clone(),
                 FramebufferCreateInfo {
-                    attachments: vec![final_image.clone(), self.depth_buffer.clone()],
+                    attachments: self.targets.attachments(final_image.clone()),
                     ..Default::default()
                 },
             )
@@ -409,10 +349,24 @@ impl NoteRenderPass {
                 )
                 .unwrap();
 
+            let (cap_height, cap_color) = settings.note_cap.unwrap_or_default();
             let push_constants = gs::PushConstants {
                 height_time: view_range,
                 win_width: img_dims[0] as f32,
                 win_height: img_dims[1] as f32,
+                color_gamma: settings.color_gamma,
+                note_alpha: settings.note_alpha,
+                cap_height,
+                cap_r: cap_color[0],
+                cap_g: cap_color[1],
+                cap_b: cap_color[2],
+                cap_mix: cap_color[3],
+                spawn_time: if settings.spawn_animation {
+                    settings.spawn_duration
+                } else {
+                    0.0
+                },
+                dither: settings.dither as i32,
             };
 
             command_buffer_builder
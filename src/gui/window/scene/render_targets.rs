@@ -0,0 +1,305 @@
+use std::sync::Arc;
+
+use vulkano::{
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, Image, ImageCreateInfo, ImageUsage, SampleCount},
+    memory::allocator::StandardMemoryAllocator,
+    pipeline::graphics::{
+        color_blend::{
+            AttachmentBlend, BlendFactor, BlendOp, ColorBlendAttachmentState, ColorBlendState,
+        },
+        depth_stencil::{DepthState, DepthStencilState},
+    },
+    render_pass::RenderPass,
+};
+
+const DEPTH_FORMAT: Format = Format::D16_UNORM;
+
+/// Returns the highest sample count supported by the device that
+/// doesn't exceed the requested one.
+pub fn supported_sample_count(device: &Device, requested: u32) -> SampleCount {
+    let properties = device.physical_device().properties();
+    let supported =
+        properties.framebuffer_color_sample_counts & properties.framebuffer_depth_sample_counts;
+
+    [
+        SampleCount::Sample8,
+        SampleCount::Sample4,
+        SampleCount::Sample2,
+    ]
+    .into_iter()
+    .find(|samples| *samples as u32 <= requested && supported.contains_enum(*samples))
+    .unwrap_or(SampleCount::Sample1)
+}
+
+/// Returns the color blend and depth states of the note pipelines.
+///
+/// Notes are drawn front to back, so opaque notes use the depth buffer to
+/// skip the ones that are hidden behind them. Transparent notes can't be
+/// skipped, so instead they get blended under what has already been drawn
+/// (using the destination alpha), which is the same as drawing back to front.
+pub fn note_blend_states(
+    num_attachments: u32,
+    transparent: bool,
+) -> (ColorBlendState, DepthStencilState) {
+    let under = AttachmentBlend {
+        src_color_blend_factor: BlendFactor::OneMinusDstAlpha,
+        dst_color_blend_factor: BlendFactor::One,
+        color_blend_op: BlendOp::Add,
+        src_alpha_blend_factor: BlendFactor::OneMinusDstAlpha,
+        dst_alpha_blend_factor: BlendFactor::One,
+        alpha_blend_op: BlendOp::Add,
+    };
+
+    let color_blend = ColorBlendState::with_attachment_states(
+        num_attachments,
+        ColorBlendAttachmentState {
+            blend: transparent.then_some(under),
+            ..Default::default()
+        },
+    );
+
+    let depth_stencil = DepthStencilState {
+        depth: (!transparent).then(DepthState::simple),
+        ..Default::default()
+    };
+
+    (color_blend, depth_stencil)
+}
+
+/// Creates the render pass used by the scene renderers.
+///
+/// If `clear` is false, the previous contents of the attachments are kept so
+/// more notes can be drawn over them. When multisampling is enabled, rendering
+/// happens in an intermediate image which gets resolved into the final one.
+pub fn create_render_pass(
+    device: Arc<Device>,
+    format: Format,
+    samples: SampleCount,
+    clear: bool,
+) -> Arc<RenderPass> {
+    match (samples == SampleCount::Sample1, clear) {
+        (true, true) => vulkano::ordered_passes_renderpass!(device,
+            attachments: {
+                final_color: {
+                    format: format,
+                    samples: 1,
+                    load_op: Clear,
+                    store_op: Store,
+                },
+                depth: {
+                    format: DEPTH_FORMAT,
+                    samples: 1,
+                    load_op: Clear,
+                    store_op: Store,
+                }
+            },
+            passes: [
+                {
+                    color: [final_color],
+                    depth_stencil: {depth},
+                    input: []
+                }
+            ]
+        ),
+        (true, false) => vulkano::ordered_passes_renderpass!(device,
+            attachments: {
+                final_color: {
+                    format: format,
+                    samples: 1,
+                    load_op: DontCare,
+                    store_op: Store,
+                },
+                depth: {
+                    format: DEPTH_FORMAT,
+                    samples: 1,
+                    load_op: DontCare,
+                    store_op: Store,
+                }
+            },
+            passes: [
+                {
+                    color: [final_color],
+                    depth_stencil: {depth},
+                    input: []
+                }
+            ]
+        ),
+        (false, true) => vulkano::ordered_passes_renderpass!(device,
+            attachments: {
+                msaa_color: {
+                    format: format,
+                    samples: samples,
+                    load_op: Clear,
+                    store_op: Store,
+                },
+                final_color: {
+                    format: format,
+                    samples: 1,
+                    load_op: DontCare,
+                    store_op: Store,
+                },
+                depth: {
+                    format: DEPTH_FORMAT,
+                    samples: samples,
+                    load_op: Clear,
+                    store_op: Store,
+                }
+            },
+            passes: [
+                {
+                    color: [msaa_color],
+                    color_resolve: [final_color],
+                    depth_stencil: {depth},
+                    input: []
+                }
+            ]
+        ),
+        (false, false) => vulkano::ordered_passes_renderpass!(device,
+            attachments: {
+                msaa_color: {
+                    format: format,
+                    samples: samples,
+                    load_op: Load,
+                    store_op: Store,
+                },
+                final_color: {
+                    format: format,
+                    samples: 1,
+                    load_op: DontCare,
+                    store_op: Store,
+                },
+                depth: {
+                    format: DEPTH_FORMAT,
+                    samples: samples,
+                    load_op: Load,
+                    store_op: Store,
+                }
+            },
+            passes: [
+                {
+                    color: [msaa_color],
+                    color_resolve: [final_color],
+                    depth_stencil: {depth},
+                    input: []
+                }
+            ]
+        ),
+    }
+    .unwrap()
+}
+
+/// The depth buffer and (if multisampling is enabled) the intermediate
+/// color image that the scene renderers draw into.
+pub struct RenderTargets {
+    allocator: Arc<StandardMemoryAllocator>,
+    format: Format,
+    samples: SampleCount,
+    depth_buffer: Arc<ImageView>,
+    msaa_color: Option<Arc<ImageView>>,
+}
+
+impl RenderTargets {
+    pub fn new(
+        allocator: Arc<StandardMemoryAllocator>,
+        format: Format,
+        samples: SampleCount,
+    ) -> Self {
+        let mut targets = Self {
+            depth_buffer: Self::create_image(
+                allocator.clone(),
+                DEPTH_FORMAT,
+                samples,
+                [1, 1, 1],
+                ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            ),
+            allocator,
+            format,
+            samples,
+            msaa_color: None,
+        };
+        targets.resize([1, 1, 1]);
+        targets
+    }
+
+    fn create_image(
+        allocator: Arc<StandardMemoryAllocator>,
+        format: Format,
+        samples: SampleCount,
+        extent: [u32; 3],
+        usage: ImageUsage,
+    ) -> Arc<ImageView> {
+        // Multisampled images can't be sampled on all devices and we don't
+        // need to, so only single sampled images get that usage
+        let usage = if samples == SampleCount::Sample1 {
+            usage | ImageUsage::SAMPLED
+        } else {
+            usage
+        };
+
+        ImageView::new_default(
+            Image::new(
+                allocator,
+                ImageCreateInfo {
+                    extent,
+                    format,
+                    samples,
+                    usage,
+                    ..Default::default()
+                },
+                Default::default(),
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    /// Recreates the images if the size of the final image has changed
+    pub fn resize(&mut self, extent: [u32; 3]) {
+        if self.depth_buffer.image().extent() != extent {
+            self.depth_buffer = Self::create_image(
+                self.allocator.clone(),
+                DEPTH_FORMAT,
+                self.samples,
+                extent,
+                ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            );
+        }
+
+        if self.samples != SampleCount::Sample1
+            && self.msaa_color.as_ref().map(|i| i.image().extent()) != Some(extent)
+        {
+            self.msaa_color = Some(Self::create_image(
+                self.allocator.clone(),
+                self.format,
+                self.samples,
+                extent,
+                ImageUsage::COLOR_ATTACHMENT,
+            ));
+        }
+    }
+
+    /// The framebuffer attachments, in the order of the render pass
+    pub fn attachments(&self, final_image: Arc<ImageView>) -> Vec<Arc<ImageView>> {
+        match self.msaa_color.as_ref() {
+            Some(msaa_color) => vec![msaa_color.clone(), final_image, self.depth_buffer.clone()],
+            None => vec![final_image, self.depth_buffer.clone()],
+        }
+    }
+
+    /// The clear values, in the order of the render pass
+    pub fn clear_values(&self, clear: bool) -> Vec<Option<ClearValue>> {
+        let (color, depth) = if clear {
+            (Some([0.0, 0.0, 0.0, 0.0].into()), Some(1.0f32.into()))
+        } else {
+            (None, None)
+        };
+
+        if self.msaa_color.is_some() {
+            vec![color, None, depth]
+        } else {
+            vec![color, depth]
+        }
+    }
+}
@@ -3,13 +3,12 @@ use std::path::PathBuf;
 use soundfonts::EguiSFList;
 
 use crate::{
-    settings::{Colors, Synth, WasabiSettings},
+    error::WasabiError,
+    settings::{Colors, MidiDeviceOutput, Synth, WasabiSettings},
     state::{SettingsTab, WasabiState},
     utils,
 };
 
-use super::WasabiError;
-
 mod midi;
 mod soundfonts;
 mod synth;
@@ -34,6 +33,8 @@ pub struct SettingsWindow {
     palettes: Vec<FilePalette>,
     midi_devices: Vec<MidiDevice>,
     sf_list: EguiSFList,
+    /// The marked keys as they are being typed, see [`crate::settings::SceneSettings::marked_keys`]
+    marked_keys: String,
 }
 
 impl SettingsWindow {
@@ -47,6 +48,13 @@ impl SettingsWindow {
             palettes: Vec::new(),
             midi_devices: Vec::new(),
             sf_list,
+            marked_keys: settings
+                .scene
+                .marked_keys
+                .iter()
+                .map(|key| key.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
         }
     }
 
@@ -199,13 +207,22 @@ impl SettingsWindow {
             });
         }
 
-        // Select the device specified in settings if found, or select the first available
-        let saved = settings.synth.midi_device.clone();
-        if let Some(found) = self.midi_devices.iter_mut().find(|d| d.name == saved) {
-            found.selected = true;
-        } else if !self.midi_devices.is_empty() {
+        // Select the devices specified in settings if found, or select the first available
+        let mut found_any = false;
+        for device in self.midi_devices.iter_mut() {
+            device.selected = settings
+                .synth
+                .midi_devices
+                .iter()
+                .any(|d| d.name == device.name);
+            found_any |= device.selected;
+        }
+        if !found_any && !self.midi_devices.is_empty() {
             self.midi_devices[0].selected = true;
-            settings.synth.midi_device = self.midi_devices[0].name.clone();
+            settings.synth.midi_devices = vec![MidiDeviceOutput {
+                name: self.midi_devices[0].name.clone(),
+                ..Default::default()
+            }];
         }
 
         Ok(())
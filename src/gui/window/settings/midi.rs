@@ -1,7 +1,8 @@
 use egui_extras::{Column, TableBuilder};
 
 use crate::{
-    settings::{Colors, MidiParsing, WasabiSettings},
+    midi::{CakeMIDIFile, CAKE_TICKS_PER_SECOND_RANGE},
+    settings::{Colors, MidiParsing, NoteOverlap, WasabiSettings},
     state::WasabiState,
 };
 
@@ -39,7 +40,11 @@ impl SettingsWindow {
                   \0    will be rendered normally by the GPU.\n\
                     - Standard (Live)\n\
                   \0    The MIDI will be streamed live from the disk and all\n\
-                  \0    the notes will be rendered normally by the GPU.\
+                  \0    the notes will be rendered normally by the GPU.\n\
+                    - Auto\n\
+                  \0    Cake for MIDIs with more notes than the threshold\n\
+                  \0    (estimated from the file size), Standard (RAM) for\n\
+                  \0    the others.\
                     ",
                     );
                 });
@@ -61,9 +66,82 @@ impl SettingsWindow {
                             MidiParsing::Live,
                             MidiParsing::Live.as_str(),
                         );
+                        ui.selectable_value(
+                            &mut settings.midi.parsing,
+                            MidiParsing::Auto,
+                            MidiParsing::Auto.as_str(),
+                        );
                     });
                 ui.end_row();
 
+                ui.label("Auto Cake Threshold:");
+                ui.add_enabled(
+                    settings.midi.parsing == MidiParsing::Auto,
+                    egui::DragValue::new(&mut settings.midi.auto_cake_threshold)
+                        .speed(100_000.0)
+                        .suffix(" notes"),
+                );
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Cake Resolution:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        How precisely the Cake algorithm stores the note times.\n\
+                        Higher values use more memory and lower the longest\n\
+                        MIDI that can be loaded.\
+                        ",
+                    );
+                });
+                ui.add_enabled(
+                    settings.midi.parsing != MidiParsing::Ram
+                        && settings.midi.parsing != MidiParsing::Live,
+                    egui::DragValue::new(&mut settings.midi.cake_ticks_per_second)
+                        .speed(100.0)
+                        .range(CAKE_TICKS_PER_SECOND_RANGE)
+                        .suffix(" ticks/s"),
+                );
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Cache Cake Trees:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Stores the notes of MIDIs loaded with the Cake algorithm\n\
+                        on disk, so loading them again is faster. Large MIDIs\n\
+                        can take several gigabytes.\
+                        ",
+                    );
+                });
+                ui.add_enabled(
+                    settings.midi.parsing != MidiParsing::Ram
+                        && settings.midi.parsing != MidiParsing::Live,
+                    egui::Checkbox::new(&mut settings.midi.cake_disk_cache, ""),
+                );
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Cake Cache Limit (GB):");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Once the cached trees take more space than this, the\n\
+                        ones that were used the longest ago get removed.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.add_enabled(
+                        settings.midi.cake_disk_cache,
+                        egui::DragValue::new(&mut settings.midi.cake_cache_max_gb)
+                            .speed(1.0)
+                            .range(1.0..=10000.0),
+                    );
+                    if ui.button("Clear Cache").clicked() {
+                        CakeMIDIFile::clear_cache().unwrap_or_else(|e| state.errors.error(&e));
+                    }
+                });
+                ui.end_row();
+
                 ui.label("Start Delay (s):");
                 ui.add(
                     egui::DragValue::new(&mut settings.midi.start_delay)
@@ -71,6 +149,180 @@ impl SettingsWindow {
                         .range(0.0..=100.0),
                 );
                 ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Skip Leading Silence:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Starts the playback the start delay before the first\n\
+                        note, instead of at the start of the MIDI.\
+                        ",
+                    );
+                });
+                ui.checkbox(&mut settings.midi.skip_leading_silence, "");
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Time Offset (s):");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Shifts the whole MIDI in time, for syncing it to an\n\
+                        external track. Positive values make it play later.\
+                        ",
+                    );
+                });
+                ui.add(
+                    egui::DragValue::new(&mut settings.midi.global_time_offset)
+                        .speed(0.01)
+                        .range(-100.0..=100.0),
+                );
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Override PPQ:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Replaces the PPQ (ticks per quarter note) of the file.\n\
+                        Useful for files that were saved with a wrong PPQ,\n\
+                        but it changes the speed of the whole MIDI.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.midi.ppq_override.is_some();
+                    ui.checkbox(&mut enabled, "");
+                    let mut ppq = settings.midi.ppq_override.unwrap_or(480);
+                    ui.add_enabled(
+                        enabled,
+                        egui::DragValue::new(&mut ppq)
+                            .speed(1.0)
+                            .range(1..=u16::MAX),
+                    );
+                    settings.midi.ppq_override = enabled.then_some(ppq);
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Split Long Notes (s):");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Splits the notes longer than the given length into\n\
+                        multiple notes, which makes rendering MIDIs with very\n\
+                        long notes faster. The split notes will have borders\n\
+                        between them. Only used by the Standard (RAM) algorithm,\n\
+                        the audio is not affected.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.midi.split_long_notes.is_some();
+                    ui.checkbox(&mut enabled, "");
+                    let mut length = settings.midi.split_long_notes.unwrap_or(10.0);
+                    ui.add_enabled(
+                        enabled,
+                        egui::DragValue::new(&mut length)
+                            .speed(0.1)
+                            .range(0.5..=600.0),
+                    );
+                    settings.midi.split_long_notes = enabled.then_some(length);
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Drum Lane:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Moves the notes of channel 10 (percussion) onto their own\n\
+                        lane to the right of the keyboard, with one key and color\n\
+                        for each GM drum instrument. The audio is not affected.\
+                        ",
+                    );
+                });
+                ui.checkbox(&mut settings.midi.drum_lane, "");
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Metronome:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Plays a click on every beat with the selected synth,\n\
+                        following the tempo and time signature of the MIDI.\n\
+                        The first beat of each bar is accented. Not available\n\
+                        with the Standard (Live) algorithm.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut settings.midi.metronome, "");
+                    ui.add_enabled(
+                        settings.midi.metronome,
+                        egui::Slider::new(&mut settings.midi.metronome_volume, 0.0..=1.0)
+                            .text("Volume"),
+                    );
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Skipped Channels:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        The checked channels are ignored when loading, so\n\
+                        their notes are neither displayed nor played. Not\n\
+                        available with the Standard (Live) algorithm.\
+                        ",
+                    );
+                });
+                ui.vertical(|ui| {
+                    for (row, channels) in settings.midi.channel_filter.chunks_mut(8).enumerate() {
+                        ui.horizontal(|ui| {
+                            for (i, skipped) in channels.iter_mut().enumerate() {
+                                ui.toggle_value(skipped, format!("{}", row * 8 + i + 1));
+                            }
+                        });
+                    }
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Transpose Channels:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Shifts the notes of each channel by this many\n\
+                        semitones, on screen and in the audio. Notes that\n\
+                        end up outside of the 128 MIDI keys are dropped.\n\
+                        Not available with the Standard (Live) algorithm.\
+                        ",
+                    );
+                });
+                ui.vertical(|ui| {
+                    for (row, channels) in settings.midi.transpose.chunks_mut(8).enumerate() {
+                        ui.horizontal(|ui| {
+                            for (i, semitones) in channels.iter_mut().enumerate() {
+                                ui.add(
+                                    egui::DragValue::new(semitones).speed(0.1).range(-127..=127),
+                                )
+                                .on_hover_text(format!("Channel {}", row * 8 + i + 1));
+                            }
+                        });
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Overlapping Notes:");
+                egui::ComboBox::from_id_salt("note_overlap_select")
+                    .selected_text(settings.midi.note_overlap.as_str())
+                    .show_ui(ui, |ui| {
+                        for overlap in
+                            [NoteOverlap::Newest, NoteOverlap::Oldest, NoteOverlap::Blend]
+                        {
+                            ui.selectable_value(
+                                &mut settings.midi.note_overlap,
+                                overlap,
+                                overlap.as_str(),
+                            );
+                        }
+                    });
+                ui.end_row();
             });
 
         ui.horizontal(|ui| ui.add_space(width + 40.0));
@@ -126,6 +378,38 @@ impl SettingsWindow {
                                 }
                             });
                         });
+                        body.row(row_height, |mut row| {
+                            row.col(|ui| {
+                                if ui
+                                    .selectable_label(
+                                        settings.midi.colors == Colors::TrackPitch,
+                                        Colors::TrackPitch.as_str(),
+                                    )
+                                    .on_hover_text(
+                                        "The keys only change the brightness with the Cake algorithm",
+                                    )
+                                    .clicked()
+                                {
+                                    settings.midi.colors = Colors::TrackPitch;
+                                }
+                            });
+                        });
+                        body.row(row_height, |mut row| {
+                            row.col(|ui| {
+                                if ui
+                                    .selectable_label(
+                                        settings.midi.colors == Colors::Spectrum,
+                                        Colors::Spectrum.as_str(),
+                                    )
+                                    .on_hover_text(
+                                        "Spreads the hues once over all the keys, from the lowest to the highest",
+                                    )
+                                    .clicked()
+                                {
+                                    settings.midi.colors = Colors::Spectrum;
+                                }
+                            });
+                        });
                         let mut temp = self.palettes.clone();
                         for i in temp.iter_mut() {
                             i.selected = false;
@@ -173,5 +457,45 @@ impl SettingsWindow {
             ui.checkbox(&mut settings.midi.randomize_palette, " Randomize Palette")
                 .on_hover_text("Does not affect \"Rainbow\" and \"Random\" palettes.");
         });
+
+        if settings.midi.colors == Colors::TrackPitch {
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                ui.label("Hue per Track:");
+                ui.add(
+                    egui::DragValue::new(&mut settings.midi.track_hue_spread)
+                        .speed(0.5)
+                        .range(0.0..=360.0)
+                        .suffix("\u{b0}"),
+                );
+                ui.separator();
+                ui.label("Brightness:");
+                let [low, high] = &mut settings.midi.pitch_shade_range;
+                ui.add(egui::DragValue::new(low).speed(0.01).range(0.0..=1.0));
+                ui.label("-");
+                ui.add(egui::DragValue::new(high).speed(0.01).range(0.0..=1.0));
+            });
+        }
+
+        if settings.midi.colors == Colors::Spectrum {
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                ui.label("Hue of the Lowest / Highest Key:");
+                let [low, high] = &mut settings.midi.spectrum_hues;
+                ui.add(
+                    egui::DragValue::new(low)
+                        .speed(0.5)
+                        .range(0.0..=360.0)
+                        .suffix("\u{b0}"),
+                );
+                ui.label("-");
+                ui.add(
+                    egui::DragValue::new(high)
+                        .speed(0.5)
+                        .range(0.0..=360.0)
+                        .suffix("\u{b0}"),
+                );
+            });
+        }
     }
 }
@@ -10,7 +10,7 @@ use egui_extras::{Column, TableBuilder};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    gui::window::WasabiError,
+    error::WasabiError,
     settings::{WasabiSettings, WasabiSoundfont},
     state::WasabiState,
 };
@@ -156,7 +156,7 @@ impl EguiSFList {
             let recv = self.sf_picker.1.clone();
             if !recv.is_empty() {
                 if let Some(path) = recv.into_iter().next() {
-                    state.last_sf_location = path.clone();
+                    settings.gui.last_sf_location = path.clone();
                     if path.is_file() {
                         if let Err(err) = self.add_path(path.clone()) {
                             state
@@ -191,13 +191,13 @@ impl EguiSFList {
                             .clicked()
                         {
                             let sender = self.sf_picker.0.clone();
-                            let last_sf_location = state.last_sf_location.clone();
+                            let last_sf_location = settings.gui.last_sf_location.clone();
 
                             thread::spawn(move || {
                                 let midi_path = rfd::FileDialog::new()
                                     .add_filter(
                                         "Supported SoundFonts",
-                                        &["sfz", "SFZ", "sf2", "SF2"],
+                                        &["sfz", "SFZ", "sf2", "SF2", "dls", "DLS"],
                                     )
                                     .set_title("Pick SoundFonts...")
                                     .set_directory(
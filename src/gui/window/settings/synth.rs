@@ -23,6 +23,25 @@ impl SettingsWindow {
             .striped(true)
             .min_col_width(width / 2.0)
             .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Enable Audio:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        When disabled, no synth is loaded and MIDIs are parsed\n\
+                        without their audio, which saves time and memory.\n\
+                        The MIDI needs to be reloaded for this to apply.\
+                        ",
+                    );
+                });
+                if ui.checkbox(&mut settings.synth.audio_enabled, "").changed() {
+                    state.synth.switch(
+                        &settings.synth,
+                        state.loading_status.clone(),
+                        state.errors.clone(),
+                    );
+                }
+                ui.end_row();
+
                 let synth_prev = settings.synth.synth;
                 ui.label("Synthesizer:");
                 ui.horizontal(|ui| {
@@ -68,6 +87,41 @@ impl SettingsWindow {
                     }
                 });
                 ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("High Priority Playback:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Runs the thread that sends the events to the synth at\n\
+                        a higher priority, so it doesn't get starved when the\n\
+                        system is busy. Applies to the next loaded MIDI.\
+                        ",
+                    );
+                });
+                if ui
+                    .checkbox(&mut settings.synth.audio_thread_priority, "")
+                    .changed()
+                {
+                    state.synth.configure(&settings.synth);
+                }
+                ui.end_row();
+
+                ui.label("Pin Playback to Core:");
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.synth.audio_thread_core.is_some();
+                    ui.checkbox(&mut enabled, "");
+                    let mut core = settings.synth.audio_thread_core.unwrap_or(0);
+                    ui.add_enabled(
+                        enabled,
+                        egui::DragValue::new(&mut core).speed(1).range(0..=255),
+                    );
+                    let core = enabled.then_some(core);
+                    if core != settings.synth.audio_thread_core {
+                        settings.synth.audio_thread_core = core;
+                        state.synth.configure(&settings.synth);
+                    }
+                });
+                ui.end_row();
             });
 
         ui.add_space(8.0);
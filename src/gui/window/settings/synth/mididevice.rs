@@ -1,6 +1,9 @@
 use egui_extras::{Column, TableBuilder};
 
-use crate::{settings::WasabiSettings, state::WasabiState};
+use crate::{
+    settings::{MidiDeviceOutput, MidiRouting, WasabiSettings},
+    state::WasabiState,
+};
 
 use super::SettingsWindow;
 
@@ -12,6 +15,42 @@ impl SettingsWindow {
         state: &WasabiState,
         width: f32,
     ) {
+        let mut changed = false;
+
+        egui::Grid::new("mididevice_settings_grid")
+            .num_columns(2)
+            .spacing(super::super::SPACING)
+            .striped(true)
+            .min_col_width(width / 2.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Multiple Devices:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        How the events get sent when more than one device is\n\
+                        selected. When routing by channel, each device only\n\
+                        gets the channels in its range.\
+                        ",
+                    );
+                });
+                let routing_prev = settings.synth.midi_routing;
+                egui::ComboBox::from_id_salt("midi_routing_select")
+                    .selected_text(settings.synth.midi_routing.as_str())
+                    .show_ui(ui, |ui| {
+                        for routing in [MidiRouting::Duplicate, MidiRouting::Channels] {
+                            ui.selectable_value(
+                                &mut settings.synth.midi_routing,
+                                routing,
+                                routing.as_str(),
+                            );
+                        }
+                    });
+                changed |= settings.synth.midi_routing != routing_prev;
+                ui.end_row();
+            });
+
+        ui.add_space(8.0);
+        let by_channel = settings.synth.midi_routing == MidiRouting::Channels;
         egui::Frame::default()
             .rounding(egui::Rounding::same(8.0))
             .stroke(ui.style().visuals.widgets.noninteractive.bg_stroke)
@@ -22,39 +61,77 @@ impl SettingsWindow {
                         egui::Direction::LeftToRight,
                     ))
                     .resizable(true)
-                    .column(Column::exact(width).resizable(false))
+                    .column(Column::exact(width - 140.0).resizable(false))
+                    .column(Column::exact(130.0).resizable(false))
                     .body(|mut body| {
                         let row_height = super::super::SPACING[1] * 3.0;
 
-                        let mut temp = self.midi_devices.clone();
-                        for i in temp.iter_mut() {
-                            i.selected = false;
-                        }
-                        let mut changed = false;
-                        for (i, device) in self.midi_devices.iter_mut().enumerate() {
+                        for device in self.midi_devices.iter_mut() {
                             body.row(row_height, |mut row| {
                                 row.col(|ui| {
                                     if ui
                                         .selectable_label(device.selected, device.name.clone())
                                         .clicked()
                                     {
-                                        temp[i].selected = true;
-                                        settings.synth.midi_device = device.name.clone();
+                                        device.selected = !device.selected;
+                                        if device.selected {
+                                            settings.synth.midi_devices.push(MidiDeviceOutput {
+                                                name: device.name.clone(),
+                                                ..Default::default()
+                                            });
+                                        } else {
+                                            settings
+                                                .synth
+                                                .midi_devices
+                                                .retain(|d| d.name != device.name);
+                                        }
+                                        changed = true;
+                                    }
+                                });
+                                row.col(|ui| {
+                                    let Some(output) = settings
+                                        .synth
+                                        .midi_devices
+                                        .iter_mut()
+                                        .find(|d| d.name == device.name)
+                                    else {
+                                        return;
+                                    };
+
+                                    let mut first = *output.channels.start();
+                                    let mut last = *output.channels.end();
+                                    ui.add_enabled_ui(by_channel, |ui| {
+                                        ui.horizontal(|ui| {
+                                            ui.add(
+                                                egui::DragValue::new(&mut first)
+                                                    .speed(1)
+                                                    .range(1..=16),
+                                            );
+                                            ui.add(
+                                                egui::DragValue::new(&mut last)
+                                                    .speed(1)
+                                                    .range(first..=16),
+                                            );
+                                        });
+                                    });
+                                    if (first..=last) != output.channels {
+                                        output.channels = first..=last.max(first);
                                         changed = true;
                                     }
                                 });
                             });
                         }
-                        if changed {
-                            self.midi_devices = temp;
-                            state.synth.switch(
-                                &settings.synth,
-                                state.loading_status.clone(),
-                                state.errors.clone(),
-                            );
-                        }
                     });
             });
+
+        if changed {
+            state.synth.switch(
+                &settings.synth,
+                state.loading_status.clone(),
+                state.errors.clone(),
+            );
+        }
+
         ui.add_space(4.0);
         if ui.button("Refresh List").clicked() {
             self.load_midi_devices(settings)
@@ -1,7 +1,12 @@
 use egui::WidgetText;
 use egui_extras::{Column, TableBuilder};
 
-use crate::{settings::WasabiSettings, utils::NOTE_SPEED_RANGE};
+use crate::{
+    settings::{
+        CatchUp, EaseCurve, EndBehavior, GridConfig, GridMode, HitLineConfig, WasabiSettings,
+    },
+    utils::NOTE_SPEED_RANGE,
+};
 
 use super::SettingsWindow;
 
@@ -42,6 +47,177 @@ impl SettingsWindow {
                         .range(0.0..=f64::MAX),
                 );
                 ui.end_row();
+
+                ui.label("When the MIDI Ends:");
+                egui::ComboBox::from_id_salt("on_end_select")
+                    .selected_text(settings.gui.on_end.as_str())
+                    .show_ui(ui, |ui| {
+                        for behavior in [
+                            EndBehavior::Stop,
+                            EndBehavior::Loop,
+                            EndBehavior::Next,
+                            EndBehavior::Quit,
+                        ] {
+                            ui.selectable_value(
+                                &mut settings.gui.on_end,
+                                behavior,
+                                behavior.as_str(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Hold the End For:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        The last frame stays on screen for this many seconds\n\
+                        before the end behavior is applied. Useful for\n\
+                        recordings that shouldn't cut off right away.\
+                        ",
+                    );
+                });
+                ui.add(
+                    egui::DragValue::new(&mut settings.gui.end_hold_seconds)
+                        .speed(0.1)
+                        .range(0.0..=60.0)
+                        .suffix(" s"),
+                );
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Ease Seeking:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        The notes move to the new position over this many\n\
+                        seconds when seeking, instead of jumping there.\n\
+                        The audio always jumps right away.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.gui.seek_ease.is_some();
+                    if ui.checkbox(&mut enabled, "").changed() {
+                        settings.gui.seek_ease = enabled.then_some(0.3);
+                    }
+                    if let Some(ease) = settings.gui.seek_ease.as_mut() {
+                        ui.add(
+                            egui::DragValue::new(ease)
+                                .speed(0.01)
+                                .range(0.01..=2.0)
+                                .suffix(" s"),
+                        );
+                        egui::ComboBox::from_id_salt("seek_ease_curve_select")
+                            .selected_text(settings.gui.seek_ease_curve.as_str())
+                            .show_ui(ui, |ui| {
+                                for curve in [EaseCurve::Linear, EaseCurve::Out, EaseCurve::InOut] {
+                                    ui.selectable_value(
+                                        &mut settings.gui.seek_ease_curve,
+                                        curve,
+                                        curve.as_str(),
+                                    );
+                                }
+                            });
+                    }
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Frame Catch-Up:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Real Time: The notes jump ahead after a slow frame,\n\
+                        so they always match the audio.\n\
+                        Smooth: The notes only move a bit per frame and\n\
+                        catch up over the next frames, so they lag behind\n\
+                        the audio for a moment instead.\
+                        ",
+                    );
+                });
+                egui::ComboBox::from_id_salt("catchup_select")
+                    .selected_text(settings.gui.catchup.as_str())
+                    .show_ui(ui, |ui| {
+                        for catchup in [CatchUp::RealTime, CatchUp::Smooth] {
+                            ui.selectable_value(
+                                &mut settings.gui.catchup,
+                                catchup,
+                                catchup.as_str(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Poster Size:");
+                ui.horizontal(|ui| {
+                    let [width, height] = &mut settings.gui.poster_size;
+                    ui.add(egui::DragValue::new(width).speed(1).range(1..=16384));
+                    ui.label("x");
+                    ui.add(egui::DragValue::new(height).speed(1).range(1..=16384));
+                });
+                ui.end_row();
+
+                ui.label("Pause When Unfocused:");
+                ui.checkbox(&mut settings.gui.auto_pause_on_unfocus, "");
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Keep Position on Reload:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        When the loaded MIDI is opened again, for example to\n\
+                        apply new MIDI settings, playback continues where it\n\
+                        was instead of at the start. This only applies if the\n\
+                        file hasn't changed.\
+                        ",
+                    );
+                });
+                ui.checkbox(&mut settings.gui.keep_position_on_reload, "");
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Restore View State:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Restores the loop markers, the note speed and the\n\
+                        keyboard range saved with Save View State when the\n\
+                        same MIDI is opened again.\
+                        ",
+                    );
+                });
+                ui.checkbox(&mut settings.gui.restore_view_state, "");
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Mute While Scrubbing:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Mutes the audio while dragging the progress bar, and\n\
+                        continues it from the new position on release. Not\n\
+                        supported by the Live algorithm.\
+                        ",
+                    );
+                });
+                ui.checkbox(&mut settings.gui.mute_while_scrubbing, "");
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Idle FPS Limit:");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Limits the framerate while the MIDI is paused or the\n\
+                        window is unfocused, to save power. The window still\n\
+                        updates right away when using the mouse or keyboard.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.gui.idle_fps.is_some();
+                    ui.checkbox(&mut enabled, "");
+                    let mut fps = settings.gui.idle_fps.unwrap_or(30);
+                    ui.add_enabled(enabled, egui::DragValue::new(&mut fps).range(1..=240));
+                    settings.gui.idle_fps = enabled.then_some(fps);
+                });
+                ui.end_row();
             });
 
         ui.add_space(super::CATEG_SPACE);
@@ -79,6 +255,32 @@ impl SettingsWindow {
                     settings.scene.key_range = firstkey..=lastkey;
                 }
 
+                ui.horizontal(|ui| {
+                    ui.label("Fit Keyboard on Load: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Sets the keyboard range to the keys used by each\n\
+                        MIDI when it's loaded, with this many extra keys on\n\
+                        both sides. The range can still be changed by hand.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.scene.auto_zoom_keyboard.is_some();
+                    if ui.checkbox(&mut enabled, "").changed() {
+                        settings.scene.auto_zoom_keyboard = enabled.then_some(2);
+                    }
+                    if let Some(margin) = settings.scene.auto_zoom_keyboard.as_mut() {
+                        ui.add(
+                            egui::DragValue::new(margin)
+                                .speed(1)
+                                .range(0..=24)
+                                .suffix(" keys"),
+                        );
+                    }
+                });
+                ui.end_row();
+
                 ui.label("Note Speed: ");
                 ui.spacing_mut().slider_width = width / 2.0 - 100.0;
                 ui.add(
@@ -86,6 +288,361 @@ impl SettingsWindow {
                         .logarithmic(true),
                 );
                 ui.end_row();
+
+                ui.label("Black Key Width: ");
+                ui.add(egui::Slider::new(
+                    &mut settings.scene.black_key_width_frac,
+                    0.3..=1.0,
+                ));
+                ui.end_row();
+
+                ui.label("Black Key Height: ");
+                ui.add(egui::Slider::new(
+                    &mut settings.scene.black_key_height_frac,
+                    0.3..=0.95,
+                ));
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Key Fade In/Out (s): ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        How long the keys take to light up when pressed and to\n\
+                        go back when released, which smooths out fast repeated\n\
+                        notes. 0 changes the keys instantly.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut settings.scene.key_attack)
+                            .speed(0.01)
+                            .range(0.0..=2.0),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut settings.scene.key_release)
+                            .speed(0.01)
+                            .range(0.0..=2.0),
+                    );
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Key Tint Retain (s): ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        How long the keys keep a faint tint of the last note\n\
+                        color after being released. 0 disables it.\
+                        ",
+                    );
+                });
+                ui.add(
+                    egui::DragValue::new(&mut settings.scene.key_retain)
+                        .speed(0.01)
+                        .range(0.0..=5.0),
+                );
+                ui.end_row();
+
+                ui.label("Octave Lines: ");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut settings.scene.octave_lines, "");
+                    ui.add_enabled_ui(settings.scene.octave_lines, |ui| {
+                        ui.color_edit_button_srgba(&mut settings.scene.octave_line_color);
+                    });
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Marked Keys: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        A comma separated list of keys (0-127) that get a\n\
+                        bolder line when the octave lines are enabled.\
+                        ",
+                    );
+                });
+                let response = ui.add_enabled(
+                    settings.scene.octave_lines,
+                    egui::TextEdit::singleline(&mut self.marked_keys).hint_text("60, 72"),
+                );
+                if response.changed() {
+                    settings.scene.marked_keys = self
+                        .marked_keys
+                        .split(',')
+                        .filter_map(|key| key.trim().parse::<u8>().ok())
+                        .filter(|key| *key < 128)
+                        .collect();
+                }
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Time Grid: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Draws a line with a label every few seconds or bars.\n\
+                        Bars are not available with the Live algorithm.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.scene.time_grid.is_some();
+                    if ui.checkbox(&mut enabled, "").changed() {
+                        settings.scene.time_grid = enabled.then(GridConfig::default);
+                    }
+                    if let Some(grid) = settings.scene.time_grid.as_mut() {
+                        egui::ComboBox::from_id_salt("grid_mode_select")
+                            .selected_text(grid.mode.as_str())
+                            .show_ui(ui, |ui| {
+                                for mode in [GridMode::Seconds, GridMode::Bars] {
+                                    ui.selectable_value(&mut grid.mode, mode, mode.as_str());
+                                }
+                            });
+                        ui.add(
+                            egui::DragValue::new(&mut grid.interval)
+                                .speed(0.1)
+                                .range(0.1..=600.0)
+                                .prefix("every "),
+                        );
+                        ui.color_edit_button_srgba(&mut grid.color);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Hit Line: ");
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.scene.hit_line.is_some();
+                    if ui.checkbox(&mut enabled, "").changed() {
+                        settings.scene.hit_line = enabled.then(HitLineConfig::default);
+                    }
+                    if let Some(hit_line) = settings.scene.hit_line.as_mut() {
+                        ui.color_edit_button_srgba(&mut hit_line.color);
+                        ui.add(
+                            egui::DragValue::new(&mut hit_line.thickness)
+                                .speed(0.1)
+                                .range(0.5..=20.0)
+                                .suffix(" pt"),
+                        );
+                        ui.checkbox(&mut hit_line.glow, "Glow");
+                    }
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Overlay Tint: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        The color mixed into the notes of the overlay MIDI\n\
+                        (Ctrl + L), which is drawn behind the loaded MIDI.\n\
+                        The overlay shows all of its tracks and is muted,\n\
+                        only the loaded MIDI is heard.\
+                        ",
+                    );
+                });
+                ui.color_edit_button_srgba(&mut settings.scene.overlay_tint);
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Note Color Gamma: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        The exponent used to convert the note colors to\n\
+                        linear colors for the sRGB framebuffer. 2.2 matches\n\
+                        the colors of the keyboard exactly, while lower values\n\
+                        make the notes look brighter.\
+                        ",
+                    );
+                });
+                ui.add(
+                    egui::DragValue::new(&mut settings.scene.color_gamma)
+                        .speed(0.05)
+                        .range(1.0..=3.0),
+                );
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Dither Note Colors: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Adds a fine pattern to the note colors, which hides\n\
+                        the banding of smooth gradients. Frame captures are\n\
+                        no longer pixel exact with it.\
+                        ",
+                    );
+                });
+                ui.checkbox(&mut settings.scene.dither, "");
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Note Opacity: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Makes the notes transparent, so the overlapping notes\n\
+                        can be seen behind each other. The Cake renderer only\n\
+                        blends notes of different keys.\
+                        ",
+                    );
+                });
+                ui.add(egui::Slider::new(&mut settings.scene.note_alpha, 0.0..=1.0));
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Glow: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Adds a glow around the bright parts of the notes.\n\
+                        It has the same cost regardless of the note count.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut settings.scene.glow, "");
+                    ui.add_enabled(
+                        settings.scene.glow,
+                        egui::Slider::new(&mut settings.scene.glow_intensity, 0.0..=2.0),
+                    );
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Note Cap: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Tints the top part of every note. With a transparent\n\
+                        color, a lighter version of the note color is used.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.scene.note_cap.is_some();
+                    ui.checkbox(&mut enabled, "");
+                    let (mut height, color) = settings
+                        .scene
+                        .note_cap
+                        .unwrap_or((0.1, [1.0, 1.0, 1.0, 0.0]));
+                    let mut color = color.map(|c| (c * 255.0).round() as u8);
+                    ui.add_enabled_ui(enabled, |ui| {
+                        ui.add(egui::Slider::new(&mut height, 0.01..=1.0));
+                        ui.color_edit_button_srgba_unmultiplied(&mut color);
+                    });
+                    let color = color.map(|c| c as f32 / 255.0);
+                    settings.scene.note_cap = enabled.then_some((height, color));
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Note Spawn Animation (s): ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Fades the notes in as they enter the top of the screen,\n\
+                        instead of having them pop in. The Cake renderer only\n\
+                        fades them, without growing them in.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut settings.scene.spawn_animation, "");
+                    ui.add_enabled(
+                        settings.scene.spawn_animation,
+                        egui::Slider::new(&mut settings.scene.spawn_duration, 0.01..=1.0),
+                    );
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Minimum Visible Velocity: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Hides the notes that are quieter than this velocity.\n\
+                        They can still be heard, but don't light up the keys.\
+                        ",
+                    );
+                });
+                ui.add(egui::Slider::new(
+                    &mut settings.scene.min_visible_velocity,
+                    0..=127,
+                ));
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Key Remap: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Draws the notes of each key on another lane, without\n\
+                        changing the MIDI. Enabling it adds a table of 128 keys\n\
+                        to the settings file, which can be edited there.\
+                        ",
+                    );
+                });
+                let mut enabled = settings.scene.key_remap.is_some();
+                if ui.checkbox(&mut enabled, "").changed() {
+                    settings.scene.key_remap =
+                        enabled.then(|| std::array::from_fn(|key| key as u8));
+                }
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Max Visible Notes: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        When more notes than this are on screen, only part of\n\
+                        them get drawn, so very dense moments don't freeze the\n\
+                        renderer. Not supported by the Cake algorithm.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.scene.max_visible_notes.is_some();
+                    ui.checkbox(&mut enabled, "");
+                    let mut max = settings.scene.max_visible_notes.unwrap_or(10_000_000);
+                    ui.add_enabled(
+                        enabled,
+                        egui::DragValue::new(&mut max)
+                            .range(10_000..=1_000_000_000)
+                            .speed(10_000),
+                    );
+                    settings.scene.max_visible_notes = enabled.then_some(max);
+                });
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Polyphony Saturation: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        Makes the playing notes of a key more saturated the more\n\
+                        notes are playing on it at once, up to fully saturated at\n\
+                        the given count. Not supported by the Cake algorithm.\
+                        ",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    let mut enabled = settings.scene.polyphony_saturation.is_some();
+                    ui.checkbox(&mut enabled, "");
+                    let mut count = settings.scene.polyphony_saturation.unwrap_or(8);
+                    ui.add_enabled(enabled, egui::DragValue::new(&mut count).range(2..=256));
+                    settings.scene.polyphony_saturation = enabled.then_some(count);
+                });
+                ui.end_row();
+
+                ui.label("Anti-Aliasing (MSAA): ");
+                let msaa_text = |samples: u32| {
+                    if samples > 1 {
+                        format!("{}x", samples)
+                    } else {
+                        "Off".to_owned()
+                    }
+                };
+                egui::ComboBox::from_id_salt("msaa_select")
+                    .selected_text(msaa_text(settings.scene.msaa_samples))
+                    .show_ui(ui, |ui| {
+                        for samples in [1, 2, 4, 8] {
+                            ui.selectable_value(
+                                &mut settings.scene.msaa_samples,
+                                samples,
+                                msaa_text(samples),
+                            );
+                        }
+                    });
+                ui.end_row();
             });
 
         ui.add_space(super::CATEG_SPACE);
@@ -112,6 +669,46 @@ impl SettingsWindow {
                     0.0..=1.0,
                 ));
                 ui.end_row();
+
+                ui.label("Update Interval (s): ");
+                ui.add(
+                    egui::DragValue::new(&mut settings.scene.statistics.update_interval)
+                        .speed(0.01)
+                        .range(0.0..=1.0),
+                );
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Smoothing: ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        How much the displayed note count, NPS and voice count\n\
+                        lag behind their real values, to keep them from jittering.\n\
+                        0 shows the exact values on every update.\
+                        ",
+                    );
+                });
+                ui.add(egui::Slider::new(
+                    &mut settings.scene.statistics.smoothing,
+                    0.0..=0.95,
+                ));
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Upcoming Notes Range (s): ");
+                    ui.monospace("\u{2139}").on_hover_text(
+                        "\
+                        How far above the visible notes to look when counting\n\
+                        the upcoming notes.\
+                        ",
+                    );
+                });
+                ui.add(
+                    egui::DragValue::new(&mut settings.scene.statistics.preroll_seconds)
+                        .speed(0.1)
+                        .range(0.1..=10.0),
+                );
+                ui.end_row();
             });
 
         ui.add_space(8.0);
@@ -1,4 +1,4 @@
-use crate::{state::WasabiState, utils};
+use crate::{midi::MIDIFileBase, state::WasabiState, utils};
 
 use super::GuiWasabiWindow;
 
@@ -7,6 +7,17 @@ impl GuiWasabiWindow {
         let frame = utils::create_window_frame(ctx);
         let size = [400.0, 200.0];
 
+        // Greys out the shortcuts that seek backward when the loaded MIDI
+        // can't do that
+        let seeks_backward = self
+            .midi_file
+            .as_ref()
+            .map_or(true, |midi| midi.allows_seeking_backward());
+        let backward_label = |ui: &mut egui::Ui, text: &str| {
+            ui.add_enabled(seeks_backward, egui::Label::new(text))
+                .on_disabled_hover_text("This MIDI loading algorithm can only seek forward");
+        };
+
         egui::Window::new("Keyboard Shortcuts")
             .collapsible(false)
             .title_bar(true)
@@ -30,8 +41,16 @@ impl GuiWasabiWindow {
                         ui.label("Right Arrow");
                         ui.end_row();
 
-                        ui.label("Go Back");
-                        ui.label("Left Arrow");
+                        backward_label(ui, "Go Back");
+                        backward_label(ui, "Left Arrow");
+                        ui.end_row();
+
+                        ui.label(if seeks_backward {
+                            "Go To 0% - 90%"
+                        } else {
+                            "Go Forward To 0% - 90%"
+                        });
+                        ui.label("0 - 9");
                         ui.end_row();
 
                         ui.label("Slower Note Speed");
@@ -42,6 +61,23 @@ impl GuiWasabiWindow {
                         ui.label("Down Arrow");
                         ui.end_row();
 
+                        ui.label("Tap Tempo");
+                        ui.label("T");
+                        ui.end_row();
+
+                        ui.label("Reset Tapped Tempo");
+                        ui.label("Shift + T");
+                        ui.end_row();
+
+                        // Looping seeks back to the start of the loop
+                        backward_label(ui, "Set Loop Start / End");
+                        backward_label(ui, "[ / ]");
+                        ui.end_row();
+
+                        ui.label("Clear Loop");
+                        ui.label("\\");
+                        ui.end_row();
+
                         ui.label("Toggle Fullscreen");
                         ui.label("Alt + Enter");
                         ui.end_row();
@@ -58,9 +94,17 @@ impl GuiWasabiWindow {
                         ui.label("Ctrl + O");
                         ui.end_row();
 
+                        ui.label("Open / Remove Muted Overlay MIDI");
+                        ui.label("Ctrl + L");
+                        ui.end_row();
+
                         ui.label("Reset Synthesizer");
                         ui.label("Insert");
                         ui.end_row();
+
+                        ui.label("Toggle Debug Info");
+                        ui.label("Ctrl + D");
+                        ui.end_row();
                     });
             });
     }
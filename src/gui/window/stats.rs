@@ -1,9 +1,11 @@
+use std::time::Instant;
+
 use egui::{Context, Frame, Pos2};
 
 use crate::{
     gui::window::GuiWasabiWindow,
     midi::{MIDIFileBase, MIDIFileStats},
-    settings::{Statistics, WasabiSettings},
+    settings::{Statistics, StatisticsSettings, WasabiSettings},
     utils::convert_seconds_to_time_string,
 };
 
@@ -11,7 +13,11 @@ pub struct GuiMidiStats {
     time_passed: f64,
     time_total: f64,
     notes_on_screen: u64,
+    notes_drawn: u64,
     voice_count: Option<u64>,
+    render_load: Option<f32>,
+    desynced: bool,
+    subsampled: bool,
 }
 
 impl GuiMidiStats {
@@ -20,7 +26,11 @@ impl GuiMidiStats {
             time_passed: 0.0,
             time_total: 0.0,
             notes_on_screen: 0,
+            notes_drawn: 0,
             voice_count: None,
+            render_load: None,
+            desynced: false,
+            subsampled: false,
         }
     }
 
@@ -28,9 +38,101 @@ impl GuiMidiStats {
         self.voice_count = voices;
     }
 
+    pub fn set_render_load(&mut self, load: Option<f32>) {
+        self.render_load = load;
+    }
+
+    /// Whether the audio fell behind the visuals, see
+    /// [`crate::audio_playback::WasabiAudioPlayer::is_synced`]
+    pub fn set_desynced(&mut self, desynced: bool) {
+        self.desynced = desynced;
+    }
+
+    /// Whether only part of the notes were drawn, see
+    /// [`crate::settings::SceneSettings::max_visible_notes`]
+    pub fn set_subsampled(&mut self, subsampled: bool) {
+        self.subsampled = subsampled;
+    }
+
     pub fn set_rendered_note_count(&mut self, notes: u64) {
         self.notes_on_screen = notes;
     }
+
+    /// The notes that were actually drawn this frame, see
+    /// [`super::scene::RenderResultData::notes_drawn`]
+    pub fn set_drawn_note_count(&mut self, notes: u64) {
+        self.notes_drawn = notes;
+    }
+}
+
+/// The values shown in the stats overlay that change too quickly to be
+/// readable. They only get updated every `update_interval` seconds and are
+/// smoothed exponentially, while the values from the MIDI stay exact.
+pub struct SmoothedStats {
+    last_update: Option<Instant>,
+    last_passed_notes: Option<(f64, u64)>,
+    passed_notes: Option<f64>,
+    nps: Option<f64>,
+    voice_count: Option<f64>,
+    upcoming_notes: Option<f64>,
+}
+
+impl SmoothedStats {
+    pub fn new() -> Self {
+        Self {
+            last_update: None,
+            last_passed_notes: None,
+            passed_notes: None,
+            nps: None,
+            voice_count: None,
+            upcoming_notes: None,
+        }
+    }
+
+    fn smooth(current: Option<f64>, target: Option<f64>, smoothing: f64) -> Option<f64> {
+        match (current, target) {
+            (Some(current), Some(target)) => Some(target + (current - target) * smoothing),
+            (_, target) => target,
+        }
+    }
+
+    fn update(
+        &mut self,
+        time: f64,
+        passed_notes: Option<u64>,
+        voice_count: Option<u64>,
+        upcoming_notes: Option<u64>,
+        settings: &StatisticsSettings,
+    ) {
+        if let Some(last_update) = self.last_update {
+            if last_update.elapsed().as_secs_f32() < settings.update_interval {
+                return;
+            }
+        }
+        self.last_update = Some(Instant::now());
+
+        // The NPS is measured in MIDI time, so it stays the same while paused
+        // and starts over after seeking backwards
+        let nps = match (self.last_passed_notes, passed_notes) {
+            (Some((last_time, last)), Some(passed)) if time > last_time && passed >= last => {
+                Some((passed - last) as f64 / (time - last_time))
+            }
+            (Some((last_time, _)), Some(_)) if time == last_time => self.nps,
+            _ => None,
+        };
+        self.last_passed_notes = passed_notes.map(|passed| (time, passed));
+
+        let smoothing = settings.smoothing.clamp(0.0, 0.99) as f64;
+        self.passed_notes =
+            Self::smooth(self.passed_notes, passed_notes.map(|n| n as f64), smoothing);
+        self.nps = Self::smooth(self.nps, nps, smoothing);
+        self.voice_count = Self::smooth(self.voice_count, voice_count.map(|n| n as f64), smoothing);
+        self.upcoming_notes = Self::smooth(
+            self.upcoming_notes,
+            upcoming_notes.map(|n| n as f64),
+            smoothing,
+        );
+    }
 }
 
 fn num_or_q(num: Option<impl ToString>) -> String {
@@ -89,9 +191,11 @@ impl GuiWasabiWindow {
                 ui.spacing_mut().interact_size.y = 16.0;
 
                 let mut note_stats = MIDIFileStats::default();
+                let mut upcoming_notes = None;
+                let mut time = 0.0;
                 if let Some(midi_file) = self.midi_file.as_mut() {
                     stats.time_total = midi_file.midi_length().unwrap_or(0.0);
-                    let time = midi_file.timer().get_time().as_seconds_f64();
+                    time = midi_file.timer().get_time().as_seconds_f64();
 
                     if time > stats.time_total {
                         stats.time_passed = stats.time_total;
@@ -100,8 +204,24 @@ impl GuiWasabiWindow {
                     }
 
                     note_stats = midi_file.stats();
+
+                    let view_end = time + settings.scene.note_speed;
+                    upcoming_notes = midi_file.notes_starting_between(
+                        view_end,
+                        view_end + settings.scene.statistics.preroll_seconds,
+                    );
                 }
 
+                self.smoothed_stats.update(
+                    time,
+                    note_stats.passed_notes,
+                    stats.voice_count,
+                    upcoming_notes,
+                    &settings.scene.statistics,
+                );
+                let smoothed = &self.smoothed_stats;
+                let to_count = |n: Option<f64>| n.map(|n| n.round() as u64);
+
                 for i in settings.scene.statistics.order.iter().filter(|i| i.1) {
                     match i.0 {
                         Statistics::Time => {
@@ -131,7 +251,7 @@ impl GuiWasabiWindow {
                             });
                         }
                         Statistics::VoiceCount => {
-                            if let Some(voice_count) = stats.voice_count {
+                            if let Some(voice_count) = to_count(smoothed.voice_count) {
                                 ui.horizontal(|ui| {
                                     ui.monospace("Voice Count:");
                                     ui.with_layout(
@@ -143,6 +263,55 @@ impl GuiWasabiWindow {
                                 });
                             }
                         }
+                        Statistics::RenderLoad => {
+                            if let Some(render_load) = stats.render_load {
+                                let text = format!("{:.0}%", render_load * 100.0);
+                                let text = if render_load > 1.0 {
+                                    // Flash red when the synth can't keep up
+                                    let flash = (ctx.input(|i| i.time) * 4.0) as u64 % 2 == 0;
+                                    ctx.request_repaint();
+                                    if flash {
+                                        egui::RichText::new(text).color(egui::Color32::RED)
+                                    } else {
+                                        egui::RichText::new(text)
+                                    }
+                                } else {
+                                    egui::RichText::new(text)
+                                };
+
+                                ui.horizontal(|ui| {
+                                    ui.monospace("Render Load:");
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            ui.label(text.monospace());
+                                        },
+                                    );
+                                });
+                            }
+                        }
+                        Statistics::Nps => {
+                            ui.horizontal(|ui| {
+                                ui.monospace("NPS:");
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.monospace(num_or_q(to_count(smoothed.nps)));
+                                    },
+                                );
+                            });
+                        }
+                        Statistics::Upcoming => {
+                            ui.horizontal(|ui| {
+                                ui.monospace("Upcoming:");
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.monospace(num_or_q(to_count(smoothed.upcoming_notes)));
+                                    },
+                                );
+                            });
+                        }
                         Statistics::Rendered => {
                             ui.horizontal(|ui| {
                                 ui.monospace("Rendered:");
@@ -154,17 +323,75 @@ impl GuiWasabiWindow {
                                 );
                             });
                         }
+                        Statistics::Drawn => {
+                            let fps = self.fps.get_fps();
+                            let response = ui.horizontal(|ui| {
+                                ui.monospace("Drawn:");
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.monospace(format!("{}", stats.notes_drawn));
+                                    },
+                                );
+                            });
+                            if fps > 0 && stats.notes_drawn > 0 {
+                                // The frame time spread over the drawn notes
+                                let frame_us = 1_000_000.0 / fps as f64;
+                                response.response.on_hover_text(format!(
+                                    "{:.3} µs of frame time per 1000 notes",
+                                    frame_us / stats.notes_drawn as f64 * 1000.0
+                                ));
+                            }
+                        }
+                        Statistics::Remaining => {
+                            // Worked out from the smoothed passed notes, so it
+                            // always adds up with the note count
+                            let remaining = to_count(smoothed.passed_notes)
+                                .zip(note_stats.total_notes)
+                                .map(|(passed, total)| total.saturating_sub(passed));
+                            ui.horizontal(|ui| {
+                                ui.monospace("Remaining:");
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.monospace(num_or_q(remaining));
+                                    },
+                                );
+                            });
+                        }
                         Statistics::NoteCount => {
                             ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
                                 ui.monospace(format!(
                                     "{} / {}",
-                                    num_or_q(note_stats.passed_notes),
+                                    num_or_q(to_count(smoothed.passed_notes)),
                                     num_or_q(note_stats.total_notes)
                                 ));
                             });
                         }
                     };
                 }
+
+                if stats.desynced {
+                    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                        ui.label(
+                            egui::RichText::new("Audio Desync")
+                                .monospace()
+                                .color(egui::Color32::RED),
+                        )
+                        .on_hover_text("The audio can't keep up with the MIDI");
+                    });
+                }
+
+                if stats.subsampled {
+                    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                        ui.label(
+                            egui::RichText::new("Notes Subsampled")
+                                .monospace()
+                                .color(egui::Color32::YELLOW),
+                        )
+                        .on_hover_text("There are more notes on screen than the Max Visible Notes setting allows");
+                    });
+                }
             });
     }
 }
@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+use crate::midi::{tempo_at, MIDIFileBase, MIDIFileUnion, DEFAULT_TEMPO};
+
+/// Taps further apart than this start a new measurement
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Only the latest taps are averaged, so the tempo can drift
+const MAX_TAPS: usize = 8;
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 4.0;
+/// How quickly the speed follows the tapped tempo, per second
+const SMOOTHING: f64 = 4.0;
+
+/// Turns taps on a key into a playback speed, so the MIDI follows the
+/// tapped beat
+pub struct TapTempo {
+    taps: Vec<Instant>,
+    target: f64,
+}
+
+impl TapTempo {
+    pub fn new() -> Self {
+        Self {
+            taps: Vec::new(),
+            target: 1.0,
+        }
+    }
+
+    /// Registers a tap. The tapped beats are compared to the quarter notes
+    /// of the MIDI at the current position.
+    pub fn tap(&mut self, midi_file: &MIDIFileUnion) {
+        let now = Instant::now();
+        if self
+            .taps
+            .last()
+            .is_some_and(|last| now.duration_since(*last) > TAP_TIMEOUT)
+        {
+            self.taps.clear();
+        }
+
+        self.taps.push(now);
+        if self.taps.len() > MAX_TAPS {
+            self.taps.remove(0);
+        }
+
+        let (Some(first), Some(last)) = (self.taps.first(), self.taps.last()) else {
+            return;
+        };
+        if self.taps.len() < 2 {
+            return;
+        }
+
+        let interval = last.duration_since(*first).as_secs_f64() / (self.taps.len() - 1) as f64;
+        let time = midi_file.timer().get_time().as_seconds_f64();
+        let bpm = midi_file
+            .tempo_changes()
+            .and_then(|changes| tempo_at(changes, time))
+            .map(|change| change.bpm)
+            .unwrap_or(60_000_000.0 / DEFAULT_TEMPO as f64);
+
+        self.target = (60.0 / interval / bpm).clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    /// Goes back to the normal speed
+    pub fn reset(&mut self) {
+        self.taps.clear();
+        self.target = 1.0;
+    }
+
+    /// Moves the speed of the timer towards the tapped speed. It eases in
+    /// over a few frames, because sudden jumps make the audio stutter.
+    pub fn update(&self, midi_file: &mut MIDIFileUnion, delta: f64) {
+        let speed = midi_file.timer().speed();
+        if speed == self.target {
+            return;
+        }
+
+        let speed = if (self.target - speed).abs() < 0.001 {
+            self.target
+        } else {
+            speed + (self.target - speed) * (1.0 - (-SMOOTHING * delta).exp())
+        };
+        midi_file.timer_mut().set_speed(speed);
+    }
+}
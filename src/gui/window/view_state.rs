@@ -0,0 +1,96 @@
+use std::{fs, ops::RangeInclusive, path::PathBuf};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    error::WasabiError,
+    midi::MIDIFileUniqueSignature,
+    settings::{SceneSettings, WasabiSettings},
+    utils::NOTE_SPEED_RANGE,
+};
+
+/// The start and end of the part of a MIDI that gets looped, in seconds
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct LoopMarkers {
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+}
+
+impl LoopMarkers {
+    /// The looped part, if both markers are set and the end is after the start
+    pub fn range(&self) -> Option<(f64, f64)> {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) if end > start => Some((start, end)),
+            _ => None,
+        }
+    }
+
+    /// Removes the markers that are outside of a MIDI of the given length.
+    /// Returns whether any were removed.
+    pub fn keep_within(&mut self, length: f64) -> bool {
+        let valid = |time: &f64| (0.0..=length).contains(time);
+        let before = (self.start.is_some(), self.end.is_some());
+        self.start = self.start.filter(valid);
+        self.end = self.end.filter(valid);
+        before != (self.start.is_some(), self.end.is_some())
+    }
+}
+
+/// The view of a MIDI that can be saved and gets restored the next time the
+/// same file is opened, see [`crate::settings::GuiSettings::restore_view_state`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewState {
+    pub loop_markers: LoopMarkers,
+    pub note_speed: f64,
+    pub key_range: RangeInclusive<u8>,
+}
+
+impl ViewState {
+    pub fn new(loop_markers: LoopMarkers, settings: &SceneSettings) -> Self {
+        Self {
+            loop_markers,
+            note_speed: settings.note_speed,
+            key_range: settings.key_range.clone(),
+        }
+    }
+
+    /// Applies the note speed and the keyboard range to the settings
+    pub fn apply(&self, settings: &mut SceneSettings) {
+        settings.note_speed = self
+            .note_speed
+            .clamp(*NOTE_SPEED_RANGE.end(), *NOTE_SPEED_RANGE.start());
+        let first = (*self.key_range.start()).min(254);
+        let last = (*self.key_range.end()).clamp(first.saturating_add(1), 254);
+        settings.key_range = first..=last;
+    }
+
+    /// The view states are stored next to the config, one file per MIDI
+    fn path(signature: &MIDIFileUniqueSignature) -> PathBuf {
+        let mut path = WasabiSettings::get_config_dir();
+        path.push("view_states");
+        path.push(format!("{:016x}.json", signature.file_key()));
+        path
+    }
+
+    pub fn save(&self, signature: &MIDIFileUniqueSignature) -> Result<(), WasabiError> {
+        let path = Self::path(signature);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(WasabiError::FilesystemError)?;
+        }
+        let data = serde_json::to_string_pretty(self)
+            .map_err(|e| WasabiError::SettingsError(e.to_string()))?;
+        fs::write(path, data).map_err(WasabiError::FilesystemError)
+    }
+
+    /// Loads the saved view state of the MIDI, if there is one
+    pub fn load(signature: &MIDIFileUniqueSignature) -> Result<Option<Self>, WasabiError> {
+        let path = Self::path(signature);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read_to_string(path).map_err(WasabiError::FilesystemError)?;
+        serde_json::from_str(&data)
+            .map(Some)
+            .map_err(|e| WasabiError::SettingsError(e.to_string()))
+    }
+}
@@ -0,0 +1,89 @@
+#![feature(type_alias_impl_trait)]
+#![feature(coroutines)]
+#![feature(impl_trait_in_assoc_type)]
+
+mod app;
+mod audio_playback;
+pub mod engine;
+mod error;
+mod gui;
+mod midi;
+mod render_dir;
+mod renderer;
+mod scenes;
+mod settings;
+mod state;
+mod utils;
+
+use app::WasabiApplication;
+pub use gui::window::{ActiveNote, ActiveNotesCallback};
+use state::CaptureOptions;
+use std::path::PathBuf;
+use vulkano::swapchain::PresentMode;
+
+use egui_winit::winit::{
+    dpi::{LogicalSize, Size},
+    event_loop::EventLoop,
+};
+use winit::event_loop::ControlFlow;
+
+pub(crate) const WINDOW_SIZE: Size = Size::Logical(LogicalSize {
+    width: 1280.0,
+    height: 720.0,
+});
+
+pub(crate) const PRESENT_MODE: PresentMode = PresentMode::Immediate;
+pub(crate) const WAYLAND_PRESENT_MODE: PresentMode = PresentMode::Mailbox;
+pub(crate) const VSYNC_PRESENT_MODE: PresentMode = PresentMode::Fifo;
+
+/// Runs the player with the command line arguments, see the README for
+/// the available options
+pub fn run() {
+    start(None);
+}
+
+/// Like [`run`], but `on_active_notes` gets called with the pressed keys
+/// and the colors of their notes whenever they change, for example to
+/// drive external visualizers
+pub fn run_with_active_notes(on_active_notes: ActiveNotesCallback) {
+    start(Some(on_active_notes));
+}
+
+fn start(on_active_notes: Option<ActiveNotesCallback>) {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `render-dir <folder> --out <folder>` renders the MIDIs to WAV files
+    // without opening the window
+    if args.first().is_some_and(|arg| arg == "render-dir") {
+        std::process::exit(render_dir::run(&args[1..]));
+    }
+
+    let event_loop = EventLoop::new().unwrap();
+    event_loop.set_control_flow(ControlFlow::Poll);
+
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    // `--stdin` loads the MIDI piped into the program on startup
+    let startup_midi = args
+        .iter()
+        .any(|arg| arg == "--stdin")
+        .then(|| PathBuf::from(midi::STDIN_PATH));
+
+    // `--capture <dir>` saves every frame of the next loaded MIDI into `dir`,
+    // at the framerate given by `--fps` (60 by default), with its audio as a
+    // WAV file, and quits at the end
+    let capture = arg_value("--capture").map(|dir| CaptureOptions {
+        dir: PathBuf::from(dir),
+        fps: arg_value("--fps")
+            .and_then(|fps| fps.parse().ok())
+            .filter(|fps| *fps > 0)
+            .unwrap_or(60),
+    });
+
+    let mut app = WasabiApplication::new(startup_midi, capture, on_active_notes);
+    event_loop.run_app(&mut app).unwrap();
+}
@@ -35,20 +35,32 @@ impl LiveAudioPlayer {
 
     pub fn spawn_playback(mut self) -> JoinHandle<()> {
         thread::spawn(move || {
+            self.player.prepare_playback_thread();
+
             let mut seek_catching_up = false;
+            let mut controls = ControlState::new();
 
             let max_fall_time = 0.1;
 
-            let push_cc = |e: &CompressedAudio| {
+            let push_cc = |controls: &mut ControlState, e: &CompressedAudio| {
+                controls.update(e.iter_control_events());
                 self.player.push_events(e.iter_control_events());
             };
 
+            // The events before the new position can't be read again, so the
+            // controllers are restored from what was played so far
+            let reset_for_seek = |controls: &ControlState| {
+                self.player.full_reset();
+                self.player.push_events(controls.events());
+            };
+
             for event in self.events.into_iter() {
                 if self.timer.is_paused() {
                     self.player.reset();
                     match self.timer.wait_until_unpause() {
-                        UnpauseWaitResult::Unpaused => push_cc(&event),
+                        UnpauseWaitResult::Unpaused => push_cc(&mut controls, &event),
                         UnpauseWaitResult::UnpausedAndSeeked(time) => {
+                            reset_for_seek(&controls);
                             if time.as_seconds_f64() - event.time > max_fall_time {
                                 seek_catching_up = true;
                             }
@@ -61,7 +73,7 @@ impl LiveAudioPlayer {
                 if seek_catching_up {
                     let time = self.timer.get_time().as_seconds_f64();
                     if time - event.time > max_fall_time {
-                        push_cc(&event);
+                        push_cc(&mut controls, &event);
                         continue;
                     } else {
                         seek_catching_up = false;
@@ -75,7 +87,7 @@ impl LiveAudioPlayer {
                         continue;
                     }
                     WaitResult::Seeked(time) => {
-                        self.player.reset();
+                        reset_for_seek(&controls);
                         if time.as_seconds_f64() - event.time > max_fall_time {
                             seek_catching_up = true;
                         }
@@ -87,8 +99,91 @@ impl LiveAudioPlayer {
                     }
                 }
 
+                controls.update(event.iter_control_events());
                 self.player.push_events(event.iter_events());
+                self.player
+                    .report_played_position(event.time, self.timer.get_time().as_seconds_f64());
             }
         })
     }
 }
+
+/// The controllers, program and pitch bend per channel
+const CONTROL_SLOTS: usize = 130;
+
+/// The latest control events of every channel, so the synth can be brought
+/// back to the same state after a full reset
+struct ControlState {
+    events: Vec<Option<u32>>,
+}
+
+impl ControlState {
+    fn new() -> Self {
+        Self {
+            events: vec![None; 16 * CONTROL_SLOTS],
+        }
+    }
+
+    fn update(&mut self, events: impl Iterator<Item = u32>) {
+        for event in events {
+            let slot = match event & 0xF0 {
+                // The channel mode messages (e.g. reset all controllers)
+                // only matter when they are played
+                0xB0 if ((event >> 8) & 0x7F) >= 120 => continue,
+                0xB0 => ((event >> 8) & 0x7F) as usize,
+                0xC0 => 128,
+                0xE0 => 129,
+                _ => continue,
+            };
+            let channel = (event & 0x0F) as usize;
+            self.events[channel * CONTROL_SLOTS + slot] = Some(event);
+        }
+    }
+
+    /// The events in the order they can be sent again: the bank selects
+    /// come before the program changes
+    fn events(&self) -> impl '_ + Iterator<Item = u32> {
+        self.events.iter().flatten().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ControlState;
+
+    /// A packed event, as in [`crate::midi::shared::audio::CompressedAudio`]
+    fn event(status: u32, data1: u32, data2: u32) -> u32 {
+        status | (data1 << 8) | (data2 << 16)
+    }
+
+    #[test]
+    fn control_state_keeps_the_latest_values() {
+        let mut controls = ControlState::new();
+        controls.update(
+            [
+                // Volume on channel 1 twice, then a program on channel 2
+                event(0xB0, 7, 100),
+                event(0xB0, 7, 90),
+                event(0xC1, 5, 0),
+                // All notes off and the notes themselves aren't kept
+                event(0xB0, 123, 0),
+                event(0x90, 60, 100),
+                event(0xE1, 0, 0x50),
+                // Bank select
+                event(0xB1, 0, 1),
+            ]
+            .into_iter(),
+        );
+
+        let events: Vec<u32> = controls.events().collect();
+        assert_eq!(
+            events,
+            [
+                event(0xB0, 7, 90),
+                event(0xB1, 0, 1),
+                event(0xC1, 5, 0),
+                event(0xE1, 0, 0x50),
+            ]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+use time::Duration;
+
+use crate::{
+    audio_playback::WasabiAudioPlayer,
+    midi::{
+        shared::{
+            tempo_map::Beat,
+            timer::{SeekWaitResult, TimeListener, UnpauseWaitResult, WaitResult},
+        },
+        DRUM_CHANNEL,
+    },
+};
+
+/// The GM percussion keys used for the clicks
+const CLICK_KEY: u8 = 33;
+const ACCENT_KEY: u8 = 34;
+
+/// Plays a click on every beat of the MIDI, with an accent on the downbeats.
+///
+/// The clicks are sent to the same synth as the MIDI, on the percussion
+/// channel. This player never resets the synth, the MIDI audio player
+/// already does that when the playback gets paused or seeked.
+pub struct MetronomePlayer {
+    beats: Vec<Beat>,
+    timer: TimeListener,
+    player: Arc<WasabiAudioPlayer>,
+    index: usize,
+    volume: f32,
+}
+
+impl MetronomePlayer {
+    pub fn new(
+        beats: Vec<Beat>,
+        timer: TimeListener,
+        player: Arc<WasabiAudioPlayer>,
+        volume: f32,
+    ) -> Self {
+        MetronomePlayer {
+            beats,
+            timer,
+            player,
+            index: 0,
+            volume,
+        }
+    }
+
+    pub fn spawn_playback(mut self) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            if self.timer.is_paused() {
+                match self.timer.wait_until_unpause() {
+                    UnpauseWaitResult::Unpaused => {
+                        self.seek_to_time(self.timer.get_time().as_seconds_f64());
+                        continue;
+                    }
+                    UnpauseWaitResult::UnpausedAndSeeked(time) => {
+                        self.seek_to_time(time.as_seconds_f64());
+                        continue;
+                    }
+                    UnpauseWaitResult::Killed => break,
+                }
+            }
+
+            if self.index >= self.beats.len() {
+                match self.timer.wait_until_seeked() {
+                    SeekWaitResult::UnpausedAndSeeked(time) => {
+                        self.seek_to_time(time.as_seconds_f64());
+                        continue;
+                    }
+                    SeekWaitResult::Killed => break,
+                }
+            }
+
+            let beat = self.beats[self.index];
+            match self.timer.wait_until(Duration::seconds_f64(beat.time)) {
+                WaitResult::Ok => {}
+                WaitResult::Paused => continue,
+                WaitResult::Seeked(time) => {
+                    self.seek_to_time(time.as_seconds_f64());
+                    continue;
+                }
+                WaitResult::Killed => break,
+            }
+
+            self.click(beat.downbeat);
+            self.index += 1;
+        })
+    }
+
+    fn click(&self, accent: bool) {
+        let (key, velocity) = if accent {
+            (ACCENT_KEY, 127.0)
+        } else {
+            (CLICK_KEY, 100.0)
+        };
+        let velocity = (velocity * self.volume.clamp(0.0, 1.0)).max(1.0) as u32;
+        let channel = DRUM_CHANNEL as u32;
+
+        let note_on = (0x90 | channel) | (key as u32) << 8 | velocity << 16;
+        let note_off = (0x80 | channel) | (key as u32) << 8;
+        self.player.push_events([note_on, note_off].into_iter());
+    }
+
+    fn seek_to_time(&mut self, time: f64) {
+        self.index = self.beats.partition_point(|beat| beat.time < time);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod live;
+pub mod metronome;
+pub mod offline;
 pub mod ram;
@@ -0,0 +1,256 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use midi_toolkit::{
+    io::MIDIFile as TKMIDIFile,
+    pipe,
+    sequence::{
+        event::{cancel_tempo_events, scale_event_time},
+        unwrap_items, TimeCaster,
+    },
+};
+use xsynth_core::{
+    channel::{ChannelAudioEvent, ChannelConfigEvent, ChannelEvent, ControlEvent},
+    channel_group::{ChannelGroup, ChannelGroupConfig, ParallelismOptions, SynthEvent},
+    soundfont::{SampleSoundfont, SoundfontBase},
+    AudioPipe, AudioStreamParams, ChannelCount,
+};
+
+use crate::{
+    audio_playback::check_soundfont_format,
+    error::WasabiError,
+    midi::{effective_ppq, open_file_and_signature, shared::audio::CompressedAudio},
+    settings::{MidiSettings, SynthSettings},
+};
+
+pub const SAMPLE_RATE: u32 = 48000;
+/// How long the synth keeps rendering after the last event, so the
+/// releases of the last notes aren't cut off
+const TAIL_SECONDS: f64 = 2.0;
+/// The most samples per channel rendered at once
+const RENDER_CHUNK: usize = 4096;
+
+fn stream_params() -> AudioStreamParams {
+    AudioStreamParams::new(SAMPLE_RATE, ChannelCount::Stereo)
+}
+
+/// Loads the enabled soundfonts of the settings up front. The loaded
+/// soundfonts can be shared by several [`OfflineRenderer`]s.
+pub fn load_soundfonts(
+    settings: &SynthSettings,
+) -> Result<Vec<Arc<dyn SoundfontBase>>, WasabiError> {
+    let mut out: Vec<Arc<dyn SoundfontBase>> = Vec::new();
+    // Same order as the realtime synth, the first soundfont has the priority
+    for sf in settings.soundfonts.iter().rev().filter(|sf| sf.enabled) {
+        check_soundfont_format(&sf.path)?;
+        let soundfont = SampleSoundfont::new(&sf.path, stream_params(), sf.options)
+            .map_err(WasabiError::SoundFontLoadError)?;
+        out.push(Arc::new(soundfont));
+    }
+    Ok(out)
+}
+
+/// Reads the audio events of a MIDI, the same way the loaders do for the
+/// realtime playback
+pub fn read_audio_blocks(
+    path: &Path,
+    settings: &MidiSettings,
+) -> Result<Vec<CompressedAudio>, WasabiError> {
+    let (file, _) = open_file_and_signature(
+        path,
+        settings.ppq_override,
+        settings.channel_filter,
+        settings.transpose,
+    )?;
+    let midi = TKMIDIFile::open_from_stream(file, None).map_err(WasabiError::MidiLoadError)?;
+    let ppq = effective_ppq(midi.ppq(), settings);
+
+    let merged = pipe!(
+        midi.iter_all_track_events_merged_batches()
+        |>TimeCaster::<f64>::cast_event_delta()
+        |>cancel_tempo_events(250000)
+        |>scale_event_time(1.0 / ppq as f64)
+        |>unwrap_items()
+    );
+
+    Ok(CompressedAudio::build_blocks(
+        merged.map(Arc::new),
+        settings.channel_filter,
+        settings.transpose,
+    )
+    .collect())
+}
+
+/// Renders MIDI events with its own XSynth instance, as fast as possible
+/// instead of in real time
+pub struct OfflineRenderer {
+    synth: ChannelGroup,
+    buffer: Vec<f32>,
+}
+
+impl OfflineRenderer {
+    pub fn new(settings: &SynthSettings, soundfonts: Vec<Arc<dyn SoundfontBase>>) -> Self {
+        let config = &settings.xsynth.config;
+        let mut synth = ChannelGroup::new(ChannelGroupConfig {
+            channel_init_options: config.channel_init_options,
+            format: config.format,
+            audio_params: stream_params(),
+            parallelism: ParallelismOptions::default(),
+        });
+
+        let layers = settings
+            .xsynth
+            .limit_layers
+            .then_some(settings.xsynth.layers);
+        synth.send_event(SynthEvent::AllChannels(ChannelEvent::Config(
+            ChannelConfigEvent::SetLayerCount(layers),
+        )));
+        synth.send_event(SynthEvent::AllChannels(ChannelEvent::Config(
+            ChannelConfigEvent::SetSoundfonts(soundfonts),
+        )));
+
+        Self {
+            synth,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Renders the blocks into a 32-bit float stereo WAV file, starting at
+    /// `start` seconds into the MIDI. The events before the start are still
+    /// sent right away, so the channels are set up the same way.
+    pub fn render_to_wav(
+        &mut self,
+        blocks: &[CompressedAudio],
+        wav_path: &Path,
+        start: f64,
+    ) -> Result<(), WasabiError> {
+        let mut wav = WavWriter::create(wav_path)?;
+        let mut rendered = 0;
+
+        for block in blocks {
+            let sample = ((block.time - start).max(0.0) * SAMPLE_RATE as f64) as u64;
+            self.render(sample.saturating_sub(rendered), &mut wav)?;
+            rendered = rendered.max(sample);
+
+            for event in block.iter_events() {
+                if let Some(event) = synth_event(event) {
+                    self.synth.send_event(event);
+                }
+            }
+        }
+
+        self.render((TAIL_SECONDS * SAMPLE_RATE as f64) as u64, &mut wav)?;
+        wav.finish()
+    }
+
+    fn render(&mut self, samples: u64, wav: &mut WavWriter) -> Result<(), WasabiError> {
+        let mut left = samples as usize;
+        while left > 0 {
+            let count = left.min(RENDER_CHUNK);
+            self.buffer.clear();
+            self.buffer.resize(count * 2, 0.0);
+            self.synth.read_samples(&mut self.buffer);
+            wav.write_samples(&self.buffer)?;
+            left -= count;
+        }
+        Ok(())
+    }
+}
+
+/// Converts the packed events of [`CompressedAudio`] for the synth. The
+/// pressure events aren't supported by XSynth and get skipped.
+fn synth_event(event: u32) -> Option<SynthEvent> {
+    let status = (event & 0xFF) as u8;
+    let data1 = ((event >> 8) & 0x7F) as u8;
+    let data2 = ((event >> 16) & 0x7F) as u8;
+    let channel = (status & 0x0F) as u32;
+
+    let event = match status & 0xF0 {
+        0x80 => ChannelAudioEvent::NoteOff { key: data1 },
+        0x90 if data2 == 0 => ChannelAudioEvent::NoteOff { key: data1 },
+        0x90 => ChannelAudioEvent::NoteOn {
+            key: data1,
+            vel: data2,
+        },
+        0xB0 => ChannelAudioEvent::Control(ControlEvent::Raw(data1, data2)),
+        0xC0 => ChannelAudioEvent::ProgramChange(data1),
+        0xE0 => {
+            let value = ((data2 as i32) << 7 | data1 as i32) - 8192;
+            ChannelAudioEvent::Control(ControlEvent::PitchBendValue(value as f32 / 8192.0))
+        }
+        _ => return None,
+    };
+
+    Some(SynthEvent::Channel(channel, ChannelEvent::Audio(event)))
+}
+
+/// Writes interleaved stereo samples as a 32-bit float WAV file
+struct WavWriter {
+    writer: BufWriter<File>,
+    data_bytes: u64,
+}
+
+impl WavWriter {
+    const HEADER_LEN: u64 = 44;
+
+    fn create(path: &Path) -> Result<Self, WasabiError> {
+        let file = File::create(path).map_err(WasabiError::FilesystemError)?;
+        let mut wav = Self {
+            writer: BufWriter::new(file),
+            data_bytes: 0,
+        };
+        // The sizes are filled in by `finish`, once they are known
+        wav.write_header(0).map_err(WasabiError::FilesystemError)?;
+        Ok(wav)
+    }
+
+    fn write_header(&mut self, data_bytes: u32) -> std::io::Result<()> {
+        let channels = 2u16;
+        let bits = 32u16;
+        let block_align = channels * bits / 8;
+
+        let w = &mut self.writer;
+        w.write_all(b"RIFF")?;
+        w.write_all(&(data_bytes.saturating_add(Self::HEADER_LEN as u32 - 8)).to_le_bytes())?;
+        w.write_all(b"WAVEfmt ")?;
+        w.write_all(&16u32.to_le_bytes())?;
+        // 3 is the IEEE float format
+        w.write_all(&3u16.to_le_bytes())?;
+        w.write_all(&channels.to_le_bytes())?;
+        w.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        w.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+        w.write_all(&block_align.to_le_bytes())?;
+        w.write_all(&bits.to_le_bytes())?;
+        w.write_all(b"data")?;
+        w.write_all(&data_bytes.to_le_bytes())
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) -> Result<(), WasabiError> {
+        for sample in samples {
+            self.writer
+                .write_all(&sample.to_le_bytes())
+                .map_err(WasabiError::FilesystemError)?;
+        }
+        self.data_bytes += samples.len() as u64 * 4;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), WasabiError> {
+        if self.data_bytes > u32::MAX as u64 - Self::HEADER_LEN {
+            return Err(WasabiError::Other(
+                "The audio is too long for a WAV file".to_string(),
+            ));
+        }
+
+        let finish = |wav: &mut Self| -> std::io::Result<()> {
+            wav.writer.seek(SeekFrom::Start(0))?;
+            wav.write_header(wav.data_bytes as u32)?;
+            wav.writer.flush()
+        };
+        finish(&mut self).map_err(WasabiError::FilesystemError)
+    }
+}
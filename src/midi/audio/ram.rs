@@ -12,11 +12,18 @@ use crate::{
     },
 };
 
+/// Plays the audio events of a loaded MIDI along with its timer. Once all
+/// the events have been played, the thread keeps waiting for a seek, so
+/// looping and seeking back after the end restart the audio without
+/// rebuilding the player.
 pub struct InRamAudioPlayer {
     events: Vec<CompressedAudio>,
     timer: TimeListener,
     player: Arc<WasabiAudioPlayer>,
     index: usize,
+    /// Whether the audio was suppressed the last time it was checked,
+    /// see [`WasabiAudioPlayer::set_audio_suppressed`]
+    suppressed: bool,
 }
 
 impl InRamAudioPlayer {
@@ -30,61 +37,86 @@ impl InRamAudioPlayer {
             timer,
             player,
             index: 0,
+            suppressed: false,
         }
     }
 
     pub fn spawn_playback(mut self) -> JoinHandle<()> {
-        thread::spawn(move || loop {
-            let reset = || {
-                self.player.reset();
-            };
-
-            if self.timer.is_paused() {
-                reset();
-                match self.timer.wait_until_unpause() {
-                    UnpauseWaitResult::Unpaused => {
-                        self.seek_to_time(self.timer.get_time().as_seconds_f64());
-                        continue;
+        thread::spawn(move || {
+            self.player.prepare_playback_thread();
+
+            loop {
+                let reset = || {
+                    self.player.reset();
+                };
+
+                if self.timer.is_paused() {
+                    reset();
+                    match self.timer.wait_until_unpause() {
+                        UnpauseWaitResult::Unpaused => {
+                            self.seek_to_time(self.timer.get_time().as_seconds_f64());
+                            continue;
+                        }
+                        UnpauseWaitResult::UnpausedAndSeeked(time) => {
+                            self.seek_to_time(time.as_seconds_f64());
+                            continue;
+                        }
+                        UnpauseWaitResult::Killed => break,
                     }
-                    UnpauseWaitResult::UnpausedAndSeeked(time) => {
-                        self.seek_to_time(time.as_seconds_f64());
-                        continue;
+                }
+
+                if self.index >= self.events.len() {
+                    match self.timer.wait_until_seeked() {
+                        SeekWaitResult::UnpausedAndSeeked(time) => {
+                            self.seek_to_time(time.as_seconds_f64());
+                            continue;
+                        }
+                        SeekWaitResult::Killed => break,
                     }
-                    UnpauseWaitResult::Killed => break,
                 }
-            }
 
-            if self.index >= self.events.len() {
-                match self.timer.wait_until_seeked() {
-                    SeekWaitResult::UnpausedAndSeeked(time) => {
-                        self.seek_to_time(time.as_seconds_f64());
+                let suppressed = self.player.is_audio_suppressed();
+                if suppressed != self.suppressed {
+                    self.suppressed = suppressed;
+                    if suppressed {
+                        reset();
+                    } else {
+                        self.seek_to_time(self.timer.get_time().as_seconds_f64());
                         continue;
                     }
-                    SeekWaitResult::Killed => break,
                 }
-            }
 
-            let event = &self.events[self.index];
+                let event = &self.events[self.index];
 
-            let time = Duration::seconds_f64(event.time);
-            match self.timer.wait_until(time) {
-                WaitResult::Ok => {}
-                WaitResult::Paused => {
-                    continue;
-                }
-                WaitResult::Seeked(time) => {
-                    reset();
-                    self.seek_to_time(time.as_seconds_f64());
-                    continue;
+                let time = Duration::seconds_f64(event.time);
+                match self.timer.wait_until(time) {
+                    WaitResult::Ok => {}
+                    WaitResult::Paused => {
+                        continue;
+                    }
+                    WaitResult::Seeked(time) if self.suppressed => {
+                        // The synth gets caught up once the audio comes back
+                        self.index = self.find_time_index(time.as_seconds_f64());
+                        continue;
+                    }
+                    WaitResult::Seeked(time) => {
+                        reset();
+                        self.seek_to_time(time.as_seconds_f64());
+                        continue;
+                    }
+                    WaitResult::Killed => {
+                        reset();
+                        break;
+                    }
                 }
-                WaitResult::Killed => {
-                    reset();
-                    break;
+
+                if !self.suppressed {
+                    self.player.push_events(event.iter_events());
+                    self.player
+                        .report_played_position(event.time, self.timer.get_time().as_seconds_f64());
                 }
+                self.index += 1;
             }
-
-            self.player.push_events(event.iter_events());
-            self.index += 1;
         })
     }
 
@@ -126,8 +158,10 @@ impl InRamAudioPlayer {
     fn seek_to_time(&mut self, time: f64) {
         self.index = self.find_time_index(time);
 
-        // Reset and push all control events before
-        self.player.reset();
+        // Reset and push all control events before. The controllers get
+        // reset too, otherwise the ones changed after the new position (e.g.
+        // when looping back) would keep their values.
+        self.player.full_reset();
         for i in 0..(self.index) {
             self.player
                 .push_events(self.events[i].iter_control_events());
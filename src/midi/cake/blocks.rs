@@ -1,3 +1,5 @@
+use rustc_hash::FxHashSet;
+
 use crate::midi::{IntVector4, MIDIColor};
 
 pub struct CakeBlock {
@@ -10,9 +12,51 @@ pub struct CakeNoteData {
     pub start_time: u32,
     pub end_time: u32,
     pub color: MIDIColor,
+    pub velocity: u8,
+    /// The position of the note in the tree
+    pub tree_index: usize,
+}
+
+/// The shape of a note tree, for diagnosing slow queries
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeStats {
+    /// The most nodes passed on the way from the root to a note
+    pub depth: u32,
+    pub nodes: u32,
+}
+
+impl TreeStats {
+    /// Combines the stats of two trees, keeping the deepest depth
+    pub fn merge(self, other: TreeStats) -> TreeStats {
+        TreeStats {
+            depth: self.depth.max(other.depth),
+            nodes: self.nodes + other.nodes,
+        }
+    }
 }
 
 impl CakeBlock {
+    /// Walks the whole tree, so this is as slow as reading every node once
+    pub fn tree_stats(&self) -> TreeStats {
+        let mut stats = TreeStats::default();
+        let mut stack = vec![(self.tree[0].length_marker_len(), 1)];
+
+        while let Some((index, depth)) = stack.pop() {
+            let node = self.tree[index];
+            stats.nodes += 1;
+            stats.depth = stats.depth.max(depth);
+
+            // Negative offsets point to child nodes, positive ones to notes
+            for offset in [node.leaf_left(), node.leaf_right()] {
+                if offset < 0 {
+                    stack.push((index - (-offset) as usize, depth + 1));
+                }
+            }
+        }
+
+        stats
+    }
+
     pub fn get_note_at(&self, time: u32) -> Option<CakeNoteData> {
         let mut next_index = self.tree[0].length_marker_len();
 
@@ -33,7 +77,44 @@ impl CakeBlock {
             next_index -= offset as usize;
         }
 
-        let note = self.tree[next_index];
+        self.note_data(next_index)
+    }
+
+    /// The notes that are shown between `start` and `end`, in the order they
+    /// get shown. A note that gets covered by another one for a while and
+    /// then shows up again is only returned the first time.
+    pub fn get_notes_between(&self, start: u32, end: u32) -> Vec<CakeNoteData> {
+        let mut notes = Vec::new();
+        let mut returned = FxHashSet::default();
+        // The nodes and notes left to walk, the last one is the earliest
+        let mut stack = vec![(self.tree[0].length_marker_len(), false)];
+
+        while let Some((index, is_note)) = stack.pop() {
+            if is_note {
+                if returned.insert(index) {
+                    notes.extend(self.note_data(index));
+                }
+                continue;
+            }
+
+            let node = self.tree[index];
+            let cutoff = node.leaf_cutoff() as u32;
+            // Negative offsets point to child nodes, positive ones to notes
+            for (visible, offset) in [
+                (end > cutoff, node.leaf_right()),
+                (start < cutoff, node.leaf_left()),
+            ] {
+                if visible {
+                    stack.push((index - offset.unsigned_abs() as usize, offset > 0));
+                }
+            }
+        }
+
+        notes
+    }
+
+    fn note_data(&self, index: usize) -> Option<CakeNoteData> {
+        let note = self.tree[index];
 
         if note.is_note_empty() {
             None
@@ -42,23 +123,38 @@ impl CakeBlock {
                 start_time: note.note_start(),
                 end_time: note.note_end(),
                 color: MIDIColor::from_u32(note.note_color()),
+                velocity: note.note_velocity(),
+                tree_index: index,
             })
         }
     }
+
+    /// The number of notes that start at or before `time`, like
+    /// [`crate::midi::note_started`]. Every note start is a cutoff of the
+    /// tree, so this is the count of the first cutoff after `time`.
     pub fn get_notes_passed_at(&self, time: i32) -> u32 {
+        // There is no cutoff at zero, the notes start there at the earliest
+        if time < 0 {
+            return 0;
+        }
+
         let mut last_notes_passed;
+        let mut first_cutoff_after = None;
         let mut next_index = self.tree[0].length_marker_len();
 
         loop {
             let node = self.tree[next_index];
+            let notes_to_the_left = node.leaf_notes_to_the_left();
 
+            // Each cutoff going left is earlier than the previous one
             let offset = if time < node.leaf_cutoff() {
+                first_cutoff_after = Some(notes_to_the_left);
                 node.leaf_left()
             } else {
                 node.leaf_right()
             };
 
-            last_notes_passed = node.leaf_notes_to_the_left();
+            last_notes_passed = notes_to_the_left;
 
             if offset > 0 {
                 break;
@@ -67,6 +163,93 @@ impl CakeBlock {
             next_index -= offset as usize;
         }
 
-        last_notes_passed
+        // Past the last cutoff every note has started, except the ones that
+        // start right at the end of the tree
+        first_cutoff_after.unwrap_or(last_notes_passed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        midi::{cake::tree_serializer::TreeSerializer, note_started, IntVector4},
+        settings::NoteOverlap,
+    };
+
+    use super::CakeBlock;
+
+    const FIRST: i32 = 0x112233;
+    const SECOND: i32 = 0x445566;
+
+    /// A key with a long note from 0 to 100 and a short one from 20 to 50
+    /// on top of it
+    fn covered_note_block() -> CakeBlock {
+        let mut serializer = TreeSerializer::new(NoteOverlap::Newest);
+        serializer.start_note(0, 0, IntVector4::pack_note_color(FIRST, 100));
+        serializer.start_note(20, 1, IntVector4::pack_note_color(SECOND, 50));
+        serializer.end_note(50, 1);
+        serializer.end_note(100, 0);
+
+        CakeBlock {
+            start_time: 0,
+            end_time: 200,
+            tree: serializer.complete_and_seal(200),
+        }
+    }
+
+    #[test]
+    fn notes_pass_at_their_start() {
+        // Two of the notes start at the same time
+        let starts = [0, 20, 60, 60];
+        let mut serializer = TreeSerializer::new(NoteOverlap::Newest);
+        serializer.start_note(0, 0, 0);
+        serializer.start_note(20, 1, 0);
+        serializer.end_note(50, 1);
+        serializer.start_note(60, 2, 0);
+        serializer.start_note(60, 3, 0);
+        serializer.end_note(70, 2);
+        serializer.end_note(80, 3);
+        serializer.end_note(100, 0);
+        let block = CakeBlock {
+            start_time: 0,
+            end_time: 200,
+            tree: serializer.complete_and_seal(200),
+        };
+
+        for time in [-1, 0, 1, 19, 20, 21, 59, 60, 61, 100, 150, 1000] {
+            let started = starts
+                .iter()
+                .filter(|start| note_started(**start as f64, time as f64))
+                .count() as u32;
+            assert_eq!(block.get_notes_passed_at(time), started, "at {time}");
+        }
+    }
+
+    #[test]
+    fn notes_between_are_in_shown_order() {
+        let block = covered_note_block();
+
+        let colors = |start, end| {
+            block
+                .get_notes_between(start, end)
+                .iter()
+                .map(|note| note.color.as_u32() as i32)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(colors(0, 200), [FIRST, SECOND]);
+        assert_eq!(colors(30, 40), [SECOND]);
+        assert_eq!(colors(60, 200), [FIRST]);
+        assert_eq!(colors(150, 200), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn notes_between_keep_their_full_length() {
+        let block = covered_note_block();
+        let notes = block.get_notes_between(60, 70);
+
+        assert_eq!(notes.len(), 1);
+        assert_eq!((notes[0].start_time, notes[0].end_time), (0, 100));
+        assert_eq!(notes[0].velocity, 100);
     }
 }
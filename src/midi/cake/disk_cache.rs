@@ -0,0 +1,233 @@
+use std::{
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use rustc_hash::FxHasher;
+
+use crate::{
+    error::WasabiError,
+    midi::{shared::parse_report::NotePairing, IntVector4, MIDIColor, MIDIFileUniqueSignature},
+    settings::MidiSettings,
+};
+
+use super::blocks::CakeBlock;
+
+const CACHE_MAGIC: &[u8; 4] = b"WCKC";
+const CACHE_EXTENSION: &str = "cake";
+/// Bump this whenever the layout of the file or of the trees changes, so
+/// the older caches get ignored instead of misread
+const CACHE_VERSION: u32 = 3;
+
+/// A key for everything the trees depend on: the file, the loading settings
+/// and the note colors, which are stored in the notes themselves
+pub(super) fn cache_key(
+    signature: &MIDIFileUniqueSignature,
+    settings: &MidiSettings,
+    ticks_per_second: u32,
+    track_colors: &[MIDIColor],
+) -> u64 {
+    let mut hasher = FxHasher::default();
+    (
+        signature.file_key(),
+        signature.ppq_override,
+        signature.channel_filter,
+        signature.transpose,
+        ticks_per_second,
+        settings.note_overlap as usize,
+        settings.drum_lane,
+        settings.colors as usize,
+        settings.track_hue_spread.to_bits(),
+        settings.pitch_shade_range.map(f32::to_bits),
+        settings.spectrum_hues.map(f64::to_bits),
+    )
+        .hash(&mut hasher);
+    for color in track_colors {
+        color.as_u32().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+pub(super) fn cache_path(dir: &Path, key: u64) -> PathBuf {
+    dir.join(format!("{key:016x}.{CACHE_EXTENSION}"))
+}
+
+/// The layout is the magic, the version and the key, followed by how the
+/// notes were paired while building the trees, and by the blocks. The
+/// pairing is kept because it can't be worked out from the trees.
+///
+/// Each block is its start and end time, the length of its tree and the
+/// tree itself. The caches aren't meant to be shared between machines, so
+/// everything is stored in the native byte order.
+pub(super) fn write_blocks(
+    path: &Path,
+    key: u64,
+    pairing: NotePairing,
+    blocks: &[CakeBlock],
+) -> Result<(), WasabiError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(WasabiError::FilesystemError)?;
+    }
+
+    // Written under another name first, so an interrupted write never
+    // leaves a truncated cache behind
+    let temp_path = path.with_extension("tmp");
+    let file = File::create(&temp_path).map_err(WasabiError::FilesystemError)?;
+    let mut writer = BufWriter::new(file);
+
+    let result = write_to(&mut writer, key, pairing, blocks);
+    // The file has to be closed before it can be renamed or removed
+    drop(writer);
+
+    result
+        .and_then(|_| fs::rename(&temp_path, path))
+        .map_err(|e| {
+            fs::remove_file(&temp_path).ok();
+            WasabiError::FilesystemError(e)
+        })
+}
+
+fn write_to(
+    writer: &mut impl Write,
+    key: u64,
+    pairing: NotePairing,
+    blocks: &[CakeBlock],
+) -> std::io::Result<()> {
+    writer.write_all(CACHE_MAGIC)?;
+    writer.write_all(&CACHE_VERSION.to_ne_bytes())?;
+    writer.write_all(&key.to_ne_bytes())?;
+    writer.write_all(&pairing.overlapping_notes.to_ne_bytes())?;
+    writer.write_all(&pairing.unmatched_note_offs.to_ne_bytes())?;
+    writer.write_all(&pairing.unended_notes.to_ne_bytes())?;
+    writer.write_all(&(blocks.len() as u64).to_ne_bytes())?;
+    for block in blocks {
+        writer.write_all(&block.start_time.to_ne_bytes())?;
+        writer.write_all(&block.end_time.to_ne_bytes())?;
+        writer.write_all(&(block.tree.len() as u64).to_ne_bytes())?;
+        writer.write_all(bytemuck::cast_slice(&block.tree))?;
+    }
+    writer.flush()
+}
+
+/// Returns `None` for missing, unreadable or stale caches, which then just
+/// get rebuilt
+pub(super) fn read_blocks(path: &Path, key: u64) -> Option<(Vec<CakeBlock>, NotePairing)> {
+    let file = File::open(path).ok()?;
+    let file_len = file.metadata().ok()?.len();
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).ok()?;
+    if &magic != CACHE_MAGIC
+        || read_u32(&mut reader)? != CACHE_VERSION
+        || read_u64(&mut reader)? != key
+    {
+        return None;
+    }
+
+    let pairing = NotePairing {
+        overlapping_notes: read_u64(&mut reader)?,
+        unmatched_note_offs: read_u64(&mut reader)?,
+        unended_notes: read_u64(&mut reader)?,
+    };
+
+    let block_count = read_u64(&mut reader)?;
+    let mut blocks = Vec::new();
+    for _ in 0..block_count {
+        let start_time = read_u32(&mut reader)?;
+        let end_time = read_u32(&mut reader)?;
+        let len = read_u64(&mut reader)?;
+        // Every tree starts with its length marker, and a damaged length
+        // shouldn't allocate more than the file could hold
+        if len == 0 || len > file_len / size_of::<IntVector4>() as u64 {
+            return None;
+        }
+        let len = len as usize;
+
+        let mut tree = vec![IntVector4::default(); len];
+        reader
+            .read_exact(bytemuck::cast_slice_mut(&mut tree))
+            .ok()?;
+
+        blocks.push(CakeBlock {
+            start_time,
+            end_time,
+            tree,
+        });
+    }
+
+    // The modification time orders the caches for `evict`, so reading one
+    // counts as using it. Access times aren't updated on every system.
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+        .ok();
+
+    Some((blocks, pairing))
+}
+
+/// The caches in `dir` with their last use and size, most recent first
+fn list_caches(dir: &Path) -> std::io::Result<Vec<(PathBuf, SystemTime, u64)>> {
+    let mut caches = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == CACHE_EXTENSION) {
+            let metadata = fs::metadata(&path)?;
+            caches.push((path, metadata.modified()?, metadata.len()));
+        }
+    }
+    caches.sort_by(|a, b| b.1.cmp(&a.1));
+    Ok(caches)
+}
+
+/// Removes the least recently used caches until the rest fit in `max_bytes`.
+/// The most recent cache is always kept, even if it's larger on its own.
+pub(super) fn evict(dir: &Path, max_bytes: u64) -> Result<(), WasabiError> {
+    let caches = list_caches(dir).map_err(WasabiError::FilesystemError)?;
+
+    let mut total = 0;
+    for (i, (path, _, len)) in caches.into_iter().enumerate() {
+        total += len;
+        if i > 0 && total > max_bytes {
+            fs::remove_file(path).map_err(WasabiError::FilesystemError)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes every cache in `dir`, along with the unfinished writes
+pub(super) fn clear(dir: &Path) -> Result<(), WasabiError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        // Nothing was cached yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(WasabiError::FilesystemError(e)),
+    };
+
+    for entry in entries {
+        let path = entry.map_err(WasabiError::FilesystemError)?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext == CACHE_EXTENSION || ext == "tmp")
+        {
+            fs::remove_file(path).map_err(WasabiError::FilesystemError)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> Option<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u32::from_ne_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> Option<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u64::from_ne_bytes(bytes))
+}
@@ -1,5 +1,7 @@
 use bytemuck::{Pod, Zeroable};
 
+use crate::midi::MIDIColor;
+
 #[repr(C)]
 #[derive(Pod, Debug, Copy, Clone, Zeroable)]
 pub struct IntVector4 {
@@ -83,8 +85,26 @@ impl IntVector4 {
         self.val2 as u32
     }
 
+    /// Stores the velocity in the unused top byte of a note color. The
+    /// velocity is at most 127, so the value never becomes the empty marker.
+    pub fn pack_note_color(color: i32, velocity: u8) -> i32 {
+        (color & 0xFFFFFF) | ((velocity as i32 & 0x7F) << 24)
+    }
+
+    /// Mixes the color of a packed note color halfway with another one,
+    /// keeping its velocity
+    pub fn blend_note_color(color: i32, covered: i32) -> i32 {
+        let rgb = |color: i32| MIDIColor::from_u32(color as u32 & 0xFFFFFF);
+        let mixed = rgb(color).mix(rgb(covered), 0.5).as_u32() as i32;
+        (color & !0xFFFFFF) | mixed
+    }
+
     pub fn note_color(&self) -> u32 {
-        self.val3 as u32
+        self.val3 as u32 & 0xFFFFFF
+    }
+
+    pub fn note_velocity(&self) -> u8 {
+        (self.val3 >> 24) as u8 & 0x7F
     }
 
     pub fn is_note_empty(&self) -> bool {
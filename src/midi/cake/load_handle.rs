@@ -0,0 +1,87 @@
+use std::{path::PathBuf, sync::Arc, thread};
+
+use crossbeam_channel::{Receiver, TryRecvError};
+
+use crate::{
+    audio_playback::WasabiAudioPlayer,
+    error::WasabiError,
+    midi::{shared::load_progress::LoadProgress, NoteColorFn},
+    settings::MidiSettings,
+};
+
+use super::CakeMIDIFile;
+
+/// Gets called on the loading thread once a load finishes or fails, see
+/// [`CakeMIDIFile::load_async`]. The loaded MIDI itself still comes from
+/// [`CakeLoadHandle::poll`], so the error is only lent to the callback.
+pub type LoadCallback = Box<dyn FnOnce(Result<(), &WasabiError>) + Send>;
+
+/// A Cake MIDI that is loading on its own thread, see [`CakeMIDIFile::load_async`]
+pub struct CakeLoadHandle {
+    progress: Arc<LoadProgress>,
+    result: Receiver<Result<CakeMIDIFile, WasabiError>>,
+}
+
+impl CakeLoadHandle {
+    pub(super) fn spawn(
+        path: PathBuf,
+        player: Option<Arc<WasabiAudioPlayer>>,
+        settings: MidiSettings,
+        color_fn: Option<NoteColorFn>,
+        on_load_complete: Option<LoadCallback>,
+    ) -> Self {
+        let progress = Arc::new(LoadProgress::default());
+        let (snd, rcv) = crossbeam_channel::bounded(1);
+
+        let thread_progress = progress.clone();
+        thread::spawn(move || {
+            let result = CakeMIDIFile::load_with_progress(
+                path,
+                player,
+                &settings,
+                color_fn,
+                &thread_progress,
+            );
+            if let Some(on_load_complete) = on_load_complete {
+                on_load_complete(result.as_ref().map(|_| ()));
+            }
+            snd.send(result).ok();
+        });
+
+        Self {
+            progress,
+            result: rcv,
+        }
+    }
+
+    /// How far into the MIDI the events have been read, in seconds
+    pub fn parsed_seconds(&self) -> f64 {
+        self.progress.parsed_seconds()
+    }
+
+    /// How much of the file has been read, from 0 to 1, see
+    /// [`LoadProgress::fraction`]
+    pub fn fraction(&self) -> Option<f32> {
+        self.progress.fraction()
+    }
+
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.progress.is_cancelled()
+    }
+
+    /// Returns the loaded MIDI or the error once loading has finished,
+    /// without blocking
+    pub fn poll(&mut self) -> Option<Result<CakeMIDIFile, WasabiError>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(WasabiError::Other(
+                "The MIDI loading thread stopped unexpectedly".to_string(),
+            ))),
+        }
+    }
+}
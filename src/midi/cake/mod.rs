@@ -1,4 +1,14 @@
-use std::{path::PathBuf, sync::Arc, thread};
+use directories::BaseDirs;
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    thread,
+    time::Instant,
+};
 use time::Duration;
 
 use midi_toolkit::{
@@ -13,114 +23,349 @@ use midi_toolkit::{
 
 use crate::{
     audio_playback::WasabiAudioPlayer,
-    gui::window::WasabiError,
+    error::WasabiError,
     midi::{
-        audio::ram::InRamAudioPlayer,
+        audio::{metronome::MetronomePlayer, ram::InRamAudioPlayer},
         cake::tree_threader::{NoteEvent, ThreadedTreeSerializers},
-        open_file_and_signature,
-        shared::{audio::CompressedAudio, timer::TimeKeeper},
-        MIDIColor,
+        channel_filtered, display_key, effective_ppq, note_off_key, open_file_and_signature,
+        shared::{
+            audio::CompressedAudio,
+            instruments::{Instrument, InstrumentsBuilder},
+            load_progress::LoadProgress,
+            parse_report::{NotePairing, ParseReport, ParseReportBuilder},
+            tempo_map::{TempoMap, TempoMapBuilder},
+            timer::TimeKeeper,
+        },
+        transposed_key, IntVector4, LoadStats, MIDIColor, NoteColorFn, NoteInfo,
     },
-    settings::MidiSettings,
+    settings::{Colors, MidiSettings, WasabiSettings},
 };
 
-use self::blocks::CakeBlock;
+use self::{
+    blocks::{CakeBlock, TreeStats},
+    load_handle::{CakeLoadHandle, LoadCallback},
+};
 
-use super::{MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature};
+use super::{
+    Beat, MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature, NoteDetails, NoteId, TempoChange,
+    VisibleNote,
+};
 
 pub mod blocks;
+mod disk_cache;
 pub mod intvec4;
+pub mod load_handle;
 mod tree_serializer;
 mod tree_threader;
 mod unended_note_batch;
 
+/// The tree ticks are `i32`, so the longest MIDI that can be loaded is
+/// `i32::MAX / ticks_per_second` seconds (about 59.6 hours at the default
+/// 10000 ticks per second)
+pub fn max_cake_length(ticks_per_second: u32) -> f64 {
+    i32::MAX as f64 / ticks_per_second as f64
+}
+
+/// How many batches of events get written between the updates of the read
+/// bytes, see [`LoadProgress::set_read_bytes`]
+const PROGRESS_INTERVAL: usize = 1024;
+
+/// The supported time resolutions of the trees, see
+/// [`MidiSettings::cake_ticks_per_second`]
+pub const CAKE_TICKS_PER_SECOND_RANGE: RangeInclusive<u32> = 1000..=100_000;
+
 pub struct CakeMIDIFile {
-    blocks: Vec<CakeBlock>,
+    /// Shared with the thread that writes the cache
+    blocks: Arc<[CakeBlock]>,
     timer: TimeKeeper,
     length: f64,
     note_count: u64,
+    key_range: Option<(u8, u8)>,
+    track_note_counts: Vec<u64>,
+    velocity_counts: [u64; 128],
+    first_note: Option<f64>,
+    instruments: Vec<Instrument>,
+    parse_report: ParseReport,
+    tempo_map: TempoMap,
+    track_colors: Vec<MIDIColor>,
     ticks_per_second: u32,
+    /// The tick up to which the notes have been added to the trees, written
+    /// by the loading thread
+    parsed_ticks: Arc<AtomicU64>,
     signature: MIDIFileUniqueSignature,
+    /// Identifies the trees on disk, see [`CakeMIDIFile::save_cache`]. The
+    /// notes colored by a [`NoteColorFn`] can't be cached.
+    cache_key: Option<u64>,
+    /// Whether the trees were built during this load and still have to be
+    /// cached, see [`CakeMIDIFile::cache_writer`]
+    cache_pending: bool,
+    /// See [`MidiSettings::cake_cache_max_gb`]
+    cache_max_bytes: u64,
+    ppq: u16,
+    load_stats: LoadStats,
+    /// Computed when first requested, see [`CakeMIDIFile::tree_stats`]
+    tree_stats: OnceLock<TreeStats>,
+}
+
+/// The trees of a [`CakeMIDIFile`] that still have to be cached, see
+/// [`CakeMIDIFile::cache_writer`]
+pub struct CakeCacheWriter {
+    key: u64,
+    pairing: NotePairing,
+    blocks: Arc<[CakeBlock]>,
+    max_bytes: u64,
+}
+
+impl CakeCacheWriter {
+    /// See [`CakeMIDIFile::save_cache`]
+    pub fn save(self, dir: &Path) -> Result<(), WasabiError> {
+        let path = disk_cache::cache_path(dir, self.key);
+        disk_cache::write_blocks(&path, self.key, self.pairing, &self.blocks)?;
+        disk_cache::evict(dir, self.max_bytes)
+    }
 }
 
 impl CakeMIDIFile {
     pub fn load_from_file(
         path: impl Into<PathBuf>,
-        player: Arc<WasabiAudioPlayer>,
+        player: Option<Arc<WasabiAudioPlayer>>,
         settings: &MidiSettings,
+        color_fn: Option<NoteColorFn>,
     ) -> Result<Self, WasabiError> {
-        let ticks_per_second = 10000;
+        Self::load_with_progress(path, player, settings, color_fn, &LoadProgress::default())
+    }
 
-        let (file, signature) = open_file_and_signature(path)?;
+    /// Loads the MIDI on a new thread and returns right away. The returned
+    /// handle reports the progress, can cancel the load, and gets polled for
+    /// the result. `on_load_complete` is called as soon as the result is
+    /// ready, for callers that don't want to poll.
+    pub fn load_async(
+        path: impl Into<PathBuf>,
+        player: Option<Arc<WasabiAudioPlayer>>,
+        settings: &MidiSettings,
+        color_fn: Option<NoteColorFn>,
+        on_load_complete: Option<LoadCallback>,
+    ) -> CakeLoadHandle {
+        CakeLoadHandle::spawn(
+            path.into(),
+            player,
+            settings.clone(),
+            color_fn,
+            on_load_complete,
+        )
+    }
+
+    fn load_with_progress(
+        path: impl Into<PathBuf>,
+        player: Option<Arc<WasabiAudioPlayer>>,
+        settings: &MidiSettings,
+        color_fn: Option<NoteColorFn>,
+        progress: &LoadProgress,
+    ) -> Result<Self, WasabiError> {
+        let load_start = Instant::now();
+        let ticks_per_second = settings.cake_ticks_per_second.clamp(
+            *CAKE_TICKS_PER_SECOND_RANGE.start(),
+            *CAKE_TICKS_PER_SECOND_RANGE.end(),
+        );
+
+        let (mut file, signature) = open_file_and_signature(
+            path,
+            settings.ppq_override,
+            settings.channel_filter,
+            settings.transpose,
+        )?;
+        let total_bytes = file.data_length();
+        let read_bytes = file.count_read_bytes();
         let midi = TKMIDIFile::open_from_stream(file, None).map_err(WasabiError::MidiLoadError)?;
 
-        let ppq = midi.ppq();
+        let file_ppq = midi.ppq();
+        let ppq = effective_ppq(file_ppq, settings);
+        let mut tempo_map = TempoMapBuilder::new();
         let merged = pipe!(
             midi.iter_all_track_events_merged_batches()
             |>TimeCaster::<f64>::cast_event_delta()
+        )
+        .inspect(|batch| {
+            if let Ok(batch) = batch {
+                tempo_map.push_batch(batch);
+            }
+        });
+        let merged = pipe!(
+            merged
             |>cancel_tempo_events(250000)
             |>scale_event_time(1.0 / ppq as f64)
             |>unwrap_items()
         );
 
-        let colors = MIDIColor::new_vec_from_settings(midi.track_count(), settings)?;
+        let track_count = midi.track_count();
+        let note_overlap = settings.note_overlap;
+        let drum_lane = settings.drum_lane;
+        let channel_filter = settings.channel_filter;
+        let transpose = settings.transpose;
+        let colors = MIDIColor::new_vec_from_settings(track_count, settings)?;
+        let track_colors = colors.clone();
+        // These colors depend on the key, so they can't come from the table
+        let key_colors = matches!(settings.colors, Colors::TrackPitch | Colors::Spectrum)
+            .then(|| settings.clone());
+
+        let cache_key = color_fn
+            .is_none()
+            .then(|| disk_cache::cache_key(&signature, settings, ticks_per_second, &colors));
+        // Random palettes never match a previous load
+        let use_cache = settings.cake_disk_cache && !settings.randomize_palette;
+        let cached_blocks = cache_key
+            .filter(|_| use_cache)
+            .and_then(|key| Self::try_load_cache(&Self::cache_dir(), key));
+        // The events are still read for the stats and the audio, but the
+        // trees take most of the loading time
+        let build_trees = cached_blocks.is_none();
 
         type Ev = Delta<f64, Track<EventBatch<Event>>>;
         let (key_snd, key_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
-        let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
+
+        let parsed_ticks = Arc::new(AtomicU64::new(0));
+        let thread_parsed_ticks = parsed_ticks.clone();
+        // Stops building the trees once the load has failed or was cancelled
+        let stop_trees = Arc::new(AtomicBool::new(false));
+        let thread_stop_trees = stop_trees.clone();
 
         let key_join_handle = thread::spawn(move || {
-            let mut trees = ThreadedTreeSerializers::new();
+            let start = Instant::now();
+            let mut trees = build_trees.then(|| ThreadedTreeSerializers::new(note_overlap));
 
             let mut time = 0.0;
 
             let mut note_count = 0;
+            let mut key_note_counts = [0u64; 256];
+            let mut track_note_counts = vec![0u64; track_count];
+            let mut velocity_counts = [0u64; 128];
+            let mut first_note = None;
+            let mut instruments = InstrumentsBuilder::new();
+            let mut parse_report = ParseReportBuilder::new(file_ppq);
+
+            // Drum lane notes are colored by instrument instead of by track
+            let note_color = |key: u8, channel_track: i32| {
+                drum_lane
+                    .then(|| MIDIColor::new_for_drum(key))
+                    .flatten()
+                    .unwrap_or_else(|| {
+                        MIDIColor::get_cycled(&colors, channel_track as u32 as usize)
+                    })
+                    .as_u32() as i32
+            };
 
             for batch in key_rcv.into_iter() {
+                if thread_stop_trees.load(Ordering::Relaxed) {
+                    return None;
+                }
                 time += batch.delta;
 
                 let int_time = (time * ticks_per_second as f64) as i32;
+                // All the notes before this batch have been handed to the trees
+                thread_parsed_ticks.store(int_time as u64, Ordering::Relaxed);
 
+                // Wrapping, so that files with absurd track counts don't overflow.
+                // The value is only used to pair note ons with note offs.
                 fn channel_track(channel: u8, track: u32) -> i32 {
-                    (channel as i32) + (track as i32) * 16
+                    (channel as i32).wrapping_add((track as i32).wrapping_mul(16))
                 }
 
                 for event in batch.iter_events() {
+                    if channel_filtered(event.as_event(), &channel_filter) {
+                        continue;
+                    }
                     let track = event.track;
+                    instruments.push_event(event.as_event());
+                    parse_report.push_event(event.as_event());
                     match event.as_event() {
-                        Event::NoteOn(e) => {
+                        Event::NoteOn(e) if e.velocity > 0 => {
+                            let Some(midi_key) = transposed_key(e.key, e.channel, &transpose)
+                            else {
+                                continue;
+                            };
                             let channel_track = channel_track(e.channel, track);
-
-                            trees.push_event(
-                                e.key as usize,
-                                NoteEvent::On {
-                                    time: int_time,
-                                    channel_track,
-                                    color: colors[channel_track as usize].as_u32() as i32,
+                            let key = display_key(midi_key, e.channel, drum_lane);
+                            let color = match color_fn.as_ref() {
+                                Some(color_fn) => {
+                                    let [r, g, b, _] = color_fn(NoteInfo {
+                                        key: midi_key,
+                                        velocity: e.velocity,
+                                        track,
+                                        channel: e.channel,
+                                    });
+                                    MIDIColor::new(r, g, b).as_u32() as i32
+                                }
+                                None => match key_colors.as_ref() {
+                                    Some(settings)
+                                        if !(drum_lane
+                                            && MIDIColor::new_for_drum(key).is_some()) =>
+                                    {
+                                        let color = match settings.colors {
+                                            Colors::Spectrum => {
+                                                MIDIColor::new_spectrum(midi_key, settings)
+                                            }
+                                            _ => MIDIColor::new_track_pitch(
+                                                track as usize,
+                                                midi_key,
+                                                settings,
+                                            ),
+                                        };
+                                        color.as_u32() as i32
+                                    }
+                                    _ => note_color(key, channel_track),
                                 },
-                            );
+                            };
+                            let color = IntVector4::pack_note_color(color, e.velocity);
+
+                            if let Some(trees) = trees.as_mut() {
+                                trees.push_event(
+                                    key as usize,
+                                    NoteEvent::On {
+                                        time: int_time,
+                                        channel_track,
+                                        color,
+                                    },
+                                );
+                            }
                             note_count += 1;
+                            key_note_counts[key as usize] += 1;
+                            track_note_counts[track as usize] += 1;
+                            velocity_counts[e.velocity as usize & 0x7F] += 1;
+                            first_note.get_or_insert(time);
                         }
-                        Event::NoteOff(e) => {
-                            let channel_track = channel_track(e.channel, track);
-
-                            trees.push_event(
-                                e.key as usize,
-                                NoteEvent::Off {
-                                    time: int_time,
-                                    channel_track,
-                                    color: colors[channel_track as usize].as_u32() as i32,
-                                },
-                            );
+                        other => {
+                            let Some((channel, key)) = note_off_key(other) else {
+                                continue;
+                            };
+                            let Some(midi_key) = transposed_key(key, channel, &transpose) else {
+                                continue;
+                            };
+                            let channel_track = channel_track(channel, track);
+                            let key = display_key(midi_key, channel, drum_lane);
+
+                            if let Some(trees) = trees.as_mut() {
+                                trees.push_event(
+                                    key as usize,
+                                    NoteEvent::Off {
+                                        time: int_time,
+                                        channel_track,
+                                        color: note_color(key, channel_track),
+                                    },
+                                );
+                            }
                         }
-                        _ => {}
                     }
                 }
             }
             let final_time = (time * ticks_per_second as f64) as i32;
-            let serialized = trees.seal(final_time);
-
-            let keys: Vec<_> = serialized
+            thread_parsed_ticks.store(final_time as u64, Ordering::Relaxed);
+            if thread_stop_trees.load(Ordering::Relaxed) {
+                return None;
+            }
+            let (keys, pairing) = trees
+                .map(|trees| trees.seal(final_time))
+                .unwrap_or_default();
+            let keys: Vec<_> = keys
                 .into_iter()
                 .map(|s| CakeBlock {
                     start_time: 0,
@@ -129,56 +374,277 @@ impl CakeMIDIFile {
                 })
                 .collect();
 
-            (keys, note_count)
+            let first_key = key_note_counts.iter().position(|&n| n > 0);
+            let last_key = key_note_counts.iter().rposition(|&n| n > 0);
+            let key_range = first_key.zip(last_key).map(|(f, l)| (f as u8, l as u8));
+
+            Some((
+                keys,
+                note_count,
+                key_range,
+                track_note_counts,
+                velocity_counts,
+                first_note,
+                instruments.build(),
+                parse_report,
+                pairing,
+                start.elapsed(),
+            ))
         });
 
-        let audio_join_handle = thread::spawn(|| {
-            let vec: Vec<_> = CompressedAudio::build_blocks(audio_rcv.into_iter()).collect();
-            vec
-        });
+        let (audio_snd, audio_join_handle) = match player {
+            Some(player) => {
+                let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
+                let audio_join_handle = thread::spawn(move || {
+                    let start = Instant::now();
+                    let vec: Vec<_> = CompressedAudio::build_blocks(
+                        audio_rcv.into_iter(),
+                        channel_filter,
+                        transpose,
+                    )
+                    .collect();
+                    (vec, start.elapsed())
+                });
+                (Some(audio_snd), Some((audio_join_handle, player)))
+            }
+            None => (None, None),
+        };
 
         let mut length = 0.0;
+        let max_length = max_cake_length(ticks_per_second);
 
         // Write events to the threads
-        for batch in merged {
+        for (i, batch) in merged.enumerate() {
+            if progress.is_cancelled() {
+                stop_trees.store(true, Ordering::Relaxed);
+                return Err(WasabiError::Cancelled);
+            }
             length += batch.delta;
+            progress.set_parsed_seconds(length);
+            if i % PROGRESS_INTERVAL == 0 {
+                progress.set_read_bytes(read_bytes.load(Ordering::Relaxed), total_bytes);
+            }
+            if length > max_length {
+                // A lower resolution fits more time into the ticks
+                let lower_resolution = if ticks_per_second > *CAKE_TICKS_PER_SECOND_RANGE.start() {
+                    "lower the Cake resolution or "
+                } else {
+                    ""
+                };
+                // Dropping the writers lets the threads finish on their own
+                stop_trees.store(true, Ordering::Relaxed);
+                return Err(WasabiError::MidiFormatError(format!(
+                    "The MIDI is longer than the {:.1} hours supported by the Cake algorithm \
+                    at {ticks_per_second} ticks per second. Please {lower_resolution}use the \
+                    Standard (RAM) or Standard (Live) algorithm instead.",
+                    max_length / 3600.0
+                )));
+            }
             let batch = Arc::new(batch);
-            key_snd.send(batch.clone()).unwrap();
-            audio_snd.send(batch).unwrap();
+            if let Some(audio_snd) = audio_snd.as_ref() {
+                audio_snd.send(batch.clone()).unwrap();
+            }
+            key_snd.send(batch).unwrap();
         }
         // Drop the writers so the threads finish
         drop(key_snd);
         drop(audio_snd);
 
-        let (keys, note_count) = key_join_handle.join().unwrap();
-        let audio = audio_join_handle.join().unwrap();
+        // The trees are still being built and sealed after the last events
+        // were read, which can take a while for large MIDIs
+        while !key_join_handle.is_finished() {
+            if progress.is_cancelled() {
+                stop_trees.store(true, Ordering::Relaxed);
+                return Err(WasabiError::Cancelled);
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let Some((
+            keys,
+            note_count,
+            key_range,
+            track_note_counts,
+            velocity_counts,
+            first_note,
+            instruments,
+            parse_report,
+            pairing,
+            tree_build_time,
+        )) = key_join_handle.join().unwrap()
+        else {
+            return Err(WasabiError::Cancelled);
+        };
+        let (keys, pairing) = cached_blocks.unwrap_or((keys, pairing));
+        let parse_report = parse_report.build(pairing);
+
+        let mut timer = TimeKeeper::new(
+            settings.start_delay,
+            Duration::seconds_f64(settings.global_time_offset),
+        );
 
-        let mut timer = TimeKeeper::new(settings.start_delay);
+        let tempo_map = tempo_map.build(ppq);
+
+        let mut audio_build_ms = None;
+        if let Some((audio_join_handle, player)) = audio_join_handle {
+            let (audio, audio_build_time) = audio_join_handle.join().unwrap();
+            audio_build_ms = Some(audio_build_time.as_secs_f64() * 1000.0);
+            if settings.metronome {
+                MetronomePlayer::new(
+                    tempo_map.beats().to_vec(),
+                    timer.get_listener(),
+                    player.clone(),
+                    settings.metronome_volume,
+                )
+                .spawn_playback();
+            }
+            InRamAudioPlayer::new(audio, timer.get_listener(), player).spawn_playback();
+        }
 
-        InRamAudioPlayer::new(audio, timer.get_listener(), player).spawn_playback();
+        let load_stats = LoadStats {
+            parse_ms: load_start.elapsed().as_secs_f64() * 1000.0,
+            tree_build_ms: tree_build_time.as_secs_f64() * 1000.0,
+            audio_build_ms,
+        };
 
-        Ok(CakeMIDIFile {
-            blocks: keys,
+        let midi = CakeMIDIFile {
+            blocks: keys.into(),
             timer,
             length,
             note_count,
+            key_range,
+            track_note_counts,
+            velocity_counts,
+            first_note,
+            instruments,
+            parse_report,
+            tempo_map,
+            track_colors,
             ticks_per_second,
+            parsed_ticks,
             signature,
+            cache_key,
+            cache_pending: use_cache && build_trees,
+            cache_max_bytes: (settings.cake_cache_max_gb.max(0.0) * 1e9) as u64,
+            ppq: file_ppq,
+            load_stats,
+            tree_stats: OnceLock::new(),
+        };
+
+        Ok(midi)
+    }
+
+    /// Where the trees get cached when
+    /// [`MidiSettings::cake_disk_cache`] is enabled
+    pub fn cache_dir() -> PathBuf {
+        match BaseDirs::new() {
+            Some(base_dirs) => base_dirs.cache_dir().join("wasabi").join("cake"),
+            None => WasabiSettings::get_config_dir().join("cake_cache"),
+        }
+    }
+
+    /// Removes all the cached trees
+    pub fn clear_cache() -> Result<(), WasabiError> {
+        disk_cache::clear(&Self::cache_dir())
+    }
+
+    /// Writes the trees to the cache in `dir` if they were built by this
+    /// load, so the next load of the same file with the same settings can
+    /// read them instead. Then the least recently used caches over
+    /// [`MidiSettings::cake_cache_max_gb`] get removed.
+    pub fn save_cache(&self, dir: &Path) -> Result<(), WasabiError> {
+        match self.cache_writer() {
+            Some(writer) => writer.save(dir),
+            None => Ok(()),
+        }
+    }
+
+    /// Like [`CakeMIDIFile::save_cache`], but the trees can be written on
+    /// another thread while the MIDI plays. Returns `None` if there is
+    /// nothing to cache.
+    pub fn cache_writer(&self) -> Option<CakeCacheWriter> {
+        let key = self.cache_key.filter(|_| self.cache_pending)?;
+        Some(CakeCacheWriter {
+            key,
+            pairing: self.parse_report.pairing(),
+            blocks: self.blocks.clone(),
+            max_bytes: self.cache_max_bytes,
         })
     }
 
+    fn try_load_cache(dir: &Path, key: u64) -> Option<(Vec<CakeBlock>, NotePairing)> {
+        disk_cache::read_blocks(&disk_cache::cache_path(dir, key), key)
+    }
+
     pub fn key_blocks(&self) -> &[CakeBlock] {
         &self.blocks
     }
 
-    pub fn ticks_per_second(&self) -> u32 {
-        self.ticks_per_second
+    /// The deepest tree of all the keys and the total node count. This
+    /// walks every tree the first time it gets called.
+    pub fn tree_stats(&self) -> TreeStats {
+        *self.tree_stats.get_or_init(|| {
+            self.blocks
+                .iter()
+                .map(|block| block.tree_stats())
+                .fold(TreeStats::default(), TreeStats::merge)
+        })
+    }
+
+    /// Converts a time in the ticks of the note trees to seconds
+    pub fn tick_to_seconds(&self, tick: i32) -> f64 {
+        tick as f64 / self.ticks_per_second as f64
+    }
+
+    /// Converts a time in seconds to the ticks of the note trees
+    pub fn seconds_to_tick(&self, seconds: f64) -> i32 {
+        (seconds * self.ticks_per_second as f64) as i32
     }
 
     pub fn current_time(&self) -> Duration {
         self.timer.get_time()
     }
 
+    fn notes_passed_at(&self, time: f64) -> u64 {
+        let time_int = self.seconds_to_tick(time);
+        self.blocks
+            .iter()
+            .map(|b| b.get_notes_passed_at(time_int) as u64)
+            .sum()
+    }
+
+    /// Returns the amount of notes that start in each `bucket_length` seconds
+    /// long section of the MIDI.
+    pub fn density_histogram(&self, bucket_length: f64) -> Vec<u64> {
+        let bucket_count = (self.length / bucket_length).ceil().max(1.0) as usize;
+
+        let mut prev = self.notes_passed_at(0.0);
+        (1..=bucket_count)
+            .map(|i| {
+                let passed = self.notes_passed_at(i as f64 * bucket_length);
+                let count = passed.saturating_sub(prev);
+                prev = passed;
+                count
+            })
+            .collect()
+    }
+
+    /// Returns the start of the one second long section of the MIDI
+    /// with the most notes.
+    pub fn peak_density_time(&self) -> Duration {
+        let bucket_length = 1.0;
+
+        let peak = self
+            .density_histogram(bucket_length)
+            .into_iter()
+            .enumerate()
+            .max_by_key(|(_, count)| *count)
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+
+        Duration::seconds_f64(peak as f64 * bucket_length)
+    }
+
     pub fn cake_signature(&self) -> CakeSignature {
         CakeSignature {
             file_signature: self.signature.clone(),
@@ -203,7 +669,12 @@ impl MIDIFileBase for CakeMIDIFile {
     }
 
     fn parsed_up_to(&self) -> Option<f64> {
-        None
+        let ticks = self.parsed_ticks.load(Ordering::Relaxed);
+        // The last tick is rounded down, but the whole MIDI has been parsed
+        if ticks >= self.seconds_to_tick(self.length) as u64 {
+            return Some(self.length);
+        }
+        Some(self.tick_to_seconds(ticks as i32))
     }
 
     fn timer(&self) -> &TimeKeeper {
@@ -218,23 +689,231 @@ impl MIDIFileBase for CakeMIDIFile {
         true
     }
 
+    /// The time can also be before the current one, and times past the end
+    /// are clamped to the end
+    fn seek(&mut self, time: Duration) {
+        self.timer
+            .seek(time.min(Duration::seconds_f64(self.length)));
+    }
+
+    fn key_range(&self) -> Option<(u8, u8)> {
+        self.key_range
+    }
+
+    fn notes_per_track(&self) -> Option<&[u64]> {
+        Some(&self.track_note_counts)
+    }
+
+    fn first_note_time(&self) -> Option<f64> {
+        self.first_note
+    }
+
+    fn velocity_counts(&self) -> Option<&[u64; 128]> {
+        Some(&self.velocity_counts)
+    }
+
+    fn instruments(&self) -> Option<&[Instrument]> {
+        Some(&self.instruments)
+    }
+
+    fn parse_report(&self) -> Option<&ParseReport> {
+        Some(&self.parse_report)
+    }
+
+    fn tempo_changes(&self) -> Option<&[TempoChange]> {
+        Some(self.tempo_map.changes())
+    }
+
+    fn beats(&self) -> Option<&[Beat]> {
+        Some(self.tempo_map.beats())
+    }
+
+    fn track_colors(&self) -> &[MIDIColor] {
+        &self.track_colors
+    }
+
     fn stats(&self) -> MIDIFileStats {
         let time = self.timer.get_time().as_seconds_f64();
-        let time_int = (time * self.ticks_per_second as f64) as i32;
-
-        let passed_notes = self
-            .key_blocks()
-            .iter()
-            .map(|b| b.get_notes_passed_at(time_int) as u64)
-            .sum();
 
         MIDIFileStats {
             total_notes: Some(self.note_count),
-            passed_notes: Some(passed_notes),
+            passed_notes: Some(self.notes_passed_at(time)),
         }
     }
 
+    fn load_stats(&self) -> Option<LoadStats> {
+        Some(self.load_stats)
+    }
+
+    fn notes_starting_between(&self, start: f64, end: f64) -> Option<u64> {
+        let passed_at_end = self.notes_passed_at(end);
+        Some(passed_at_end.saturating_sub(self.notes_passed_at(start)))
+    }
+
     fn signature(&self) -> &MIDIFileUniqueSignature {
         &self.signature
     }
+
+    fn ppq(&self) -> u16 {
+        self.ppq
+    }
+
+    fn note_at(&self, key: u8, time: f64) -> Option<NoteDetails> {
+        if time < 0.0 {
+            return None;
+        }
+
+        let note = self
+            .blocks
+            .get(key as usize)?
+            .get_note_at(self.seconds_to_tick(time) as u32)?;
+        let start = self.tick_to_seconds(note.start_time as i32);
+        Some(NoteDetails {
+            key,
+            start,
+            length: self.tick_to_seconds(note.end_time as i32) - start,
+            velocity: note.velocity,
+            track: None,
+            channel: None,
+        })
+    }
+
+    /// Walks the trees on the CPU
+    fn visible_notes(&mut self, range: f64) -> Vec<VisibleNote> {
+        let time = self.timer.get_time().as_seconds_f64();
+        let range = range.max(f64::EPSILON);
+        let start = self.seconds_to_tick(time.max(0.0)) as u32;
+        let end = self.seconds_to_tick((time + range).max(0.0)) as u32;
+
+        let view_position = |tick: u32| ((self.tick_to_seconds(tick as i32) - time) / range) as f32;
+
+        let mut notes = Vec::new();
+        for (key, block) in self.blocks.iter().enumerate() {
+            notes.extend(
+                block
+                    .get_notes_between(start, end)
+                    .into_iter()
+                    .map(|note| VisibleNote {
+                        key: key as u8,
+                        start: view_position(note.start_time),
+                        end: view_position(note.end_time),
+                        color: note.color,
+                        velocity: note.velocity,
+                        track: None,
+                        id: NoteId::new(key as u8, (note.tree_index as u64, 0)),
+                    }),
+            );
+        }
+        notes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use crate::{
+        error::WasabiError,
+        midi::{shared::test_midi, MIDIFileBase},
+        settings::MidiSettings,
+    };
+
+    use super::{max_cake_length, CakeMIDIFile, CAKE_TICKS_PER_SECOND_RANGE};
+
+    #[test]
+    fn seek_updates_passed_notes() {
+        let spacing = test_midi::TICKS_PER_SECOND / 2;
+        let events = test_midi::note_sequence(8, spacing, spacing / 2);
+        let path = test_midi::write_midi("cake_seek", &events);
+        let mut midi =
+            CakeMIDIFile::load_from_file(&path, None, &MidiSettings::default(), None).unwrap();
+
+        let mut passed_at = |seconds: f64| {
+            midi.seek(Duration::seconds_f64(seconds));
+            midi.stats().passed_notes.unwrap()
+        };
+
+        // The notes start every half second, from 0 to 3.5
+        let forward: Vec<u64> = [0.25, 1.25, 2.25, 3.25].map(&mut passed_at).to_vec();
+        assert_eq!(forward, [1, 3, 5, 7]);
+        assert!(forward.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(passed_at(0.75), 2);
+        assert_eq!(passed_at(-1.0), 0);
+
+        // Past the end is clamped to the end
+        assert_eq!(passed_at(100.0), 8);
+        let length = midi.midi_length().unwrap();
+        assert_eq!(midi.timer().get_time(), Duration::seconds_f64(length));
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn long_midi_needs_a_lower_resolution() {
+        // A single note that is 100 hours long
+        let hours = 100;
+        let length = hours * 3600 * test_midi::TICKS_PER_SECOND;
+        let events = [(0, [0x90, 60, 100]), (length, [0x80, 60, 0])];
+        let path = test_midi::write_midi("cake_long_delta", &events);
+
+        let default = MidiSettings::default();
+        match CakeMIDIFile::load_from_file(&path, None, &default, None) {
+            Err(WasabiError::MidiFormatError(message)) => {
+                assert!(message.contains("lower the Cake resolution"), "{message}");
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!(
+                "the MIDI loaded at {} ticks/s",
+                default.cake_ticks_per_second
+            ),
+        }
+
+        let settings = MidiSettings {
+            cake_ticks_per_second: 1000,
+            ..Default::default()
+        };
+        let midi = CakeMIDIFile::load_from_file(&path, None, &settings, None).unwrap();
+        let loaded_hours = midi.midi_length().unwrap() / 3600.0;
+        assert!((loaded_hours - hours as f64).abs() < 0.01, "{loaded_hours}");
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn too_long_even_at_the_lowest_resolution() {
+        let lowest = *CAKE_TICKS_PER_SECOND_RANGE.start();
+        assert!((max_cake_length(10000) / 3600.0 - 59.65).abs() < 0.01);
+
+        // Two notes with the longest delta a MIDI can store, about 388 hours
+        // each, so together they are past the ~596 hours at the lowest
+        // resolution
+        let longest_delta = 0x0FFF_FFFF;
+        let events = [
+            (0, [0x90, 60, 100]),
+            (longest_delta, [0x80, 60, 0]),
+            (0, [0x90, 62, 100]),
+            (longest_delta, [0x80, 62, 0]),
+        ];
+        let path = test_midi::write_midi("cake_too_long", &events);
+        assert!(
+            2.0 * longest_delta as f64 / test_midi::TICKS_PER_SECOND as f64
+                > max_cake_length(lowest)
+        );
+
+        let settings = MidiSettings {
+            cake_ticks_per_second: lowest,
+            ..Default::default()
+        };
+        match CakeMIDIFile::load_from_file(&path, None, &settings, None) {
+            Err(WasabiError::MidiFormatError(message)) => {
+                // There is no lower resolution to suggest
+                assert!(!message.contains("lower the Cake resolution"), "{message}");
+                assert!(message.contains("Standard (RAM)"), "{message}");
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("the MIDI loaded at {lowest} ticks/s"),
+        }
+
+        std::fs::remove_file(path).ok();
+    }
 }
@@ -1,5 +1,7 @@
 use std::collections::VecDeque;
 
+use crate::{midi::shared::parse_report::NotePairing, settings::NoteOverlap};
+
 use super::{intvec4::IntVector4, unended_note_batch::UnendedNotes};
 
 enum TreeFrame {
@@ -14,6 +16,10 @@ enum TreeFrame {
     },
 }
 
+/// The address of the empty note, which is written right after the length
+/// marker, see [`TreeSerializer::new`]
+const EMPTY_NOTE_ADDRESS: i32 = -1;
+
 struct NoteMarker {
     start: i32,
     track_channel: i32,
@@ -34,6 +40,9 @@ pub struct TreeSerializer {
 
     added_notes: u32,
     last_tree_time: i32,
+
+    overlap: NoteOverlap,
+    pairing: NotePairing,
 }
 
 impl std::fmt::Debug for TreeSerializer {
@@ -43,8 +52,11 @@ impl std::fmt::Debug for TreeSerializer {
 }
 
 impl TreeSerializer {
-    pub fn new() -> TreeSerializer {
-        let written_values = vec![IntVector4::new_empty()];
+    pub fn new(overlap: NoteOverlap) -> TreeSerializer {
+        // The length marker is only known once the tree is sealed, but its
+        // place is kept from the start. Inserting it at the end would move
+        // the whole tree, and could need a second buffer of the same size.
+        let written_values = vec![IntVector4::new_length_marker(0), IntVector4::new_empty()];
 
         TreeSerializer {
             note_stack: UnendedNotes::new(),
@@ -54,13 +66,30 @@ impl TreeSerializer {
 
             added_notes: 0,
             last_tree_time: 0,
+
+            overlap,
+            pairing: NotePairing::default(),
+        }
+    }
+
+    /// How the notes were paired so far. The notes that are still playing
+    /// count as unended, since sealing ends them.
+    pub fn pairing(&self) -> NotePairing {
+        NotePairing {
+            unended_notes: self.note_stack.len() as u64,
+            ..self.pairing
         }
     }
 
+    /// Returns the address of the note that is visible when multiple notes overlap,
+    /// writing it to the array if it hasn't been written yet.
     fn get_top_note_address(&mut self) -> i32 {
-        let top_marker = self.note_stack.top_mut();
+        let top_marker = match self.overlap {
+            NoteOverlap::Newest | NoteOverlap::Blend => self.note_stack.top_mut(),
+            NoteOverlap::Oldest => self.note_stack.bottom_mut(),
+        };
         match top_marker {
-            None => 0,
+            None => EMPTY_NOTE_ADDRESS,
 
             // Negative are returned because note addresses are negative
             Some(marker) => match marker.written_pos {
@@ -117,6 +146,18 @@ impl TreeSerializer {
         }
 
         self.added_notes += 1;
+        if self.note_stack.has_note_for(&track_channel) {
+            self.pairing.overlapping_notes += 1;
+        }
+
+        // The covered note already has its own shown color, so the colors
+        // of deeper stacks keep blending together
+        let color = match (self.overlap, self.note_stack.top_mut()) {
+            (NoteOverlap::Blend, Some(covered)) => {
+                IntVector4::blend_note_color(color, covered.color)
+            }
+            _ => color,
+        };
 
         self.note_stack.push_note(
             track_channel,
@@ -142,7 +183,7 @@ impl TreeSerializer {
         let marker = if let Some(marker) = marker {
             marker
         } else {
-            //ignore
+            self.pairing.unmatched_note_offs += 1;
             return;
         };
 
@@ -159,18 +200,18 @@ impl TreeSerializer {
         }
     }
 
-    /// Ends all notes, finishes all stack frames, inserts the address of the last item into the start of the array,
+    /// Ends all notes, finishes all stack frames, writes the address of the last item into the start of the array,
     /// and returns the array.
     pub fn complete_and_seal(mut self, time: i32) -> Vec<IntVector4> {
         self.end_all_notes(time);
         self.end_all_frames();
 
-        if self.written_values.len() == 1 {
-            self.write_leaf(0, 0, 0, 0);
+        if self.written_values.len() == 2 {
+            self.write_leaf(EMPTY_NOTE_ADDRESS, EMPTY_NOTE_ADDRESS, 0, 0);
         }
 
-        self.written_values
-            .insert(0, IntVector4::new_length_marker(self.written_values.len()));
+        // The marker points to the root, which is written last
+        self.written_values[0] = IntVector4::new_length_marker(self.written_values.len() - 1);
 
         self.written_values
     }
@@ -185,11 +226,12 @@ impl TreeSerializer {
         match top_frame {
             None => {
                 // If there are no frames, initialize the top stack frame
+                // The notes at time 0 start before the first change
                 self.tree_frames.push_back(TreeFrame::WaitingRight {
                     left_address: address,
                     mid: until,
-                    end: until * 2,
-                    notes_to_the_left: 0,
+                    end: until.saturating_mul(2),
+                    notes_to_the_left: self.added_notes,
                 });
             }
             Some(frame) => match frame {
@@ -202,7 +244,7 @@ impl TreeSerializer {
 
                     if end > until {
                         loop {
-                            let mid = (end + start) / 2;
+                            let mid = start + (end - start) / 2;
                             if mid > until {
                                 // If the frame is too wide, push a left frame and tunnel down
                                 self.tree_frames.push_back(TreeFrame::WaitingLeft { end });
@@ -268,7 +310,7 @@ impl TreeSerializer {
                                     self.tree_frames.push_back(TreeFrame::WaitingRight {
                                         left_address: address,
                                         mid: until,
-                                        end: until * 2,
+                                        end: until.saturating_mul(2),
                                         notes_to_the_left: self.added_notes,
                                     });
                                     break;
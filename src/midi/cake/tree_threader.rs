@@ -1,6 +1,10 @@
 use std::sync::{Arc, Mutex};
 
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+
+use crate::{midi::shared::parse_report::NotePairing, settings::NoteOverlap};
 
 use super::{intvec4::IntVector4, tree_serializer::TreeSerializer};
 
@@ -37,8 +41,10 @@ impl ThreadedTreeSerializers {
         (0..256).map(|_| Vec::new()).collect()
     }
 
-    pub fn new() -> ThreadedTreeSerializers {
-        let trees = (0..256).map(|_| TreeSerializer::new()).collect::<Vec<_>>();
+    pub fn new(overlap: NoteOverlap) -> ThreadedTreeSerializers {
+        let trees = (0..256)
+            .map(|_| TreeSerializer::new(overlap))
+            .collect::<Vec<_>>();
         let trees = Arc::new(Mutex::new(trees));
 
         let (snd_in, rcv_in) = crossbeam_channel::unbounded::<Vec<Vec<NoteEvent>>>();
@@ -105,7 +111,8 @@ impl ThreadedTreeSerializers {
         }
     }
 
-    pub fn seal(self, time: i32) -> Vec<Vec<IntVector4>> {
+    /// Waits for the remaining events to be added and returns the trees
+    fn finish_events(self) -> Vec<TreeSerializer> {
         self.snd.send(self.current_vec).unwrap();
         drop(self.snd);
 
@@ -114,14 +121,87 @@ impl ThreadedTreeSerializers {
 
         self.join.join().unwrap();
 
-        let trees = Arc::try_unwrap(self.trees).unwrap().into_inner().unwrap();
+        Arc::try_unwrap(self.trees).unwrap().into_inner().unwrap()
+    }
+
+    /// Returns the sealed trees, with how the notes of all the keys were
+    /// paired
+    pub fn seal(self, time: i32) -> (Vec<Vec<IntVector4>>, NotePairing) {
+        let trees = self.finish_events();
+        let pairing = trees.iter().fold(NotePairing::default(), |pairing, tree| {
+            pairing.merge(tree.pairing())
+        });
+        (Self::seal_trees(trees, time), pairing)
+    }
+
+    fn seal_trees(trees: Vec<TreeSerializer>, time: i32) -> Vec<Vec<IntVector4>> {
+        // The keys are independent, and collecting keeps them in order.
+        // Sealing only adds the last nodes to each tree, so sealing several
+        // of them at once barely raises the peak memory.
+        trees
+            .into_par_iter()
+            .map(|tree| tree.complete_and_seal(time))
+            .collect()
+    }
+}
 
-        let mut serialized = Vec::new();
-        for tree in trees.into_iter() {
-            let sealed = tree.complete_and_seal(time);
-            serialized.push(sealed);
+#[cfg(test)]
+mod tests {
+    use crate::settings::NoteOverlap;
+
+    use super::{NoteEvent, ThreadedTreeSerializers};
+
+    const NOTES_PER_KEY: i32 = 500;
+
+    /// Two overlapping notes every 10 ticks on each of the 128 keys
+    fn dense_serializers() -> ThreadedTreeSerializers {
+        let mut serializers = ThreadedTreeSerializers::new(NoteOverlap::Newest);
+        for i in 0..NOTES_PER_KEY {
+            let time = i * 10;
+            for key in 0..128 {
+                for (offset, channel_track, on) in
+                    [(0, 0, true), (2, 1, true), (5, 0, false), (8, 1, false)]
+                {
+                    let time = time + offset;
+                    let event = if on {
+                        NoteEvent::On {
+                            time,
+                            channel_track,
+                            color: key as i32,
+                        }
+                    } else {
+                        NoteEvent::Off {
+                            time,
+                            channel_track,
+                            color: key as i32,
+                        }
+                    };
+                    serializers.push_event(key, event);
+                }
+            }
         }
+        serializers
+    }
 
-        serialized
+    #[test]
+    fn parallel_sealing_matches_sequential() {
+        let end = NOTES_PER_KEY * 10 + 10;
+
+        let sequential: Vec<_> = dense_serializers()
+            .finish_events()
+            .into_iter()
+            .map(|tree| tree.complete_and_seal(end))
+            .collect();
+        let parallel =
+            ThreadedTreeSerializers::seal_trees(dense_serializers().finish_events(), end);
+
+        // The nodes don't implement PartialEq, so their bytes get compared
+        let bytes = |trees: &[Vec<super::IntVector4>]| -> Vec<Vec<u8>> {
+            trees
+                .iter()
+                .map(|tree| bytemuck::cast_slice(tree).to_vec())
+                .collect()
+        };
+        assert!(bytes(&sequential) == bytes(&parallel), "the trees differ");
     }
 }
@@ -29,6 +29,15 @@ impl<K: Ord, T> UnendedNotes<K, T> {
         self.notes.get_mut(&key)
     }
 
+    pub fn bottom_mut(&mut self) -> Option<&mut T> {
+        let key = *self.notes.first_entry()?.key();
+        self.notes.get_mut(&key)
+    }
+
+    pub fn has_note_for(&self, key: &K) -> bool {
+        self.ids.get(key).is_some_and(|ids| !ids.is_empty())
+    }
+
     pub fn get_note_for(&mut self, key: K) -> Option<RemovedValue<T>> {
         let ids = self.ids.get_mut(&key)?;
         let id = ids.pop_front()?;
@@ -3,10 +3,11 @@ use std::{
     sync::{Arc, RwLock},
     thread,
 };
+use time::Duration;
 
 use midi_toolkit::{io::MIDIFile as TKMIDIFile, sequence::event::get_channels_array_statistics};
 
-use crate::{audio_playback::WasabiAudioPlayer, gui::window::WasabiError, settings::MidiSettings};
+use crate::{audio_playback::WasabiAudioPlayer, error::WasabiError, settings::MidiSettings};
 
 use self::{
     parse::LiveMidiParser,
@@ -14,8 +15,10 @@ use self::{
 };
 
 use super::{
-    open_file_and_signature, shared::timer::TimeKeeper, MIDIColor, MIDIFile, MIDIFileBase,
-    MIDIFileStats, MIDIFileUniqueSignature, MIDIViewRange,
+    effective_ppq, open_file_and_signature,
+    shared::{instruments::Instrument, parse_report::ParseReport, timer::TimeKeeper},
+    Beat, LoadStats, MIDIColor, MIDIFile, MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature,
+    MIDIViewRange, NoteDetails, TempoChange, VisibleNote,
 };
 
 pub mod block;
@@ -33,22 +36,26 @@ pub struct LiveLoadMIDIFile {
     timer: TimeKeeper,
     stats: Arc<RwLock<Option<ParseStats>>>,
     signature: MIDIFileUniqueSignature,
+    ppq: u16,
 }
 
 impl LiveLoadMIDIFile {
     pub fn load_from_file(
         path: impl Into<PathBuf>,
-        player: Arc<WasabiAudioPlayer>,
+        player: Option<Arc<WasabiAudioPlayer>>,
         settings: &MidiSettings,
     ) -> Result<Self, WasabiError> {
-        let (file, signature) = open_file_and_signature(path)?;
+        // The live loader doesn't filter or transpose channels
+        let (file, signature) =
+            open_file_and_signature(path, settings.ppq_override, [false; 16], [0; 16])?;
 
         let midi = TKMIDIFile::open_from_stream(file, None).map_err(WasabiError::MidiLoadError)?;
 
         let stats_outer = Arc::new(RwLock::new(None));
         let stats = stats_outer.clone();
 
-        let ppq = midi.ppq();
+        let file_ppq = midi.ppq();
+        let ppq = effective_ppq(file_ppq, settings);
         let tracks = midi.iter_all_tracks().collect();
         thread::spawn(move || {
             let stats = get_channels_array_statistics(tracks);
@@ -61,18 +68,28 @@ impl LiveLoadMIDIFile {
             }
         });
 
-        let mut timer = TimeKeeper::new(settings.start_delay);
+        let mut timer = TimeKeeper::new(
+            settings.start_delay,
+            Duration::seconds_f64(settings.global_time_offset),
+        );
 
         let colors = MIDIColor::new_vec_from_settings(midi.track_count(), settings)?;
 
-        let parser = LiveMidiParser::init(&midi, player, &mut timer);
-        let file = LiveNoteViewData::new(parser, colors);
+        let parser = LiveMidiParser::init(&midi, ppq, player, settings.drum_lane, &mut timer);
+        let file = LiveNoteViewData::new(
+            parser,
+            colors,
+            settings.note_overlap,
+            settings.drum_lane,
+            MIDIColor::new_key_vec(settings),
+        );
 
         Ok(LiveLoadMIDIFile {
             view_data: file,
             timer,
             stats,
             signature,
+            ppq: file_ppq,
         })
     }
 }
@@ -99,6 +116,42 @@ impl MIDIFileBase for LiveLoadMIDIFile {
         false
     }
 
+    fn key_range(&self) -> Option<(u8, u8)> {
+        None
+    }
+
+    fn notes_per_track(&self) -> Option<&[u64]> {
+        None
+    }
+
+    fn first_note_time(&self) -> Option<f64> {
+        None
+    }
+
+    fn velocity_counts(&self) -> Option<&[u64; 128]> {
+        None
+    }
+
+    fn instruments(&self) -> Option<&[Instrument]> {
+        None
+    }
+
+    fn parse_report(&self) -> Option<&ParseReport> {
+        None
+    }
+
+    fn tempo_changes(&self) -> Option<&[TempoChange]> {
+        None
+    }
+
+    fn beats(&self) -> Option<&[Beat]> {
+        None
+    }
+
+    fn track_colors(&self) -> &[MIDIColor] {
+        self.view_data.track_colors()
+    }
+
     fn stats(&self) -> MIDIFileStats {
         let stats = self.stats.read().unwrap();
 
@@ -108,9 +161,30 @@ impl MIDIFileBase for LiveLoadMIDIFile {
         }
     }
 
+    fn load_stats(&self) -> Option<LoadStats> {
+        // Live MIDIs keep parsing while playing
+        None
+    }
+
+    fn notes_starting_between(&self, start: f64, end: f64) -> Option<u64> {
+        Some(self.view_data.notes_starting_between(start, end))
+    }
+
     fn signature(&self) -> &MIDIFileUniqueSignature {
         &self.signature
     }
+
+    fn ppq(&self) -> u16 {
+        self.ppq
+    }
+
+    fn note_at(&self, key: u8, time: f64) -> Option<NoteDetails> {
+        self.view_data.note_at(key, time)
+    }
+
+    fn visible_notes(&mut self, range: f64) -> Vec<VisibleNote> {
+        super::visible_notes_in(&self.get_current_column_views(range))
+    }
 }
 
 impl MIDIFile for LiveLoadMIDIFile {
@@ -39,17 +39,18 @@ pub struct ThreadManager {
 pub struct LiveMidiParser {
     file_manager: ThreadManager,
     note_manager: ThreadManager,
-    audio_manager: ThreadManager,
+    audio_manager: Option<ThreadManager>,
     note_reciever: Receiver<LiveNoteBlockWithKey>,
 }
 
 impl LiveMidiParser {
     pub fn init(
         midi: &TKMIDIFile<DiskReader>,
-        player: Arc<WasabiAudioPlayer>,
+        ppq: u16,
+        player: Option<Arc<WasabiAudioPlayer>>,
+        drum_lane: bool,
         timer: &mut TimeKeeper,
     ) -> Self {
-        let ppq = midi.ppq();
         let merged = pipe!(
             midi.iter_all_track_events_merged_batches()
             |>TimeCaster::<f64>::cast_event_delta()
@@ -59,12 +60,20 @@ impl LiveMidiParser {
         );
 
         let (note_snd, note_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
-        let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
+        let notes = notes::init_note_manager(note_rcv, drum_lane);
 
-        let notes = notes::init_note_manager(note_rcv);
-        let audio = audio::init_audio_manager(audio_rcv);
+        let (audio_snd, audio_manager) = match player {
+            Some(player) => {
+                let (audio_snd, audio_rcv) =
+                    crossbeam_channel::bounded::<Arc<TrackEventBatch>>(1000);
+                let audio = audio::init_audio_manager(audio_rcv);
 
-        LiveAudioPlayer::new(audio.reciever, timer.get_listener(), player).spawn_playback();
+                LiveAudioPlayer::new(audio.reciever, timer.get_listener(), player).spawn_playback();
+
+                (Some(audio_snd), Some(audio.manager))
+            }
+            None => (None, None),
+        };
 
         let mut parser_timer = timer.get_listener();
 
@@ -86,12 +95,14 @@ impl LiveMidiParser {
 
                 let block = Arc::new(block);
 
-                let res = note_snd.send(block.clone());
-                if res.is_err() {
-                    break;
+                if let Some(audio_snd) = audio_snd.as_ref() {
+                    let res = audio_snd.send(block.clone());
+                    if res.is_err() {
+                        break;
+                    }
                 }
 
-                let res = audio_snd.send(block);
+                let res = note_snd.send(block);
                 if res.is_err() {
                     break;
                 }
@@ -104,7 +115,7 @@ impl LiveMidiParser {
                 parse_time: parse_time_outer,
             },
             note_manager: notes.manager,
-            audio_manager: audio.manager,
+            audio_manager,
             note_reciever: notes.reciever,
         }
     }
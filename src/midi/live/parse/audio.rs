@@ -18,7 +18,7 @@ pub fn init_audio_manager(blocks: Receiver<Arc<TrackEventBatch>>) -> AudioParser
 
     let parse_time = parse_time_outer.clone();
     let join_handle = std::thread::spawn(move || {
-        for block in CompressedAudio::build_blocks(blocks.into_iter()) {
+        for block in CompressedAudio::build_blocks(blocks.into_iter(), [false; 16]) {
             parse_time.store(block.time, Ordering::Relaxed);
             let res = sender.send(block);
             if res.is_err() {
@@ -8,7 +8,9 @@ use crossbeam_channel::{Receiver, Sender};
 use midi_toolkit::events::{Event, MIDIEventEnum};
 
 use crate::midi::{
+    display_key,
     live::block::{LiveNoteEnderHandle, LiveRefNoteBlock},
+    note_off_key,
     shared::track_channel::TrackAndChannel,
 };
 
@@ -88,7 +90,7 @@ impl UnendedNotesHandler {
 
 struct ParserState {
     unended_notes: UnendedNotesHandler,
-    keys: Box<[Vec<TrackAndChannel>]>,
+    keys: Box<[Vec<(TrackAndChannel, u8)>]>,
     sender: Sender<LiveNoteBlockWithKey>,
 }
 
@@ -105,8 +107,8 @@ impl ParserState {
         }
     }
 
-    fn add_note(&mut self, key: u8, track_chan: TrackAndChannel) {
-        self.keys[key as usize].push(track_chan);
+    fn add_note(&mut self, key: u8, track_chan: TrackAndChannel, velocity: u8) {
+        self.keys[key as usize].push((track_chan, velocity));
     }
 
     fn flush(&mut self, time: f64) -> Result<(), ()> {
@@ -145,7 +147,10 @@ pub struct NoteParserResult {
     pub manager: ThreadManager,
 }
 
-pub fn init_note_manager(blocks: Receiver<Arc<TrackEventBatch>>) -> NoteParserResult {
+pub fn init_note_manager(
+    blocks: Receiver<Arc<TrackEventBatch>>,
+    drum_lane: bool,
+) -> NoteParserResult {
     let (sender, reciever) = crossbeam_channel::unbounded();
     let parse_time_outer = Arc::new(AtomicF64::default());
 
@@ -165,13 +170,20 @@ pub fn init_note_manager(blocks: Receiver<Arc<TrackEventBatch>>) -> NoteParserRe
 
             for event in block.iter_events() {
                 match event.as_event() {
-                    Event::NoteOn(e) => {
-                        state.add_note(e.key, TrackAndChannel::new(event.track, e.channel));
+                    Event::NoteOn(e) if e.velocity > 0 => {
+                        let key = display_key(e.key, e.channel, drum_lane);
+                        state.add_note(
+                            key,
+                            TrackAndChannel::new(event.track, e.channel),
+                            e.velocity,
+                        );
                     }
-                    Event::NoteOff(e) => {
-                        state.end_note(e.key, TrackAndChannel::new(event.track, e.channel), time);
+                    other => {
+                        if let Some((channel, key)) = note_off_key(other) {
+                            let key = display_key(key, channel, drum_lane);
+                            state.end_note(key, TrackAndChannel::new(event.track, channel), time);
+                        }
                     }
-                    _ => {}
                 }
             }
 
@@ -3,15 +3,25 @@
 use gen_iter::GenIter;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
-use crate::midi::{DisplacedMIDINote, MIDIColor, MIDINoteColumnView, MIDINoteViews, MIDIViewRange};
+use crate::{
+    midi::{
+        blend_overlap_colors, note_playing, note_started, overlap_order, DisplacedMIDINote,
+        MIDIColor, MIDINoteColumnView, MIDINoteViews, MIDIViewRange, NoteDetails,
+    },
+    settings::NoteOverlap,
+};
 
-use super::{column::LiveNoteColumn, parse::LiveMidiParser};
+use super::{block::LiveMIDINote, column::LiveNoteColumn, parse::LiveMidiParser};
 
 pub struct LiveNoteViewData {
     parser: LiveMidiParser,
     columns: Vec<LiveNoteColumn>,
     default_track_colors: Vec<MIDIColor>,
     view_range: MIDIViewRange,
+    overlap: NoteOverlap,
+    drum_lane: bool,
+    /// The color of every key, for the colors that don't depend on the track
+    key_colors: Option<Vec<MIDIColor>>,
 }
 
 pub struct LiveCurrentNoteViews<'a> {
@@ -25,7 +35,13 @@ impl<'a> LiveCurrentNoteViews<'a> {
 }
 
 impl LiveNoteViewData {
-    pub fn new(parser: LiveMidiParser, colors: Vec<MIDIColor>) -> Self {
+    pub fn new(
+        parser: LiveMidiParser,
+        colors: Vec<MIDIColor>,
+        overlap: NoteOverlap,
+        drum_lane: bool,
+        key_colors: Option<Vec<MIDIColor>>,
+    ) -> Self {
         let mut columns = Vec::with_capacity(256);
         columns.resize_with(256, LiveNoteColumn::new);
         LiveNoteViewData {
@@ -36,6 +52,9 @@ impl LiveNoteViewData {
                 end: f64::NEG_INFINITY,
             },
             default_track_colors: colors,
+            overlap,
+            drum_lane,
+            key_colors,
         }
     }
 
@@ -71,7 +90,10 @@ impl LiveNoteViewData {
             }
 
             while data.blocks_passed_keyboard_index < blocks.len() {
-                if blocks[data.blocks_passed_keyboard_index].start > new_view_range.start {
+                if !note_started(
+                    blocks[data.blocks_passed_keyboard_index].start,
+                    new_view_range.start,
+                ) {
                     break;
                 }
                 data.notes_passed_keyboard +=
@@ -100,18 +122,65 @@ impl LiveNoteViewData {
         self.parser.parse_time()
     }
 
+    pub fn track_colors(&self) -> &[MIDIColor] {
+        &self.default_track_colors
+    }
+
     pub fn passed_notes(&self) -> u64 {
         self.columns
             .iter()
             .map(|column| column.data.notes_passed_keyboard)
             .sum()
     }
+
+    /// Only counts the notes that were already parsed
+    pub fn note_at(&self, key: u8, time: f64) -> Option<NoteDetails> {
+        let column = self.columns.get(key as usize)?;
+        let end = column.blocks.partition_point(|block| block.start <= time);
+
+        for block_index in overlap_order(0..end, self.overlap) {
+            let block = &column.blocks[block_index];
+            for note_index in overlap_order(0..block.notes.len(), self.overlap) {
+                let note = &block.notes[note_index];
+                if note_playing(block.start, block.start + note.len as f64, time) {
+                    return Some(NoteDetails {
+                        key,
+                        start: block.start,
+                        length: note.len as f64,
+                        velocity: note.velocity,
+                        track: Some(note.track_chan.track()),
+                        channel: Some(note.track_chan.channel()),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn notes_starting_between(&self, start: f64, end: f64) -> u64 {
+        self.columns
+            .iter()
+            .map(|column| {
+                let first = column.blocks.partition_point(|block| block.start < start);
+                column
+                    .blocks
+                    .range(first..)
+                    .take_while(|block| block.start < end)
+                    .map(|block| block.notes.len() as u64)
+                    .sum::<u64>()
+            })
+            .sum()
+    }
 }
 
 pub struct LiveNoteColumnView<'a> {
     view: &'a LiveNoteViewData,
     column: &'a LiveNoteColumn,
     view_range: MIDIViewRange,
+    /// Drum lane notes are colored by instrument instead of by track, and
+    /// the notes of the key colors by their key
+    key_color: Option<MIDIColor>,
 }
 
 impl<'a> MIDINoteViews for LiveCurrentNoteViews<'a> {
@@ -122,6 +191,12 @@ impl<'a> MIDINoteViews for LiveCurrentNoteViews<'a> {
             view: self.data,
             column: &self.data.columns[key],
             view_range: self.data.view_range,
+            key_color: self
+                .data
+                .drum_lane
+                .then(|| MIDIColor::new_for_drum(key as u8))
+                .flatten()
+                .or_else(|| self.data.key_colors.as_ref().map(|colors| colors[key])),
         }
     }
 
@@ -140,19 +215,45 @@ impl<'a> MIDINoteColumnView for LiveNoteColumnView<'a> {
 
     fn iterate_displaced_notes(&self) -> Self::Iter<'_> {
         let colors = &self.view.default_track_colors;
+        let overlap = self.view.overlap;
+
+        let note_color = move |note: &LiveMIDINote| {
+            self.key_color
+                .unwrap_or_else(|| MIDIColor::get_cycled(colors, note.track_chan.as_usize()))
+        };
 
         let iter = GenIter(
             #[coroutine]
             move || {
-                for block_index in (0..self.column.data.end_block).rev() {
+                let end_block = self.column.data.end_block;
+                // Yielded from the end, as the drawing order is the reverse
+                let mut blended = (overlap == NoteOverlap::Blend).then(|| {
+                    blend_overlap_colors(self.column.blocks.range(0..end_block).flat_map(|block| {
+                        block
+                            .notes
+                            .iter()
+                            .map(|note| (block.start, note.len, note_color(note)))
+                    }))
+                });
+
+                for block_index in overlap_order(0..end_block, overlap) {
                     let block = &self.column.blocks[block_index];
                     let start = (block.start - self.view_range.start) as f32;
 
-                    for note in block.notes.iter().rev() {
+                    for note_index in overlap_order(0..block.notes.len(), overlap) {
+                        let note = &block.notes[note_index];
                         yield DisplacedMIDINote {
                             start,
                             len: note.len,
-                            color: colors[note.track_chan.as_usize()],
+                            color: blended
+                                .as_mut()
+                                .and_then(|colors| colors.pop())
+                                .unwrap_or_else(|| note_color(note)),
+                            // Old blocks get removed while playing, so the
+                            // start time is used instead of the block index
+                            position: (block.start.to_bits(), note_index as u64),
+                            velocity: note.velocity,
+                            track: note.track_chan.track(),
                         };
                     }
                 }
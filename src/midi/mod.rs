@@ -8,24 +8,46 @@ mod ram;
 mod audio;
 
 mod shared;
-use std::{fs::File, path::PathBuf, time::UNIX_EPOCH};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io::Read,
+    ops::{Range, RangeInclusive},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::UNIX_EPOCH,
+};
 
 use enum_dispatch::enum_dispatch;
 use image::{DynamicImage, GenericImageView, ImageReader};
+use midi_toolkit::events::Event;
 use palette::{convert::FromColorUnclamped, Hsv, Srgb};
 use rand::seq::IteratorRandom;
 use rand::Rng;
+use rustc_hash::FxHasher;
+use time::Duration;
 
-pub use cake::{blocks::CakeBlock, intvec4::IntVector4, CakeMIDIFile, CakeSignature};
+pub use audio::offline::{load_soundfonts, read_audio_blocks, OfflineRenderer};
+pub use cake::{
+    blocks::CakeBlock,
+    intvec4::IntVector4,
+    load_handle::{CakeLoadHandle, LoadCallback},
+    CakeCacheWriter, CakeMIDIFile, CakeSignature, CAKE_TICKS_PER_SECOND_RANGE,
+};
 pub use live::LiveLoadMIDIFile;
 pub use ram::InRamMIDIFile;
+pub use shared::debug_events::{read_debug_events, DebugEvent};
+pub use shared::instruments::Instrument;
+pub use shared::parse_report::ParseReport;
+pub use shared::tempo_map::{export_tempo_changes, tempo_at, Beat, TempoChange, DEFAULT_TEMPO};
+pub use shared::timer::TimeKeeper;
 
 use crate::{
-    gui::window::WasabiError,
-    settings::{Colors, MidiSettings},
+    error::WasabiError,
+    settings::{Colors, MidiParsing, MidiSettings, NoteOverlap},
 };
 
-use self::shared::timer::TimeKeeper;
+use self::shared::stream::MIDIStream;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct MIDIFileStats {
@@ -33,6 +55,56 @@ pub struct MIDIFileStats {
     pub passed_notes: Option<u64>,
 }
 
+impl MIDIFileStats {
+    /// The number of notes that haven't been passed yet, if the total is known
+    pub fn remaining_notes(&self) -> Option<u64> {
+        Some(self.total_notes?.saturating_sub(self.passed_notes?))
+    }
+}
+
+/// How long loading a MIDI took, in milliseconds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadStats {
+    /// The whole load, from opening the file until it is ready to play
+    pub parse_ms: f64,
+    /// Building the notes of the keys, which runs in its own thread
+    pub tree_build_ms: f64,
+    /// Building the audio events, which runs in its own thread, if audio is enabled
+    pub audio_build_ms: Option<f64>,
+}
+
+/// The note that a [`NoteColorFn`] gets called with
+#[derive(Debug, Clone, Copy)]
+pub struct NoteInfo {
+    pub key: u8,
+    pub velocity: u8,
+    pub track: u32,
+    pub channel: u8,
+}
+
+/// A note of a loaded MIDI, see [`MIDIFileBase::note_at`]
+#[derive(Debug, Clone, Copy)]
+pub struct NoteDetails {
+    /// The key the note is displayed on
+    pub key: u8,
+    /// The start time in seconds
+    pub start: f64,
+    /// The length in seconds
+    pub length: f64,
+    pub velocity: u8,
+    /// The track, unless the loader doesn't keep it (Cake)
+    pub track: Option<u32>,
+    /// The channel, unless the loader doesn't keep it (Cake)
+    pub channel: Option<u8>,
+}
+
+/// Computes the RGBA color of every note while a MIDI gets loaded, instead of
+/// using the palette. The alpha is ignored, the note alpha setting is used instead.
+/// Only the Cake loader takes one, see [`CakeMIDIFile::load_from_file`], and the
+/// colors are stored in the trees, so the MIDI has to be reloaded for a new
+/// function to apply.
+pub type NoteColorFn = Arc<dyn Fn(NoteInfo) -> [u8; 4] + Send + Sync>;
+
 /// A struct that represents the view range of a midi screen render
 #[derive(Debug, Clone, Copy, Default)]
 pub struct MIDIViewRange {
@@ -50,38 +122,336 @@ impl MIDIViewRange {
     }
 }
 
+/// The path used to load a MIDI from the standard input instead of a file
+pub const STDIN_PATH: &str = "-";
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MIDIFileUniqueSignature {
     pub filepath: PathBuf,
     pub length_in_bytes: u64,
     pub last_modified: u128,
+    /// A hash of the contents, only used for MIDIs without a file
+    pub content_hash: Option<u64>,
+    pub ppq_override: Option<u16>,
+    /// The channels that were skipped, see [`MidiSettings::channel_filter`]
+    pub channel_filter: [bool; 16],
+    /// The semitones each channel was shifted by, see [`MidiSettings::transpose`]
+    pub transpose: [i8; 16],
 }
 
-fn open_file_and_signature(
-    path: impl Into<PathBuf>,
-) -> Result<(File, MIDIFileUniqueSignature), WasabiError> {
-    let path = path.into();
-    let file = std::fs::File::open(&path).map_err(WasabiError::FilesystemError)?;
-    let file_length = file.metadata().map_err(WasabiError::FilesystemError)?.len();
-    let file_last_modified = file
-        .metadata()
-        .map_err(WasabiError::FilesystemError)?
+impl MIDIFileUniqueSignature {
+    /// Whether `path` still points to the same, unchanged file. The loading
+    /// settings stored in the signature (like the PPQ override) are ignored.
+    pub fn is_same_file(&self, path: &Path) -> bool {
+        if self.filepath != path {
+            return false;
+        }
+        // Stdin is only read once, so its data never changes
+        if is_stdin_path(path) {
+            return true;
+        }
+
+        std::fs::metadata(path)
+            .ok()
+            .and_then(|metadata| length_and_last_modified(&metadata).ok())
+            .is_some_and(|(length, last_modified)| {
+                length == self.length_in_bytes && last_modified == self.last_modified
+            })
+    }
+
+    /// A key for the file of the signature that stays the same across runs.
+    /// Like [`MIDIFileUniqueSignature::is_same_file`], the loading settings
+    /// are ignored.
+    pub fn file_key(&self) -> u64 {
+        let mut hasher = FxHasher::default();
+        (
+            &self.filepath,
+            self.length_in_bytes,
+            self.last_modified,
+            self.content_hash,
+        )
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+pub fn is_stdin_path(path: &Path) -> bool {
+    path == Path::new(STDIN_PATH)
+}
+
+/// About how many bytes a note takes in a MIDI file: a note on and a note
+/// off with running status and short deltas
+const BYTES_PER_NOTE: u64 = 8;
+
+/// A rough estimate of the note count of a MIDI from its file size, for
+/// picking an algorithm before the file gets parsed
+pub fn estimate_note_count(path: &Path) -> Option<u64> {
+    if is_stdin_path(path) {
+        return None;
+    }
+
+    std::fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.len() / BYTES_PER_NOTE)
+}
+
+fn length_and_last_modified(metadata: &std::fs::Metadata) -> Result<(u64, u128), WasabiError> {
+    let last_modified = metadata
         .modified()
         .map_err(WasabiError::FilesystemError)?
         .duration_since(UNIX_EPOCH)
         .map_err(|e: std::time::SystemTimeError| WasabiError::Other(e.to_string()))?
         .as_micros();
 
+    Ok((metadata.len(), last_modified))
+}
+
+fn open_file_and_signature(
+    path: impl Into<PathBuf>,
+    ppq_override: Option<u16>,
+    channel_filter: [bool; 16],
+    transpose: [i8; 16],
+) -> Result<(MIDIStream, MIDIFileUniqueSignature), WasabiError> {
+    let path = path.into();
+    if is_stdin_path(&path) {
+        return open_stdin_and_signature(path, ppq_override, channel_filter, transpose);
+    }
+
+    let file = std::fs::File::open(&path).map_err(WasabiError::FilesystemError)?;
+    let (file_length, file_last_modified) =
+        length_and_last_modified(&file.metadata().map_err(WasabiError::FilesystemError)?)?;
+
     let signature = MIDIFileUniqueSignature {
         filepath: path,
         length_in_bytes: file_length,
         last_modified: file_last_modified,
+        content_hash: None,
+        ppq_override,
+        channel_filter,
+        transpose,
     };
 
-    Ok((file, signature))
+    Ok((MIDIStream::new(file)?, signature))
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+static STDIN_DATA: OnceLock<Arc<[u8]>> = OnceLock::new();
+
+fn open_stdin_and_signature(
+    path: PathBuf,
+    ppq_override: Option<u16>,
+    channel_filter: [bool; 16],
+    transpose: [i8; 16],
+) -> Result<(MIDIStream, MIDIFileUniqueSignature), WasabiError> {
+    // Stdin can only be read once, so the data is kept around in case
+    // the MIDI gets reloaded
+    let data = match STDIN_DATA.get() {
+        Some(data) => data.clone(),
+        None => {
+            let mut data = Vec::new();
+            std::io::stdin()
+                .lock()
+                .read_to_end(&mut data)
+                .map_err(WasabiError::FilesystemError)?;
+            STDIN_DATA.get_or_init(|| data.into()).clone()
+        }
+    };
+
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+
+    let signature = MIDIFileUniqueSignature {
+        filepath: path,
+        length_in_bytes: data.len() as u64,
+        last_modified: 0,
+        content_hash: Some(hasher.finish()),
+        ppq_override,
+        channel_filter,
+        transpose,
+    };
+
+    Ok((MIDIStream::from_memory(data)?, signature))
+}
+
+/// The PPQ used to scale the event times, which is either the one of the
+/// file or the one set in the settings
+fn effective_ppq(file_ppq: u16, settings: &MidiSettings) -> u16 {
+    settings
+        .ppq_override
+        .filter(|ppq| *ppq > 0)
+        .unwrap_or(file_ppq)
+}
+
+/// Tracks past this limit reuse the colors of the first tracks, so files that
+/// claim an absurd amount of tracks don't allocate a huge color table.
+pub const MAX_COLORED_TRACKS: usize = 65536;
+
+/// The most tracks that [`MIDIColor::export_swatches`] exports
+pub const MAX_EXPORTED_TRACKS: usize = 4096;
+
+/// A 3x5 pixel font for the digits of the exported swatch labels
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+const DIGIT_SCALE: u32 = 2;
+const DIGIT_HEIGHT: u32 = 5 * DIGIT_SCALE;
+const DIGIT_ADVANCE: u32 = 4 * DIGIT_SCALE;
+
+fn digits_width(number: u32) -> u32 {
+    number.to_string().len() as u32 * DIGIT_ADVANCE - DIGIT_SCALE
+}
+
+fn draw_number(image: &mut image::RgbaImage, x: u32, y: u32, number: u32, color: [u8; 4]) {
+    for (i, digit) in number.to_string().bytes().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        let left = x + i as u32 * DIGIT_ADVANCE;
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..DIGIT_SCALE {
+                    for dx in 0..DIGIT_SCALE {
+                        let px = left + col * DIGIT_SCALE + dx;
+                        let py = y + row as u32 * DIGIT_SCALE + dy;
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, image::Rgba(color));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The GM percussion channel (channel 10)
+pub const DRUM_CHANNEL: u8 = 9;
+
+/// The lowest GM percussion key, which is the first key of the drum lane
+const FIRST_GM_DRUM_KEY: u8 = 27;
+
+/// The keys of the drum lane, right above the regular MIDI keys
+pub const DRUM_LANE_KEYS: RangeInclusive<u8> = 128..=188;
+
+/// Returns the key a note gets displayed on. With `drum_lane`, the notes
+/// of the percussion channel get moved onto the drum lane, where each key
+/// is one GM instrument.
+pub fn display_key(key: u8, channel: u8, drum_lane: bool) -> u8 {
+    if drum_lane && channel == DRUM_CHANNEL {
+        let offset = key.saturating_sub(FIRST_GM_DRUM_KEY);
+        (DRUM_LANE_KEYS.start() + offset).min(*DRUM_LANE_KEYS.end())
+    } else {
+        key
+    }
+}
+
+/// Returns the key a note gets played on after the transpose of its
+/// channel, see [`MidiSettings::transpose`]. Notes that get transposed out
+/// of the MIDI keys are dropped.
+pub fn transposed_key(key: u8, channel: u8, transpose: &[i8; 16]) -> Option<u8> {
+    let key = key as i16 + transpose[channel as usize & 0xF] as i16;
+    (0..=127).contains(&key).then_some(key as u8)
+}
+
+/// Whether an event belongs to one of the channels that get skipped when
+/// loading. Events without a channel are never skipped.
+pub fn channel_filtered(event: &Event, channel_filter: &[bool; 16]) -> bool {
+    let channel = match event {
+        Event::NoteOn(e) => e.channel,
+        Event::NoteOff(e) => e.channel,
+        Event::PolyphonicKeyPressure(e) => e.channel,
+        Event::ControlChange(e) => e.channel,
+        Event::ProgramChange(e) => e.channel,
+        Event::ChannelPressure(e) => e.channel,
+        Event::PitchWheelChange(e) => e.channel,
+        _ => return false,
+    };
+    channel_filter[channel as usize & 0xF]
+}
+
+/// The channel and key of a note off. Note ons with a velocity of 0 are
+/// note offs as well, some files use them instead to save space.
+pub fn note_off_key(event: &Event) -> Option<(u8, u8)> {
+    match event {
+        Event::NoteOn(e) if e.velocity == 0 => Some((e.channel, e.key)),
+        Event::NoteOff(e) => Some((e.channel, e.key)),
+        _ => None,
+    }
+}
+
+const KEY_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// The name of a displayed key, with middle C (60) as C4. The keys of the
+/// drum lane are named by their GM drum key instead.
+pub fn key_name(key: u8) -> String {
+    if DRUM_LANE_KEYS.contains(&key) {
+        format!("Drum {}", key - DRUM_LANE_KEYS.start() + FIRST_GM_DRUM_KEY)
+    } else {
+        format!("{}{}", KEY_NAMES[key as usize % 12], key as i32 / 12 - 1)
+    }
+}
+
+/// Whether a note that starts at `start` has reached the keyboard at `time`.
+///
+/// The boundary is inclusive: a note counts as passed in the stats and lights
+/// up its key from the exact moment its start time is reached, which is also
+/// when its audio event gets sent (see [`shared::timer::TimeListener::wait_until`]).
+pub fn note_started(start: f64, time: f64) -> bool {
+    start <= time
+}
+
+/// Whether a note is playing at `time`. A note stops playing at the exact
+/// moment it ends, so a note that starts right when another one ends
+/// replaces it.
+pub fn note_playing(start: f64, end: f64, time: f64) -> bool {
+    note_started(start, time) && end > time
+}
+
+/// Orders the indices of a range of notes (sorted by start time) so that
+/// the note that should be displayed on top comes first.
+fn overlap_order(range: Range<usize>, overlap: NoteOverlap) -> impl Iterator<Item = usize> + Send {
+    let Range { start, end } = range;
+    (start..end).map(move |i| match overlap {
+        NoteOverlap::Newest | NoteOverlap::Blend => start + end - 1 - i,
+        NoteOverlap::Oldest => i,
+    })
+}
+
+/// The colors of [`NoteOverlap::Blend`] for notes sorted by start time, as
+/// (start, length, color): a note that starts while another one is playing
+/// on the key is shown halfway between its color and the shown color of
+/// the covered note. The colors are in the same order as the notes, which
+/// is the reverse of the drawing order.
+fn blend_overlap_colors(notes: impl Iterator<Item = (f64, f32, MIDIColor)>) -> Vec<MIDIColor> {
+    let mut colors = Vec::new();
+    // The end and shown color of the notes that are still playing, with the
+    // one on top last
+    let mut playing: Vec<(f64, MIDIColor)> = Vec::new();
+
+    for (start, len, color) in notes {
+        playing.retain(|(end, _)| *end > start);
+        let shown = match playing.last() {
+            Some((_, covered)) => color.mix(*covered, 0.5),
+            None => color,
+        };
+        colors.push(shown);
+        playing.push((start + len as f64, shown));
+    }
+
+    colors
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct MIDIColor(u32);
 
 impl MIDIColor {
@@ -100,6 +470,14 @@ impl MIDIColor {
         )
     }
 
+    /// The color of a drum lane key, which is different for each instrument
+    pub fn new_for_drum(key: u8) -> Option<Self> {
+        DRUM_LANE_KEYS.contains(&key).then(|| {
+            let instrument = key - DRUM_LANE_KEYS.start();
+            Self::new_from_hue(instrument as f64 * 137.5 % 360.0)
+        })
+    }
+
     pub fn new_vec(tracks: usize) -> Vec<Self> {
         let count = tracks * 16;
 
@@ -114,6 +492,41 @@ impl MIDIColor {
         vec
     }
 
+    /// The color of a note with [`Colors::TrackPitch`]: the hue is picked
+    /// by the track and the brightness by the key
+    pub fn new_track_pitch(track: usize, key: u8, settings: &MidiSettings) -> Self {
+        let [low, high] = settings.pitch_shade_range;
+        let value = low + (high - low) * (key.min(127) as f32 / 127.0);
+        let hue = (track as f64 * settings.track_hue_spread).rem_euclid(360.0);
+
+        let hsv: Hsv<Srgb, f64> = palette::Hsv::new(hue, 1.0, value.clamp(0.0, 1.0) as f64);
+        let rgb = palette::rgb::Rgb::from_color_unclamped(hsv);
+        Self::new(
+            (rgb.red * 255.0) as u8,
+            (rgb.green * 255.0) as u8,
+            (rgb.blue * 255.0) as u8,
+        )
+    }
+
+    /// Picks the hue of a key on the full MIDI range, see
+    /// [`MidiSettings::spectrum_hues`]. Unlike the other colors, this
+    /// doesn't depend on the track or channel at all.
+    pub fn new_spectrum(key: u8, settings: &MidiSettings) -> Self {
+        let [low, high] = settings.spectrum_hues;
+        let hue = low + (high - low) * (key.min(127) as f64 / 127.0);
+        Self::new_from_hue(hue.rem_euclid(360.0))
+    }
+
+    /// The color of each of the 256 keys, if the colors come from the keys
+    /// instead of the tracks
+    pub fn new_key_vec(settings: &MidiSettings) -> Option<Vec<Self>> {
+        (settings.colors == Colors::Spectrum).then(|| {
+            (0..=255)
+                .map(|key| Self::new_spectrum(key, settings))
+                .collect()
+        })
+    }
+
     pub fn new_random_vec(tracks: usize) -> Vec<Self> {
         let count = tracks * 16;
 
@@ -150,9 +563,18 @@ impl MIDIColor {
         tracks: usize,
         settings: &MidiSettings,
     ) -> Result<Vec<Self>, WasabiError> {
+        let tracks = tracks.clamp(1, MAX_COLORED_TRACKS);
+
         match settings.colors {
             Colors::Rainbow => Ok(MIDIColor::new_vec(tracks)),
             Colors::Random => Ok(MIDIColor::new_random_vec(tracks)),
+            // Only Cake colors every note by its key, the others use the
+            // brightness of the middle key
+            Colors::TrackPitch => Ok((0..tracks * 16)
+                .map(|i| MIDIColor::new_track_pitch(i / 16, 64, settings))
+                .collect()),
+            // Only used where the key isn't known, e.g. for the legend
+            Colors::Spectrum => Ok(vec![MIDIColor::new_spectrum(64, settings); tracks * 16]),
             Colors::Palette => {
                 let path = &settings.palette_path;
                 if path.exists() {
@@ -185,6 +607,66 @@ impl MIDIColor {
         }
     }
 
+    /// Returns the color for the given track/channel index, looping
+    /// through the table if the index is out of its bounds.
+    pub fn get_cycled(colors: &[Self], index: usize) -> Self {
+        colors[index % colors.len()]
+    }
+
+    /// Saves the colors as a PNG with one swatch for each track (rows) and
+    /// channel (columns), with the track and channel indices on the sides.
+    /// Only the first [`MAX_EXPORTED_TRACKS`] tracks are exported.
+    pub fn export_swatches(colors: &[Self], path: &Path) -> Result<(), WasabiError> {
+        const SWATCH_SIZE: u32 = 16;
+        const BACKGROUND: [u8; 4] = [30, 30, 30, 255];
+        const LABEL: [u8; 4] = [210, 210, 210, 255];
+
+        let tracks = colors.len().div_ceil(16).clamp(1, MAX_EXPORTED_TRACKS) as u32;
+        let left = digits_width(tracks - 1) + 2 * DIGIT_SCALE;
+        let top = DIGIT_HEIGHT + 2 * DIGIT_SCALE;
+
+        let mut image = image::RgbaImage::from_pixel(
+            left + 16 * SWATCH_SIZE,
+            top + tracks * SWATCH_SIZE,
+            image::Rgba(BACKGROUND),
+        );
+
+        for channel in 0..16 {
+            let x = left + channel * SWATCH_SIZE;
+            let x = x + SWATCH_SIZE.saturating_sub(digits_width(channel)) / 2;
+            draw_number(&mut image, x, DIGIT_SCALE, channel, LABEL);
+        }
+
+        for track in 0..tracks {
+            let y = top + track * SWATCH_SIZE;
+            draw_number(
+                &mut image,
+                DIGIT_SCALE,
+                y + (SWATCH_SIZE - DIGIT_HEIGHT) / 2,
+                track,
+                LABEL,
+            );
+
+            for channel in 0..16 {
+                let index = (track * 16 + channel) as usize;
+                let Some(color) = colors.get(index) else {
+                    continue;
+                };
+                let x = left + channel * SWATCH_SIZE;
+                let rgba = image::Rgba([color.red(), color.green(), color.blue(), 255]);
+                for py in y..y + SWATCH_SIZE {
+                    for px in x..x + SWATCH_SIZE {
+                        image.put_pixel(px, py, rgba);
+                    }
+                }
+            }
+        }
+
+        image
+            .save(path)
+            .map_err(|e| WasabiError::Other(format!("Failed to export the colors: {e}")))
+    }
+
     pub fn as_u32(&self) -> u32 {
         self.0
     }
@@ -204,6 +686,26 @@ impl MIDIColor {
     pub fn blue(&self) -> u8 {
         self.0 as u8
     }
+
+    /// Mixes the color with `other` by `amount`, from 0 (unchanged) to 1
+    pub fn mix(&self, other: MIDIColor, amount: f32) -> Self {
+        let amount = amount.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+        Self::new(
+            mix(self.red(), other.red()),
+            mix(self.green(), other.green()),
+            mix(self.blue(), other.blue()),
+        )
+    }
+
+    /// Moves the saturation towards full by `amount`, from 0 (unchanged) to 1
+    pub fn saturate(&self, amount: f32) -> Self {
+        let rgb = Srgb::new(self.red(), self.green(), self.blue()).into_format::<f32>();
+        let mut hsv: Hsv<Srgb, f32> = Hsv::from_color_unclamped(rgb);
+        hsv.saturation += (1.0 - hsv.saturation) * amount.clamp(0.0, 1.0);
+        let rgb: Srgb<u8> = Srgb::<f32>::from_color_unclamped(hsv).into_format();
+        Self::new(rgb.red, rgb.green, rgb.blue)
+    }
 }
 
 /// The basic shared functions in a midi file. The columns related functions are
@@ -218,9 +720,183 @@ pub trait MIDIFileBase {
 
     fn stats(&self) -> MIDIFileStats;
 
+    /// How long loading took, if the MIDI was fully loaded upfront
+    fn load_stats(&self) -> Option<LoadStats>;
+
+    /// The number of notes that start between `start` and `end` (in seconds),
+    /// if known. Used to show how many notes are coming above the view.
+    fn notes_starting_between(&self, start: f64, end: f64) -> Option<u64>;
+
     fn allows_seeking_backward(&self) -> bool;
 
+    /// The lowest and highest keys that contain notes, if known
+    fn key_range(&self) -> Option<(u8, u8)>;
+
+    /// The note count of each track, indexed by track, if known
+    fn notes_per_track(&self) -> Option<&[u64]>;
+
+    /// The start of the first note in seconds, if known
+    fn first_note_time(&self) -> Option<f64>;
+
+    /// The note count of each velocity, if known
+    fn velocity_counts(&self) -> Option<&[u64; 128]>;
+
+    /// The note counts of `bins` equally wide velocity ranges, starting at
+    /// the lowest velocity. Empty if the velocities aren't known.
+    fn velocity_histogram(&self, bins: usize) -> Vec<u64> {
+        let Some(counts) = self.velocity_counts() else {
+            return Vec::new();
+        };
+
+        let bins = bins.clamp(1, counts.len());
+        let mut histogram = vec![0; bins];
+        for (velocity, count) in counts.iter().enumerate() {
+            histogram[velocity * bins / counts.len()] += count;
+        }
+        histogram
+    }
+
+    /// The instruments that play notes, sorted by channel, if known
+    fn instruments(&self) -> Option<&[Instrument]>;
+
+    /// The quirks of the file that came up while loading it, if known
+    fn parse_report(&self) -> Option<&ParseReport>;
+
+    /// The tempo and time signature changes, if known
+    fn tempo_changes(&self) -> Option<&[TempoChange]>;
+
+    /// The beats of the MIDI, if known
+    fn beats(&self) -> Option<&[Beat]>;
+
+    /// The note colors, indexed by `track * 16 + channel`, see [`MIDIColor::get_cycled`]
+    fn track_colors(&self) -> &[MIDIColor];
+
+    /// The distinct note colors with what they stand for, for the tracks and
+    /// channels that have notes. When every track or every channel has one
+    /// color, the colors are labeled by track or channel only. Colors that
+    /// repeat keep the label of their first use.
+    fn color_legend(&self) -> Vec<(MIDIColor, String)> {
+        let colors = self.track_colors();
+        if colors.is_empty() {
+            return Vec::new();
+        }
+
+        let tracks: Vec<usize> = match self.notes_per_track() {
+            Some(notes) => (0..notes.len()).filter(|track| notes[*track] > 0).collect(),
+            None => (0..colors.len() / 16).collect(),
+        };
+        // The instruments are sorted by channel
+        let mut channels: Vec<usize> = match self.instruments() {
+            Some(instruments) => instruments.iter().map(|i| i.channel as usize).collect(),
+            None => (0..16).collect(),
+        };
+        channels.dedup();
+
+        let (Some(first_track), Some(first_channel)) = (tracks.first(), channels.first()) else {
+            return Vec::new();
+        };
+
+        let color =
+            |track: usize, channel: usize| MIDIColor::get_cycled(colors, track * 16 + channel);
+        let by_track = tracks.iter().all(|track| {
+            channels
+                .iter()
+                .all(|channel| color(*track, *channel) == color(*track, *first_channel))
+        });
+        let by_channel = channels.iter().all(|channel| {
+            tracks
+                .iter()
+                .all(|track| color(*track, *channel) == color(*first_track, *channel))
+        });
+
+        let mut legend: Vec<(MIDIColor, String)> = Vec::new();
+        let mut push = |color: MIDIColor, label: String| {
+            if !legend.iter().any(|(c, _)| *c == color) {
+                legend.push((color, label));
+            }
+        };
+
+        match (by_track, by_channel) {
+            (true, true) => push(color(*first_track, *first_channel), "All Notes".to_string()),
+            (true, false) => {
+                for track in tracks.iter() {
+                    push(color(*track, *first_channel), format!("Track {}", track));
+                }
+            }
+            (false, true) => {
+                for channel in channels.iter() {
+                    push(
+                        color(*first_track, *channel),
+                        format!("Channel {}", channel + 1),
+                    );
+                }
+            }
+            (false, false) => {
+                for track in tracks.iter() {
+                    for channel in channels.iter() {
+                        push(
+                            color(*track, *channel),
+                            format!("Track {}, Channel {}", track, channel + 1),
+                        );
+                    }
+                }
+            }
+        }
+
+        legend
+    }
+
     fn signature(&self) -> &MIDIFileUniqueSignature;
+
+    /// The PPQ stored in the file, ignoring any override
+    fn ppq(&self) -> u16;
+
+    /// The note that is displayed on top on `key` at `time` (in seconds),
+    /// following the overlap setting. Only notes that are in view can be found.
+    fn note_at(&self, key: u8, time: f64) -> Option<NoteDetails>;
+
+    /// The notes that are visible at the current time with the given view
+    /// range, in drawing order for each key. These are the notes that the
+    /// renderer draws, so overlays can line up with them without culling the
+    /// notes again. Cake MIDIs walk their trees on the CPU for this, which is
+    /// much slower than the GPU culling of their renderer.
+    fn visible_notes(&mut self, range: f64) -> Vec<VisibleNote>;
+
+    /// Lays out the current moment of the MIDI for a renderer, see [`RenderFrame`]
+    fn render_frame(&mut self, range: f64) -> RenderFrame {
+        let notes = self.visible_notes(range);
+        RenderFrame::new(self.timer().get_time().as_seconds_f64(), range, notes)
+    }
+
+    /// Moves the playback to `time`, see [`TimeKeeper::seek`]. The audio
+    /// players follow the timer, so they continue at the new position.
+    fn seek(&mut self, time: Duration) {
+        self.timer_mut().seek(time);
+    }
+
+    /// Seeks to a fraction of the MIDI length, clamped between 0 and 1.
+    /// Returns false without seeking if the length isn't known yet, or if
+    /// the MIDI can't seek backward to the requested time.
+    fn seek_to_fraction(&mut self, fraction: f64) -> bool {
+        let Some(length) = self.midi_length() else {
+            return false;
+        };
+
+        let time = Duration::seconds_f64(length * fraction.clamp(0.0, 1.0));
+        if time < self.timer().get_time() && !self.allows_seeking_backward() {
+            return false;
+        }
+
+        self.seek(time);
+        true
+    }
+
+    /// All the events between `start` and `end` (in seconds), including the
+    /// ones that don't get displayed. They are read again from the file, see
+    /// [`read_debug_events`] for reading them on another thread.
+    fn debug_events(&self, start: f64, end: f64) -> Result<Vec<DebugEvent>, WasabiError> {
+        read_debug_events(self.signature(), start, end)
+    }
 }
 
 /// This trait contains a function to retrieve the column view of the midi
@@ -230,6 +906,57 @@ pub trait MIDIFile: MIDIFileBase {
         Self: 'a;
 
     fn get_current_column_views(&mut self, range: f64) -> Self::ColumnsViews<'_>;
+
+    /// Hashes the notes that would be drawn at `time` with the given view
+    /// range (their keys, positions, lengths and colors), without using the
+    /// GPU. The hash is the same on every run, so it can be used to check that
+    /// a change doesn't affect what gets drawn.
+    ///
+    /// This pauses the timer at `time`.
+    fn render_snapshot(&mut self, time: Duration, range: f64) -> u64
+    where
+        Self: Sized,
+    {
+        self.timer_mut().set_time(time);
+
+        let views = self.get_current_column_views(range);
+        let mut hasher = FxHasher::default();
+        for key in 0..256 {
+            for note in views.get_column(key).iterate_displaced_notes() {
+                (
+                    key,
+                    note.start.to_bits(),
+                    note.len.to_bits(),
+                    note.color.as_u32(),
+                )
+                    .hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+}
+
+/// Collects the notes of the views for [`MIDIFileBase::visible_notes`]
+fn visible_notes_in(views: &impl MIDINoteViews) -> Vec<VisibleNote> {
+    let length = views.range().length().max(f64::EPSILON) as f32;
+    let mut notes = Vec::new();
+    for key in 0..256 {
+        notes.extend(
+            views
+                .get_column(key)
+                .iterate_displaced_notes()
+                .map(|note| VisibleNote {
+                    key: key as u8,
+                    start: note.start / length,
+                    end: (note.start + note.len) / length,
+                    color: note.color,
+                    velocity: note.velocity,
+                    track: Some(note.track),
+                    id: NoteId::new(key as u8, note.position),
+                }),
+        );
+    }
+    notes
 }
 
 pub trait MIDINoteViews {
@@ -253,6 +980,86 @@ pub struct DisplacedMIDINote {
     pub start: f32,
     pub len: f32,
     pub color: MIDIColor,
+    /// Identifies the note within its key, see [`NoteId`]
+    pub position: (u64, u64),
+    pub velocity: u8,
+    pub track: u32,
+}
+
+/// A note that is visible right now, see [`MIDIFileBase::visible_notes`]
+#[derive(Debug, Clone, Copy)]
+pub struct VisibleNote {
+    pub key: u8,
+    /// Where the note starts within the view, from 0.0 at the keyboard to
+    /// 1.0 at the top. Notes that already started are below 0.0.
+    pub start: f32,
+    /// Where the note ends within the view, can be above 1.0
+    pub end: f32,
+    pub color: MIDIColor,
+    pub velocity: u8,
+    /// Not known for Cake MIDIs, which don't keep the tracks of the notes
+    pub track: Option<u32>,
+    pub id: NoteId,
+}
+
+/// Everything a renderer needs to draw one frame, independent of how it
+/// draws it (e.g. for SVG or text output), see [`MIDIFileBase::render_frame`].
+/// The built in renderer draws the RAM and Live MIDIs from it, while the
+/// Cake MIDIs get culled on the GPU instead.
+#[derive(Debug, Clone)]
+pub struct RenderFrame {
+    /// The time of the MIDI at the keyboard, in seconds
+    pub time: f64,
+    /// How many seconds of the MIDI the view spans
+    pub view_range: f64,
+    pub notes: Vec<VisibleNote>,
+    /// The color of each of the 256 keys, if one of its notes is playing
+    pub key_colors: Vec<Option<MIDIColor>>,
+}
+
+impl RenderFrame {
+    /// `notes` have to be sorted by key, and in drawing order for each key
+    /// with the note on top first
+    pub fn new(time: f64, view_range: f64, notes: Vec<VisibleNote>) -> Self {
+        // Like the built in renderers, the first playing note colors the key
+        let mut key_colors = vec![None; 256];
+        for note in notes.iter() {
+            let color = &mut key_colors[note.key as usize];
+            if color.is_none() && note_playing(note.start as f64, note.end as f64, 0.0) {
+                *color = Some(note.color);
+            }
+        }
+
+        RenderFrame {
+            time,
+            view_range,
+            notes,
+            key_colors,
+        }
+    }
+
+    /// The notes of `key`, in drawing order
+    pub fn key_notes(&self, key: u8) -> &[VisibleNote] {
+        let start = self.notes.partition_point(|note| note.key < key);
+        let end = self.notes.partition_point(|note| note.key <= key);
+        &self.notes[start..end]
+    }
+}
+
+/// Identifies a note across frames, so it can be tracked by overlays.
+///
+/// A note keeps the same ID in every frame for as long as the MIDI stays
+/// loaded, but the IDs are not stable across reloads or parsing algorithms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NoteId(u64);
+
+impl NoteId {
+    /// `position` has to be unique for each note of the key
+    pub fn new(key: u8, position: (u64, u64)) -> NoteId {
+        let mut hasher = FxHasher::default();
+        (key, position).hash(&mut hasher);
+        NoteId(hasher.finish())
+    }
 }
 
 #[enum_dispatch(MIDIFileBase)]
@@ -261,3 +1068,265 @@ pub enum MIDIFileUnion {
     Live(live::LiveLoadMIDIFile),
     Cake(cake::CakeMIDIFile),
 }
+
+impl MIDIFileUnion {
+    /// The algorithm that loaded the MIDI
+    pub fn parsing(&self) -> MidiParsing {
+        match self {
+            MIDIFileUnion::InRam(_) => MidiParsing::Ram,
+            MIDIFileUnion::Live(_) => MidiParsing::Live,
+            MIDIFileUnion::Cake(_) => MidiParsing::Cake,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use crate::{
+        error::WasabiError,
+        settings::{MidiSettings, NoteOverlap},
+    };
+
+    use super::{
+        shared::test_midi, CakeMIDIFile, InRamMIDIFile, MIDIColor, MIDIFile, MIDIFileBase,
+        MIDIFileUnion, NoteId, RenderFrame, VisibleNote, MAX_COLORED_TRACKS,
+    };
+
+    #[test]
+    fn color_table_is_capped() {
+        let colors =
+            MIDIColor::new_vec_from_settings(usize::MAX, &MidiSettings::default()).unwrap();
+        assert_eq!(colors.len(), MAX_COLORED_TRACKS * 16);
+        assert_eq!(
+            MIDIColor::get_cycled(&colors, colors.len() + 3),
+            MIDIColor::get_cycled(&colors, 3)
+        );
+    }
+
+    #[test]
+    fn header_with_huge_track_count() {
+        let events = test_midi::note_sequence(4, 96, 48);
+        let path = test_midi::write_midi_claiming_tracks("huge_track_count", u16::MAX, &events);
+
+        // The missing tracks either get skipped or make the load fail with
+        // an error, but never panic or allocate for every claimed track
+        match InRamMIDIFile::load_from_file(&path, None, &MidiSettings::default()) {
+            Ok(midi) => assert!(midi.track_colors().len() <= MAX_COLORED_TRACKS * 16),
+            Err(WasabiError::MidiLoadError(_) | WasabiError::MidiFormatError(_)) => {}
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+
+        std::fs::remove_file(path).ok();
+    }
+
+    fn note(key: u8, start: f32, end: f32, color: MIDIColor) -> VisibleNote {
+        VisibleNote {
+            key,
+            start,
+            end,
+            color,
+            velocity: 100,
+            track: Some(0),
+            id: NoteId::new(key, (start.to_bits() as u64, 0)),
+        }
+    }
+
+    /// A long note on channel 1 from 0 to 2 seconds, covered by a short one
+    /// on channel 2 from 0.5 to 1 second
+    fn covered_note_midi(name: &str) -> std::path::PathBuf {
+        let half_second = test_midi::TICKS_PER_SECOND / 2;
+        test_midi::write_midi(
+            name,
+            &[
+                (0, [0x90, 60, 100]),
+                (half_second, [0x91, 60, 50]),
+                (half_second, [0x81, 60, 0]),
+                (half_second * 2, [0x80, 60, 0]),
+            ],
+        )
+    }
+
+    #[test]
+    fn note_at_picks_the_top_note() {
+        let path = covered_note_midi("note_at_ram");
+
+        for (overlap, covered_channel) in [(NoteOverlap::Newest, 1), (NoteOverlap::Oldest, 0)] {
+            let settings = MidiSettings {
+                note_overlap: overlap,
+                ..Default::default()
+            };
+            let midi = InRamMIDIFile::load_from_file(&path, None, &settings).unwrap();
+
+            let channel_at = |time| midi.note_at(60, time).and_then(|note| note.channel);
+            assert_eq!(channel_at(0.25), Some(0));
+            assert_eq!(channel_at(0.75), Some(covered_channel), "{overlap:?}");
+            assert_eq!(channel_at(1.5), Some(0));
+            assert_eq!(channel_at(2.5), None);
+            assert!(midi.note_at(61, 0.75).is_none());
+
+            let note = midi.note_at(60, 1.5).unwrap();
+            assert_eq!((note.start, note.length, note.velocity), (0.0, 2.0, 100));
+        }
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn note_at_walks_the_cake_tree() {
+        let path = covered_note_midi("note_at_cake");
+        let midi =
+            CakeMIDIFile::load_from_file(&path, None, &MidiSettings::default(), None).unwrap();
+
+        let note = midi.note_at(60, 0.75).unwrap();
+        assert!((note.start - 0.5).abs() < 1e-3 && (note.length - 0.5).abs() < 1e-3);
+        assert_eq!(note.velocity, 50);
+        // The trees don't keep the tracks and channels
+        assert_eq!((note.track, note.channel), (None, None));
+
+        let note = midi.note_at(60, 1.5).unwrap();
+        assert!(note.start.abs() < 1e-3 && (note.length - 2.0).abs() < 1e-3);
+        assert!(midi.note_at(60, 2.5).is_none());
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn parse_report_counts_the_pairing_of_notes() {
+        let beat = test_midi::PPQ as u32;
+        let path = test_midi::write_midi(
+            "parse_report",
+            &[
+                (0, [0x90, 60, 100]),
+                // A note on with a velocity of 0 ends the note
+                (beat, [0x90, 60, 0]),
+                (0, [0x80, 61, 0]),
+                (0, [0x90, 62, 100]),
+                (beat, [0x90, 62, 100]),
+                (beat, [0x80, 62, 0]),
+            ],
+        );
+
+        let settings = MidiSettings::default();
+        let ram = InRamMIDIFile::load_from_file(&path, None, &settings).unwrap();
+        let cake = CakeMIDIFile::load_from_file(&path, None, &settings, None).unwrap();
+
+        for report in [ram.parse_report(), cake.parse_report()] {
+            let report = report.unwrap();
+            assert_eq!(report.overlapping_notes, 1);
+            assert_eq!(report.unmatched_note_offs, 1);
+            assert_eq!(report.unended_notes, 1);
+        }
+        assert!(ram.note_at(60, 0.6).is_none());
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn visible_notes_through_the_union() {
+        let path = covered_note_midi("visible_notes");
+        let settings = MidiSettings::default();
+        let midis = [
+            MIDIFileUnion::InRam(InRamMIDIFile::load_from_file(&path, None, &settings).unwrap()),
+            MIDIFileUnion::Cake(
+                CakeMIDIFile::load_from_file(&path, None, &settings, None).unwrap(),
+            ),
+        ];
+
+        for mut midi in midis {
+            midi.timer_mut().set_time(Duration::seconds_f64(0.75));
+            let mut notes = midi.visible_notes(1.0);
+            notes.sort_by(|a, b| a.end.total_cmp(&b.end));
+
+            // The short note ends a quarter of the view up, the long one past the top
+            let ends: Vec<_> = notes
+                .iter()
+                .map(|note| (note.key, (note.end * 4.0).round()))
+                .collect();
+            assert_eq!(ends, [(60, 1.0), (60, 5.0)], "{:?}", midi.parsing());
+        }
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn render_snapshot_is_repeatable() {
+        let spacing = test_midi::TICKS_PER_SECOND / 2;
+        let events = test_midi::note_sequence(8, spacing, spacing / 2);
+        let path = test_midi::write_midi("render_snapshot", &events);
+
+        let settings = MidiSettings::default();
+        let mut first = InRamMIDIFile::load_from_file(&path, None, &settings).unwrap();
+        let mut second = InRamMIDIFile::load_from_file(&path, None, &settings).unwrap();
+        let snapshot = |midi: &mut InRamMIDIFile, seconds: f64| {
+            midi.render_snapshot(Duration::seconds_f64(seconds), 1.0)
+        };
+
+        let at_one = snapshot(&mut first, 1.0);
+        assert_eq!(at_one, snapshot(&mut second, 1.0));
+        assert_ne!(at_one, snapshot(&mut first, 1.25));
+        // Seeking back draws the same notes again
+        assert_eq!(at_one, snapshot(&mut first, 1.0));
+        // Past the end there is nothing left to draw
+        assert_eq!(snapshot(&mut first, 10.0), snapshot(&mut second, 20.0));
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn split_notes_are_counted_once() {
+        // Four notes of two seconds, split into four segments each
+        let two_seconds = test_midi::TICKS_PER_SECOND * 2;
+        let events = test_midi::note_sequence(4, two_seconds, two_seconds);
+        let path = test_midi::write_midi("split_note_stats", &events);
+
+        let settings = MidiSettings {
+            split_long_notes: Some(0.5),
+            ..Default::default()
+        };
+        let mut midi = InRamMIDIFile::load_from_file(&path, None, &settings).unwrap();
+
+        let mut passed_at = |seconds: f64| {
+            midi.render_snapshot(Duration::seconds_f64(seconds), 1.0);
+            midi.stats()
+        };
+        assert_eq!(passed_at(3.0).passed_notes, Some(2));
+        let stats = passed_at(100.0);
+        assert_eq!(stats.total_notes, Some(4));
+        assert_eq!(stats.passed_notes, Some(4));
+        assert_eq!(stats.remaining_notes(), Some(0));
+        // Seeking back counts the notes again from the start
+        assert_eq!(passed_at(1.0).passed_notes, Some(1));
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn render_frame_colors_keys_by_the_top_playing_note() {
+        let red = MIDIColor::new(255, 0, 0);
+        let green = MIDIColor::new(0, 255, 0);
+        let blue = MIDIColor::new(0, 0, 255);
+
+        let frame = RenderFrame::new(
+            10.0,
+            2.0,
+            vec![
+                // Not playing yet, so it doesn't color the key even when on top
+                note(60, 0.5, 0.8, red),
+                note(60, -0.2, 0.4, green),
+                note(60, -0.5, 1.2, blue),
+                // Ends right at the keyboard
+                note(62, -0.5, 0.0, red),
+                note(64, 0.0, 0.3, blue),
+            ],
+        );
+
+        assert_eq!(frame.notes.len(), 5);
+        assert_eq!(frame.key_colors.len(), 256);
+        assert_eq!(frame.key_colors[60], Some(green));
+        assert_eq!(frame.key_colors[62], None);
+        assert_eq!(frame.key_colors[64], Some(blue));
+        assert_eq!(frame.key_colors[61], None);
+    }
+}
@@ -4,27 +4,32 @@ pub struct InRamNoteBlock {
     pub start: f64,
     pub max_length: f32,
     pub notes: Box<[BasicMIDINote]>,
+    /// The number of notes at the beginning of the block that are
+    /// continuations of split notes, rather than new notes
+    pub continued: usize,
 }
 
 #[derive(Debug, Clone)]
 pub struct BasicMIDINote {
     pub len: f32,
     pub track_chan: TrackAndChannel,
+    pub velocity: u8,
 }
 
 impl InRamNoteBlock {
-    /// Creates a new block from an iterator of Track/Channel values.
-    /// This assumes that the lengths will be added in the future.
+    /// Creates a new block from an iterator of Track/Channel values and
+    /// velocities. This assumes that the lengths will be added in the future.
     pub fn new_from_trackchans(
         time: f64,
-        track_chans_iter: impl ExactSizeIterator<Item = TrackAndChannel>,
+        track_chans_iter: impl ExactSizeIterator<Item = (TrackAndChannel, u8)>,
     ) -> Self {
         let mut notes: Vec<BasicMIDINote> = Vec::with_capacity(track_chans_iter.len());
 
-        for track_chan in track_chans_iter {
+        for (track_chan, velocity) in track_chans_iter {
             notes.push(BasicMIDINote {
                 len: 0.0,
                 track_chan,
+                velocity,
             });
         }
 
@@ -32,9 +37,63 @@ impl InRamNoteBlock {
             start: time,
             notes: notes.into_boxed_slice(),
             max_length: 0.0,
+            continued: 0,
         }
     }
 
+    /// Creates a block that contains the continuations of split notes,
+    /// followed by the notes of `block` (if there is one at that time).
+    pub fn new_with_continued(
+        time: f64,
+        continued: Vec<BasicMIDINote>,
+        block: Option<InRamNoteBlock>,
+    ) -> Self {
+        let continued_count = continued.len();
+        let mut notes = continued;
+        if let Some(block) = block {
+            notes.extend(block.notes.into_vec());
+        }
+
+        let max_length = notes.iter().fold(0.0f32, |max, note| max.max(note.len));
+
+        InRamNoteBlock {
+            start: time,
+            notes: notes.into_boxed_slice(),
+            max_length,
+            continued: continued_count,
+        }
+    }
+
+    /// Shortens the notes that are longer than `max_length` and returns the
+    /// remaining parts of them, as (start time, note) pairs.
+    pub fn split_long_notes(&mut self, max_length: f32) -> Vec<(f64, BasicMIDINote)> {
+        let mut parts = Vec::new();
+
+        for note in self.notes.iter_mut() {
+            let mut offset = max_length;
+            while offset < note.len {
+                parts.push((
+                    self.start + offset as f64,
+                    BasicMIDINote {
+                        len: (note.len - offset).min(max_length),
+                        track_chan: note.track_chan,
+                        velocity: note.velocity,
+                    },
+                ));
+                offset += max_length;
+            }
+            note.len = note.len.min(max_length);
+        }
+
+        self.max_length = self.max_length.min(max_length);
+        parts
+    }
+
+    /// The number of notes that start in this block
+    pub fn started_notes(&self) -> usize {
+        self.notes.len() - self.continued
+    }
+
     pub fn set_note_end_time(&mut self, note_index: usize, end_time: f64) {
         let note = &mut self.notes[note_index];
         note.len = (end_time - self.start) as f32;
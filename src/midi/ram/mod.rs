@@ -1,8 +1,11 @@
 use self::view::{InRamCurrentNoteViews, InRamNoteViewData};
 
 use super::{
-    shared::timer::TimeKeeper, MIDIFile, MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature,
-    MIDIViewRange,
+    shared::{
+        instruments::Instrument, parse_report::ParseReport, tempo_map::TempoMap, timer::TimeKeeper,
+    },
+    Beat, LoadStats, MIDIColor, MIDIFile, MIDIFileBase, MIDIFileStats, MIDIFileUniqueSignature,
+    MIDIViewRange, NoteDetails, TempoChange, VisibleNote,
 };
 
 pub mod block;
@@ -15,7 +18,16 @@ pub struct InRamMIDIFile {
     timer: TimeKeeper,
     length: f64,
     note_count: u64,
+    key_range: Option<(u8, u8)>,
+    track_note_counts: Vec<u64>,
+    velocity_counts: [u64; 128],
+    first_note: Option<f64>,
+    instruments: Vec<Instrument>,
+    parse_report: ParseReport,
+    tempo_map: TempoMap,
     signature: MIDIFileUniqueSignature,
+    ppq: u16,
+    load_stats: LoadStats,
 }
 
 impl InRamMIDIFile {}
@@ -41,6 +53,42 @@ impl MIDIFileBase for InRamMIDIFile {
         true
     }
 
+    fn key_range(&self) -> Option<(u8, u8)> {
+        self.key_range
+    }
+
+    fn notes_per_track(&self) -> Option<&[u64]> {
+        Some(&self.track_note_counts)
+    }
+
+    fn first_note_time(&self) -> Option<f64> {
+        self.first_note
+    }
+
+    fn velocity_counts(&self) -> Option<&[u64; 128]> {
+        Some(&self.velocity_counts)
+    }
+
+    fn instruments(&self) -> Option<&[Instrument]> {
+        Some(&self.instruments)
+    }
+
+    fn parse_report(&self) -> Option<&ParseReport> {
+        Some(&self.parse_report)
+    }
+
+    fn tempo_changes(&self) -> Option<&[TempoChange]> {
+        Some(self.tempo_map.changes())
+    }
+
+    fn beats(&self) -> Option<&[Beat]> {
+        Some(self.tempo_map.beats())
+    }
+
+    fn track_colors(&self) -> &[MIDIColor] {
+        self.view_data.track_colors()
+    }
+
     fn stats(&self) -> MIDIFileStats {
         MIDIFileStats {
             total_notes: Some(self.note_count),
@@ -48,9 +96,29 @@ impl MIDIFileBase for InRamMIDIFile {
         }
     }
 
+    fn load_stats(&self) -> Option<LoadStats> {
+        Some(self.load_stats)
+    }
+
+    fn notes_starting_between(&self, start: f64, end: f64) -> Option<u64> {
+        Some(self.view_data.notes_starting_between(start, end))
+    }
+
     fn signature(&self) -> &MIDIFileUniqueSignature {
         &self.signature
     }
+
+    fn ppq(&self) -> u16 {
+        self.ppq
+    }
+
+    fn note_at(&self, key: u8, time: f64) -> Option<NoteDetails> {
+        self.view_data.note_at(key, time)
+    }
+
+    fn visible_notes(&mut self, range: f64) -> Vec<VisibleNote> {
+        super::visible_notes_in(&self.get_current_column_views(range))
+    }
 }
 
 impl MIDIFile for InRamMIDIFile {
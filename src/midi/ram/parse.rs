@@ -1,4 +1,5 @@
-use std::{collections::VecDeque, path::PathBuf, sync::Arc, thread};
+use std::{collections::VecDeque, iter::Peekable, path::PathBuf, sync::Arc, thread, time::Instant};
+use time::Duration;
 
 use midi_toolkit::{
     events::{Event, MIDIEventEnum},
@@ -13,18 +14,28 @@ use rustc_hash::FxHashMap;
 
 use crate::{
     audio_playback::WasabiAudioPlayer,
-    gui::window::WasabiError,
+    error::WasabiError,
     midi::{
-        audio::ram::InRamAudioPlayer,
-        open_file_and_signature,
+        audio::{metronome::MetronomePlayer, ram::InRamAudioPlayer},
+        channel_filtered, display_key, effective_ppq, note_off_key, open_file_and_signature,
         ram::{column::InRamNoteColumn, view::InRamNoteViewData},
-        shared::{audio::CompressedAudio, timer::TimeKeeper, track_channel::TrackAndChannel},
-        MIDIColor,
+        shared::{
+            audio::CompressedAudio,
+            instruments::InstrumentsBuilder,
+            parse_report::{NotePairing, ParseReportBuilder},
+            tempo_map::TempoMapBuilder,
+            timer::TimeKeeper,
+            track_channel::TrackAndChannel,
+        },
+        transposed_key, LoadStats, MIDIColor,
     },
     settings::MidiSettings,
 };
 
-use super::{block::InRamNoteBlock, InRamMIDIFile};
+use super::{
+    block::{BasicMIDINote, InRamNoteBlock},
+    InRamMIDIFile,
+};
 
 struct UnendedNote {
     column_index: usize,
@@ -33,8 +44,9 @@ struct UnendedNote {
 
 struct Key {
     column: Vec<InRamNoteBlock>,
-    block_builder: Vec<TrackAndChannel>,
+    block_builder: Vec<(TrackAndChannel, u8)>,
     unended_notes: FxHashMap<TrackAndChannel, VecDeque<UnendedNote>>,
+    pairing: NotePairing,
 }
 
 impl Key {
@@ -43,14 +55,18 @@ impl Key {
             column: Vec::new(),
             block_builder: Vec::new(),
             unended_notes: FxHashMap::default(),
+            pairing: NotePairing::default(),
         }
     }
 
-    fn add_note(&mut self, track_chan: TrackAndChannel) {
+    fn add_note(&mut self, track_chan: TrackAndChannel, velocity: u8) {
         let block_index = self.block_builder.len();
         let column_index = self.column.len();
-        self.block_builder.push(track_chan);
+        self.block_builder.push((track_chan, velocity));
         let unended_queue = self.unended_notes.entry(track_chan).or_default();
+        if !unended_queue.is_empty() {
+            self.pairing.overlapping_notes += 1;
+        }
         unended_queue.push_back(UnendedNote {
             column_index,
             block_index,
@@ -75,6 +91,8 @@ impl Key {
                 }
                 block.set_note_end_time(note.block_index, time);
             }
+        } else {
+            self.pairing.unmatched_note_offs += 1;
         }
     }
 
@@ -88,40 +106,125 @@ impl Key {
     pub fn end_all(&mut self, time: f64) {
         for (_, mut queue) in self.unended_notes.drain() {
             for note in queue.drain(..) {
+                self.pairing.unended_notes += 1;
                 self.column[note.column_index].set_note_end_time(note.block_index, time);
             }
         }
     }
+
+    /// Splits the notes that are longer than `max_length` into consecutive
+    /// notes. Long notes keep all the blocks after them in view until they
+    /// end, so this lets the blocks that have already passed get culled.
+    pub fn split_long_notes(&mut self, max_length: f32) {
+        let mut parts: Vec<(f64, BasicMIDINote)> = self
+            .column
+            .iter_mut()
+            .flat_map(|block| block.split_long_notes(max_length))
+            .collect();
+
+        if parts.is_empty() {
+            return;
+        }
+
+        parts.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut parts = parts.into_iter().peekable();
+        let mut column = Vec::with_capacity(self.column.len());
+
+        for block in self.column.drain(..) {
+            // The parts that start before this block get their own blocks
+            while let Some(time) = parts.peek().map(|p| p.0).filter(|t| *t < block.start) {
+                let continued = take_parts_at(&mut parts, time);
+                column.push(InRamNoteBlock::new_with_continued(time, continued, None));
+            }
+
+            let continued = take_parts_at(&mut parts, block.start);
+            if continued.is_empty() {
+                column.push(block);
+            } else {
+                column.push(InRamNoteBlock::new_with_continued(
+                    block.start,
+                    continued,
+                    Some(block),
+                ));
+            }
+        }
+
+        while let Some(time) = parts.peek().map(|p| p.0) {
+            let continued = take_parts_at(&mut parts, time);
+            column.push(InRamNoteBlock::new_with_continued(time, continued, None));
+        }
+
+        self.column = column;
+    }
+}
+
+fn take_parts_at(
+    parts: &mut Peekable<impl Iterator<Item = (f64, BasicMIDINote)>>,
+    time: f64,
+) -> Vec<BasicMIDINote> {
+    let mut notes = Vec::new();
+    while let Some((_, note)) = parts.next_if(|p| p.0 == time) {
+        notes.push(note);
+    }
+    notes
 }
 
 impl InRamMIDIFile {
     pub fn load_from_file(
         path: impl Into<PathBuf>,
-        player: Arc<WasabiAudioPlayer>,
+        player: Option<Arc<WasabiAudioPlayer>>,
         settings: &MidiSettings,
     ) -> Result<Self, WasabiError> {
-        let (file, signature) = open_file_and_signature(path)?;
+        let load_start = Instant::now();
+        let (file, signature) = open_file_and_signature(
+            path,
+            settings.ppq_override,
+            settings.channel_filter,
+            settings.transpose,
+        )?;
         let midi = TKMIDIFile::open_from_stream(file, None).map_err(WasabiError::MidiLoadError)?;
 
-        let ppq = midi.ppq();
+        let file_ppq = midi.ppq();
+        let ppq = effective_ppq(file_ppq, settings);
+        let mut tempo_map = TempoMapBuilder::new();
         let merged = pipe!(
             midi.iter_all_track_events_merged_batches()
             |>TimeCaster::<f64>::cast_event_delta()
+        )
+        .inspect(|batch| {
+            if let Ok(batch) = batch {
+                tempo_map.push_batch(batch);
+            }
+        });
+        let merged = pipe!(
+            merged
             |>cancel_tempo_events(250000)
             |>scale_event_time(1.0 / ppq as f64)
             |>unwrap_items()
         );
 
+        let track_count = midi.track_count();
+
         type Ev = Delta<f64, Track<EventBatch<Event>>>;
         let (key_snd, key_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
-        let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
 
-        let key_join_handle = thread::spawn(|| {
+        let split_long_notes = settings.split_long_notes.filter(|len| *len > 0.0);
+        let drum_lane = settings.drum_lane;
+        let channel_filter = settings.channel_filter;
+        let transpose = settings.transpose;
+        let key_join_handle = thread::spawn(move || {
+            let start = Instant::now();
             let mut keys: Vec<Key> = (0..256).map(|_| Key::new()).collect();
 
             let mut time = 0.0;
 
             let mut notes = 0;
+            let mut track_notes = vec![0u64; track_count];
+            let mut velocity_counts = [0u64; 128];
+            let mut first_note = None;
+            let mut instruments = InstrumentsBuilder::new();
+            let mut parse_report = ParseReportBuilder::new(file_ppq);
 
             fn flush_keys(time: f64, keys: &mut [Key]) {
                 for key in keys.iter_mut() {
@@ -136,35 +239,80 @@ impl InRamMIDIFile {
                 }
 
                 for event in batch.iter_events() {
+                    if channel_filtered(event.as_event(), &channel_filter) {
+                        continue;
+                    }
                     let track = event.track;
+                    instruments.push_event(event.as_event());
+                    parse_report.push_event(event.as_event());
                     match event.as_event() {
-                        Event::NoteOn(e) => {
+                        Event::NoteOn(e) if e.velocity > 0 => {
+                            let Some(key) = transposed_key(e.key, e.channel, &transpose) else {
+                                continue;
+                            };
                             let track_chan = TrackAndChannel::new(track, e.channel);
-                            keys[e.key as usize].add_note(track_chan);
+                            keys[display_key(key, e.channel, drum_lane) as usize]
+                                .add_note(track_chan, e.velocity);
                             notes += 1;
+                            track_notes[track as usize] += 1;
+                            velocity_counts[e.velocity as usize & 0x7F] += 1;
+                            first_note.get_or_insert(time);
                         }
-                        Event::NoteOff(e) => {
-                            let track_chan = TrackAndChannel::new(track, e.channel);
-                            keys[e.key as usize].end_note(track_chan, time);
+                        other => {
+                            let Some((channel, key)) = note_off_key(other) else {
+                                continue;
+                            };
+                            let Some(key) = transposed_key(key, channel, &transpose) else {
+                                continue;
+                            };
+                            let track_chan = TrackAndChannel::new(track, channel);
+                            keys[display_key(key, channel, drum_lane) as usize]
+                                .end_note(track_chan, time);
                         }
-                        _ => {}
                     }
                 }
             }
 
             flush_keys(time, &mut keys);
 
+            let mut pairing = NotePairing::default();
             for key in keys.iter_mut() {
                 key.end_all(time);
+                pairing = pairing.merge(key.pairing);
+                if let Some(max_length) = split_long_notes {
+                    key.split_long_notes(max_length as f32);
+                }
             }
 
-            (keys, notes)
+            (
+                keys,
+                notes,
+                track_notes,
+                velocity_counts,
+                first_note,
+                instruments.build(),
+                parse_report.build(pairing),
+                start.elapsed(),
+            )
         });
 
-        let audio_join_handle = thread::spawn(|| {
-            let vec: Vec<_> = CompressedAudio::build_blocks(audio_rcv.into_iter()).collect();
-            vec
-        });
+        let (audio_snd, audio_join_handle) = match player {
+            Some(player) => {
+                let (audio_snd, audio_rcv) = crossbeam_channel::bounded::<Arc<Ev>>(1000);
+                let audio_join_handle = thread::spawn(move || {
+                    let start = Instant::now();
+                    let vec: Vec<_> = CompressedAudio::build_blocks(
+                        audio_rcv.into_iter(),
+                        channel_filter,
+                        transpose,
+                    )
+                    .collect();
+                    (vec, start.elapsed())
+                });
+                (Some(audio_snd), Some((audio_join_handle, player)))
+            }
+            None => (None, None),
+        };
 
         let mut length = 0.0;
 
@@ -172,33 +320,87 @@ impl InRamMIDIFile {
         for batch in merged {
             length += batch.delta;
             let batch = Arc::new(batch);
-            key_snd.send(batch.clone()).unwrap();
-            audio_snd.send(batch).unwrap();
+            if let Some(audio_snd) = audio_snd.as_ref() {
+                audio_snd.send(batch.clone()).unwrap();
+            }
+            key_snd.send(batch).unwrap();
         }
         // Drop the writers so the threads finish
         drop(key_snd);
         drop(audio_snd);
 
-        let (keys, note_count) = key_join_handle.join().unwrap();
-        let audio = audio_join_handle.join().unwrap();
+        let (
+            keys,
+            note_count,
+            track_note_counts,
+            velocity_counts,
+            first_note,
+            instruments,
+            parse_report,
+            tree_build_time,
+        ) = key_join_handle.join().unwrap();
+
+        let mut timer = TimeKeeper::new(
+            settings.start_delay,
+            Duration::seconds_f64(settings.global_time_offset),
+        );
 
-        let mut timer = TimeKeeper::new(settings.start_delay);
+        let tempo_map = tempo_map.build(ppq);
+
+        let mut audio_build_ms = None;
+        if let Some((audio_join_handle, player)) = audio_join_handle {
+            let (audio, audio_build_time) = audio_join_handle.join().unwrap();
+            audio_build_ms = Some(audio_build_time.as_secs_f64() * 1000.0);
+            if settings.metronome {
+                MetronomePlayer::new(
+                    tempo_map.beats().to_vec(),
+                    timer.get_listener(),
+                    player.clone(),
+                    settings.metronome_volume,
+                )
+                .spawn_playback();
+            }
+            InRamAudioPlayer::new(audio, timer.get_listener(), player).spawn_playback();
+        }
 
-        InRamAudioPlayer::new(audio, timer.get_listener(), player).spawn_playback();
+        let first_key = keys.iter().position(|k| !k.column.is_empty());
+        let last_key = keys.iter().rposition(|k| !k.column.is_empty());
+        let key_range = first_key.zip(last_key).map(|(f, l)| (f as u8, l as u8));
 
         let columns = keys
             .into_iter()
             .map(|key| InRamNoteColumn::new(key.column))
             .collect();
 
-        let colors = MIDIColor::new_vec_from_settings(midi.track_count(), settings)?;
+        let colors = MIDIColor::new_vec_from_settings(track_count, settings)?;
+
+        let load_stats = LoadStats {
+            parse_ms: load_start.elapsed().as_secs_f64() * 1000.0,
+            tree_build_ms: tree_build_time.as_secs_f64() * 1000.0,
+            audio_build_ms,
+        };
 
         Ok(InRamMIDIFile {
-            view_data: InRamNoteViewData::new(columns, colors),
+            view_data: InRamNoteViewData::new(
+                columns,
+                colors,
+                settings.note_overlap,
+                settings.drum_lane,
+                MIDIColor::new_key_vec(settings),
+            ),
             timer,
             length,
             note_count,
+            key_range,
+            track_note_counts,
+            velocity_counts,
+            first_note,
+            instruments,
+            parse_report,
+            tempo_map,
             signature,
+            ppq: file_ppq,
+            load_stats,
         })
     }
 }
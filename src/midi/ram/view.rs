@@ -1,14 +1,24 @@
 use gen_iter::GenIter;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
-use crate::midi::{DisplacedMIDINote, MIDIColor, MIDINoteColumnView, MIDINoteViews, MIDIViewRange};
+use crate::{
+    midi::{
+        blend_overlap_colors, note_playing, note_started, overlap_order, DisplacedMIDINote,
+        MIDIColor, MIDINoteColumnView, MIDINoteViews, MIDIViewRange, NoteDetails,
+    },
+    settings::NoteOverlap,
+};
 
-use super::column::InRamNoteColumn;
+use super::{block::BasicMIDINote, column::InRamNoteColumn};
 
 pub struct InRamNoteViewData {
     columns: Vec<InRamNoteColumn>,
     default_track_colors: Vec<MIDIColor>,
     view_range: MIDIViewRange,
+    overlap: NoteOverlap,
+    drum_lane: bool,
+    /// The color of every key, for the colors that don't depend on the track
+    key_colors: Option<Vec<MIDIColor>>,
 }
 
 pub struct InRamCurrentNoteViews<'a> {
@@ -22,7 +32,13 @@ impl<'a> InRamCurrentNoteViews<'a> {
 }
 
 impl InRamNoteViewData {
-    pub fn new(columns: Vec<InRamNoteColumn>, colors: Vec<MIDIColor>) -> Self {
+    pub fn new(
+        columns: Vec<InRamNoteColumn>,
+        colors: Vec<MIDIColor>,
+        overlap: NoteOverlap,
+        drum_lane: bool,
+        key_colors: Option<Vec<MIDIColor>>,
+    ) -> Self {
         InRamNoteViewData {
             columns,
             view_range: MIDIViewRange {
@@ -30,15 +46,61 @@ impl InRamNoteViewData {
                 end: 0.0,
             },
             default_track_colors: colors,
+            overlap,
+            drum_lane,
+            key_colors,
         }
     }
 
+    pub fn track_colors(&self) -> &[MIDIColor] {
+        &self.default_track_colors
+    }
+
     pub fn passed_notes(&self) -> u64 {
         self.columns
             .iter()
             .map(|column| column.data.notes_to_keyboard)
             .sum()
     }
+
+    pub fn note_at(&self, key: u8, time: f64) -> Option<NoteDetails> {
+        let column = self.columns.get(key as usize)?;
+        let first = column.data.block_range.start;
+        let end = column.blocks.partition_point(|block| block.start <= time);
+
+        for block_index in overlap_order(first..end.max(first), self.overlap) {
+            let block = &column.blocks[block_index];
+            for note_index in overlap_order(0..block.notes.len(), self.overlap) {
+                let note = &block.notes[note_index];
+                if note_playing(block.start, block.start + note.len as f64, time) {
+                    return Some(NoteDetails {
+                        key,
+                        start: block.start,
+                        length: note.len as f64,
+                        velocity: note.velocity,
+                        track: Some(note.track_chan.track()),
+                        channel: Some(note.track_chan.channel()),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn notes_starting_between(&self, start: f64, end: f64) -> u64 {
+        self.columns
+            .iter()
+            .map(|column| {
+                let first = column.blocks.partition_point(|block| block.start < start);
+                column.blocks[first..]
+                    .iter()
+                    .take_while(|block| block.start < end)
+                    .map(|block| block.started_notes() as u64)
+                    .sum::<u64>()
+            })
+            .sum()
+    }
 }
 
 impl InRamNoteViewData {
@@ -93,10 +155,10 @@ impl InRamNoteViewData {
                 // Increment the keyboard passed notes/blocks
                 while data.blocks_to_keyboard < blocks.len() {
                     let block = &blocks[data.blocks_to_keyboard];
-                    if block.start > new_view_range.start {
+                    if !note_started(block.start, new_view_range.start) {
                         break;
                     }
-                    data.notes_to_keyboard += block.notes.len() as u64;
+                    data.notes_to_keyboard += block.started_notes() as u64;
                     data.blocks_to_keyboard += 1;
                 }
             } else if new_view_range.start < old_view_range.start {
@@ -116,17 +178,17 @@ impl InRamNoteViewData {
                     data.notes_to_render_start += block.notes.len() as u64;
                     new_block_start += 1;
 
-                    data.notes_to_keyboard += block.notes.len() as u64;
+                    data.notes_to_keyboard += block.started_notes() as u64;
                     data.blocks_to_keyboard += 1;
                 }
 
                 // Increment the remaining keyboard blocks
                 while data.blocks_to_keyboard < blocks.len() {
                     let block = &blocks[data.blocks_to_keyboard];
-                    if block.start > new_view_range.start {
+                    if !note_started(block.start, new_view_range.start) {
                         break;
                     }
-                    data.notes_to_keyboard += block.notes.len() as u64;
+                    data.notes_to_keyboard += block.started_notes() as u64;
                     data.blocks_to_keyboard += 1;
                 }
             } else {
@@ -146,6 +208,12 @@ impl<'a> MIDINoteViews for InRamCurrentNoteViews<'a> {
             view: self.data,
             column: &self.data.columns[key],
             view_range: self.data.view_range,
+            key_color: self
+                .data
+                .drum_lane
+                .then(|| MIDIColor::new_for_drum(key as u8))
+                .flatten()
+                .or_else(|| self.data.key_colors.as_ref().map(|colors| colors[key])),
         }
     }
 
@@ -163,6 +231,9 @@ pub struct InRamNoteColumnView<'a> {
     view: &'a InRamNoteViewData,
     column: &'a InRamNoteColumn,
     view_range: MIDIViewRange,
+    /// Drum lane notes are colored by instrument instead of by track, and
+    /// the notes of the key colors by their key
+    key_color: Option<MIDIColor>,
 }
 
 impl<'a> MIDINoteColumnView for InRamNoteColumnView<'a> {
@@ -170,19 +241,45 @@ impl<'a> MIDINoteColumnView for InRamNoteColumnView<'a> {
 
     fn iterate_displaced_notes(&self) -> Self::Iter<'_> {
         let colors = &self.view.default_track_colors;
+        let overlap = self.view.overlap;
+
+        let note_color = move |note: &BasicMIDINote| {
+            self.key_color
+                .unwrap_or_else(|| MIDIColor::get_cycled(colors, note.track_chan.as_usize()))
+        };
 
         let iter = GenIter(
             #[coroutine]
             move || {
-                for block_index in self.column.data.block_range.clone().rev() {
+                let block_range = self.column.data.block_range.clone();
+                // Yielded from the end, as the drawing order is the reverse
+                let mut blended = (overlap == NoteOverlap::Blend).then(|| {
+                    blend_overlap_colors(self.column.blocks[block_range.clone()].iter().flat_map(
+                        |block| {
+                            block
+                                .notes
+                                .iter()
+                                .map(|note| (block.start, note.len, note_color(note)))
+                        },
+                    ))
+                });
+
+                for block_index in overlap_order(block_range, overlap) {
                     let block = &self.column.blocks[block_index];
                     let start = (block.start - self.view_range.start) as f32;
 
-                    for note in block.notes.iter().rev() {
+                    for note_index in overlap_order(0..block.notes.len(), overlap) {
+                        let note = &block.notes[note_index];
                         yield DisplacedMIDINote {
                             start,
                             len: note.len,
-                            color: colors[note.track_chan.as_usize()],
+                            color: blended
+                                .as_mut()
+                                .and_then(|colors| colors.pop())
+                                .unwrap_or_else(|| note_color(note)),
+                            position: (block_index as u64, note_index as u64),
+                            velocity: note.velocity,
+                            track: note.track_chan.track(),
                         };
                     }
                 }
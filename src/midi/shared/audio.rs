@@ -6,6 +6,8 @@ use midi_toolkit::{
     sequence::event::{Delta, EventBatch, Track},
 };
 
+use crate::midi::{channel_filtered, transposed_key};
+
 pub struct CompressedAudio {
     pub time: f64,
     data: Vec<u8>,
@@ -26,6 +28,8 @@ impl CompressedAudio {
         E: MIDIEventEnum,
     >(
         iter: Iter,
+        channel_filter: [bool; 16],
+        transpose: [i8; 16],
     ) -> impl Iterator<Item = CompressedAudio> {
         let mut builder_vec: Vec<u8> = Vec::new();
         let mut control_builder_vec: Vec<u8> = Vec::new();
@@ -43,20 +47,32 @@ impl CompressedAudio {
                     builder_vec.clear();
 
                     for event in block.iter_events() {
+                        if channel_filtered(event.as_event(), &channel_filter) {
+                            continue;
+                        }
                         match event.as_event() {
                             Event::NoteOn(e) => {
+                                let Some(key) = transposed_key(e.key, e.channel, &transpose) else {
+                                    continue;
+                                };
                                 let head = EV_ON | e.channel;
-                                let events = &[head, e.key, e.velocity];
+                                let events = &[head, key, e.velocity];
                                 builder_vec.extend_from_slice(events);
                             }
                             Event::NoteOff(e) => {
+                                let Some(key) = transposed_key(e.key, e.channel, &transpose) else {
+                                    continue;
+                                };
                                 let head = EV_OFF | e.channel;
-                                let events = &[head, e.key];
+                                let events = &[head, key];
                                 builder_vec.extend_from_slice(events);
                             }
                             Event::PolyphonicKeyPressure(e) => {
+                                let Some(key) = transposed_key(e.key, e.channel, &transpose) else {
+                                    continue;
+                                };
                                 let head = EV_POLYPHONIC | e.channel;
-                                let events = &[head, e.key, e.velocity];
+                                let events = &[head, key, e.velocity];
                                 builder_vec.extend_from_slice(events);
                             }
                             Event::ControlChange(e) => {
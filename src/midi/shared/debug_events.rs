@@ -0,0 +1,132 @@
+use midi_toolkit::{
+    events::{Event, MIDIEventEnum},
+    io::MIDIFile as TKMIDIFile,
+    pipe,
+    sequence::{unwrap_items, TimeCaster},
+};
+
+use crate::{
+    error::WasabiError,
+    midi::{
+        channel_filtered, open_file_and_signature, shared::tempo_map::DEFAULT_TEMPO,
+        MIDIFileUniqueSignature,
+    },
+};
+
+/// An event of a MIDI, as it is stored in the file
+#[derive(Debug, Clone)]
+pub struct DebugEvent {
+    /// The time of the event in seconds
+    pub time: f64,
+    pub tick: u64,
+    pub track: u32,
+    pub description: String,
+    /// Whether the event was skipped when loading, see
+    /// [`crate::settings::MidiSettings::channel_filter`]
+    pub skipped: bool,
+}
+
+fn describe(event: &Event) -> String {
+    match event {
+        Event::NoteOn(e) => format!(
+            "Note On (channel {}, key {}, velocity {})",
+            e.channel + 1,
+            e.key,
+            e.velocity
+        ),
+        Event::NoteOff(e) => format!("Note Off (channel {}, key {})", e.channel + 1, e.key),
+        Event::PolyphonicKeyPressure(e) => format!(
+            "Key Pressure (channel {}, key {}, pressure {})",
+            e.channel + 1,
+            e.key,
+            e.velocity
+        ),
+        Event::ControlChange(e) => format!(
+            "Control Change (channel {}, controller {}, value {})",
+            e.channel + 1,
+            e.controller,
+            e.value
+        ),
+        Event::ProgramChange(e) => format!(
+            "Program Change (channel {}, program {})",
+            e.channel + 1,
+            e.program + 1
+        ),
+        Event::ChannelPressure(e) => format!(
+            "Channel Pressure (channel {}, pressure {})",
+            e.channel + 1,
+            e.pressure
+        ),
+        Event::PitchWheelChange(e) => {
+            format!("Pitch Bend (channel {}, pitch {})", e.channel + 1, e.pitch)
+        }
+        Event::Tempo(e) => format!("Tempo ({:.2} BPM)", 60_000_000.0 / e.tempo.max(1) as f64),
+        Event::TimeSignature(e) => format!(
+            "Time Signature ({}/{})",
+            e.numerator,
+            1u32 << e.denominator.min(31)
+        ),
+        _ => "Other".to_string(),
+    }
+}
+
+/// Reads the events between `start` and `end` (in seconds) again from the
+/// file of the signature. None of the loaders keep every event around, so
+/// this works the same way for all of them. Only the file is read until
+/// `end`, but that can still take a while for large MIDIs.
+pub fn read_debug_events(
+    signature: &MIDIFileUniqueSignature,
+    start: f64,
+    end: f64,
+) -> Result<Vec<DebugEvent>, WasabiError> {
+    let (file, _) = open_file_and_signature(
+        signature.filepath.clone(),
+        signature.ppq_override,
+        signature.channel_filter,
+        signature.transpose,
+    )?;
+    let midi = TKMIDIFile::open_from_stream(file, None).map_err(WasabiError::MidiLoadError)?;
+
+    let ppq = signature
+        .ppq_override
+        .filter(|ppq| *ppq > 0)
+        .unwrap_or(midi.ppq()) as f64;
+
+    let merged = pipe!(
+        midi.iter_all_track_events_merged_batches()
+        |>TimeCaster::<f64>::cast_event_delta()
+        |>unwrap_items()
+    );
+
+    let mut tempo = DEFAULT_TEMPO;
+    let mut tick = 0.0;
+    let mut time = 0.0;
+    let mut events = Vec::new();
+
+    for batch in merged {
+        tick += batch.delta;
+        time += batch.delta * tempo as f64 / 1_000_000.0 / ppq;
+        if time > end {
+            break;
+        }
+
+        for event in batch.iter_events() {
+            // A tempo change only affects the deltas that come after it
+            if let Event::Tempo(e) = event.as_event() {
+                tempo = e.tempo;
+            }
+
+            if time >= start {
+                events.push(DebugEvent {
+                    time,
+                    tick: tick as u64,
+                    track: event.track,
+                    description: describe(event.as_event()),
+                    skipped: channel_filtered(event.as_event(), &signature.channel_filter),
+                });
+            }
+        }
+    }
+
+    Ok(events)
+}
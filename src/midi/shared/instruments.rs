@@ -0,0 +1,215 @@
+use std::collections::BTreeSet;
+
+use midi_toolkit::events::Event;
+
+use crate::midi::DRUM_CHANNEL;
+
+const GM_INSTRUMENTS: [&str; 128] = [
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavinet",
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bagpipe",
+    "Fiddle",
+    "Shanai",
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+/// An instrument that plays notes in a MIDI
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instrument {
+    pub channel: u8,
+    /// The bank, combined from the MSB and LSB bank select controllers
+    pub bank: u16,
+    pub program: u8,
+}
+
+impl Instrument {
+    /// The GM name of the instrument. Instruments of other banks can't be
+    /// named, so their raw bank and program numbers are shown instead.
+    pub fn name(&self) -> String {
+        if self.channel == DRUM_CHANNEL {
+            format!("Drum Kit {}", self.program + 1)
+        } else if self.bank == 0 {
+            GM_INSTRUMENTS[self.program as usize & 0x7F].to_string()
+        } else {
+            format!("Bank {}, Program {}", self.bank, self.program + 1)
+        }
+    }
+}
+
+/// Collects the instruments that play at least one note while a MIDI gets
+/// parsed, by following the program change and bank select events
+pub struct InstrumentsBuilder {
+    current: [Instrument; 16],
+    recorded: [bool; 16],
+    instruments: BTreeSet<Instrument>,
+}
+
+impl InstrumentsBuilder {
+    pub fn new() -> Self {
+        Self {
+            current: std::array::from_fn(|channel| Instrument {
+                channel: channel as u8,
+                bank: 0,
+                program: 0,
+            }),
+            recorded: [false; 16],
+            instruments: BTreeSet::new(),
+        }
+    }
+
+    pub fn push_event(&mut self, event: &Event) {
+        match event {
+            Event::NoteOn(e) => {
+                let channel = e.channel as usize & 0xF;
+                if !self.recorded[channel] {
+                    self.recorded[channel] = true;
+                    self.instruments.insert(self.current[channel].clone());
+                }
+            }
+            Event::ProgramChange(e) => {
+                let channel = e.channel as usize & 0xF;
+                self.current[channel].program = e.program;
+                self.recorded[channel] = false;
+            }
+            Event::ControlChange(e) => {
+                let channel = e.channel as usize & 0xF;
+                let bank = &mut self.current[channel].bank;
+                // Bank select only applies with the next program change,
+                // so the recorded flag stays the same
+                match e.controller {
+                    0 => *bank = (*bank & 0x7F) | ((e.value as u16) << 7),
+                    32 => *bank = (*bank & !0x7F) | e.value as u16,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The instruments, sorted by channel
+    pub fn build(self) -> Vec<Instrument> {
+        self.instruments.into_iter().collect()
+    }
+}
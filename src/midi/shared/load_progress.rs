@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use atomic_float::AtomicF64;
+
+/// The progress of a MIDI that is loading on another thread, shared between
+/// the loader and whoever is waiting for it
+#[derive(Default)]
+pub struct LoadProgress {
+    parsed_seconds: AtomicF64,
+    read_bytes: AtomicU64,
+    total_bytes: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl LoadProgress {
+    /// How far into the MIDI the events have been read, in seconds
+    pub fn parsed_seconds(&self) -> f64 {
+        self.parsed_seconds.load(Ordering::Relaxed)
+    }
+
+    pub fn set_parsed_seconds(&self, seconds: f64) {
+        self.parsed_seconds.store(seconds, Ordering::Relaxed);
+    }
+
+    /// The length of a MIDI isn't known before all of its events have been
+    /// read, so the progress is measured in the bytes read from the file
+    pub fn set_read_bytes(&self, read: u64, total: u64) {
+        self.read_bytes.store(read, Ordering::Relaxed);
+        self.total_bytes.store(total, Ordering::Relaxed);
+    }
+
+    /// How much of the file has been read, from 0 to 1, once the loader
+    /// has reported it
+    pub fn fraction(&self) -> Option<f32> {
+        let total = self.total_bytes.load(Ordering::Relaxed);
+        let read = self.read_bytes.load(Ordering::Relaxed);
+        (total > 0).then(|| (read as f64 / total as f64).min(1.0) as f32)
+    }
+
+    /// Asks the loader to stop. It stops at the next batch of events, so
+    /// the result can still arrive a bit later.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
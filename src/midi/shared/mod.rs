@@ -1,3 +1,11 @@
 pub mod audio;
+pub mod debug_events;
+pub mod instruments;
+pub mod load_progress;
+pub mod parse_report;
+pub mod stream;
+pub mod tempo_map;
+#[cfg(test)]
+pub mod test_midi;
 pub mod timer;
 pub mod track_channel;
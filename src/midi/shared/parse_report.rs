@@ -0,0 +1,83 @@
+use midi_toolkit::events::Event;
+
+/// The unusual things about a MIDI that the loader had to deal with, see
+/// [`crate::midi::MIDIFileBase::parse_report`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseReport {
+    /// The header has an SMPTE time division instead of ticks per quarter
+    /// note. It still gets read as ticks per quarter note.
+    pub smpte_division: bool,
+    /// Note ons with a velocity of 0, which get loaded as note offs
+    pub zero_velocity_note_ons: u64,
+    /// Note ons of a key that was already playing on the same track and
+    /// channel
+    pub overlapping_notes: u64,
+    /// Note offs without a playing note, which get ignored
+    pub unmatched_note_offs: u64,
+    /// Notes that were still playing at the end of the MIDI, which get
+    /// ended there
+    pub unended_notes: u64,
+}
+
+impl ParseReport {
+    pub fn pairing(&self) -> NotePairing {
+        NotePairing {
+            overlapping_notes: self.overlapping_notes,
+            unmatched_note_offs: self.unmatched_note_offs,
+            unended_notes: self.unended_notes,
+        }
+    }
+}
+
+/// How the note ons and note offs matched up. The loaders count these
+/// while pairing the notes of each key, so they aren't tracked twice.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NotePairing {
+    pub overlapping_notes: u64,
+    pub unmatched_note_offs: u64,
+    pub unended_notes: u64,
+}
+
+impl NotePairing {
+    pub fn merge(self, other: NotePairing) -> NotePairing {
+        NotePairing {
+            overlapping_notes: self.overlapping_notes + other.overlapping_notes,
+            unmatched_note_offs: self.unmatched_note_offs + other.unmatched_note_offs,
+            unended_notes: self.unended_notes + other.unended_notes,
+        }
+    }
+}
+
+/// Collects a [`ParseReport`] from the events in the order they get loaded
+pub struct ParseReportBuilder {
+    report: ParseReport,
+}
+
+impl ParseReportBuilder {
+    pub fn new(file_ppq: u16) -> Self {
+        Self {
+            report: ParseReport {
+                // The top bit of the division marks SMPTE timing
+                smpte_division: file_ppq & 0x8000 != 0,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn push_event(&mut self, event: &Event) {
+        if let Event::NoteOn(e) = event {
+            if e.velocity == 0 {
+                self.report.zero_velocity_note_ons += 1;
+            }
+        }
+    }
+
+    pub fn build(self, pairing: NotePairing) -> ParseReport {
+        ParseReport {
+            overlapping_notes: pairing.overlapping_notes,
+            unmatched_note_offs: pairing.unmatched_note_offs,
+            unended_notes: pairing.unended_notes,
+            ..self.report
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::error::WasabiError;
+
+enum Source {
+    File(File),
+    Memory(Cursor<Arc<[u8]>>),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::File(file) => file.read(buf),
+            Source::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::File(file) => file.seek(pos),
+            Source::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// A file stream that only exposes the Standard MIDI File data of a file.
+///
+/// For plain MIDI files this is the whole file, while for RMI files (RIFF
+/// containers) it's the contents of the `data` chunk. Any other chunks,
+/// like embedded DLS banks, are ignored.
+pub struct MIDIStream {
+    file: Source,
+    start: u64,
+    len: u64,
+    pos: u64,
+    /// Counts the bytes read, see [`MIDIStream::count_read_bytes`]
+    read_bytes: Option<Arc<AtomicU64>>,
+}
+
+impl MIDIStream {
+    pub fn new(file: File) -> Result<Self, WasabiError> {
+        let file_len = file.metadata().map_err(WasabiError::FilesystemError)?.len();
+        Self::from_source(Source::File(file), file_len)
+    }
+
+    /// Creates a stream over a MIDI that was already read into memory,
+    /// like one piped through the standard input.
+    pub fn from_memory(data: Arc<[u8]>) -> Result<Self, WasabiError> {
+        let len = data.len() as u64;
+        Self::from_source(Source::Memory(Cursor::new(data)), len)
+    }
+
+    fn from_source(mut file: Source, file_len: u64) -> Result<Self, WasabiError> {
+        let mut header = [0u8; 12];
+        let is_rmi = file.read_exact(&mut header).is_ok()
+            && &header[0..4] == b"RIFF"
+            && &header[8..12] == b"RMID";
+
+        let (start, len) = if is_rmi {
+            Self::find_data_chunk(&mut file, &header, file_len)?
+        } else {
+            (0, file_len)
+        };
+
+        file.seek(SeekFrom::Start(start))
+            .map_err(WasabiError::FilesystemError)?;
+
+        Ok(Self {
+            file,
+            start,
+            len,
+            pos: 0,
+            read_bytes: None,
+        })
+    }
+
+    /// The length of the MIDI data, without the RMI container
+    pub fn data_length(&self) -> u64 {
+        self.len
+    }
+
+    /// Adds every read to the returned counter. The tracks are read as
+    /// their events get parsed, so this tracks the loading progress.
+    pub fn count_read_bytes(&mut self) -> Arc<AtomicU64> {
+        self.read_bytes
+            .get_or_insert_with(|| Arc::new(AtomicU64::new(0)))
+            .clone()
+    }
+
+    fn find_data_chunk(
+        file: &mut Source,
+        header: &[u8; 12],
+        file_len: u64,
+    ) -> Result<(u64, u64), WasabiError> {
+        let riff_len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+        let riff_end = (riff_len + 8).min(file_len);
+
+        let mut offset = 12;
+        while offset + 8 <= riff_end {
+            file.seek(SeekFrom::Start(offset))
+                .map_err(WasabiError::FilesystemError)?;
+
+            let mut chunk = [0u8; 8];
+            file.read_exact(&mut chunk)
+                .map_err(WasabiError::FilesystemError)?;
+            let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+
+            if &chunk[0..4] == b"data" {
+                let data_start = offset + 8;
+                return Ok((data_start, size.min(riff_end - data_start)));
+            }
+
+            // Chunks are padded to an even size
+            offset += 8 + size + (size & 1);
+        }
+
+        Err(WasabiError::MidiFormatError(
+            "The RMI file does not contain any MIDI data".into(),
+        ))
+    }
+}
+
+impl Read for MIDIStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos) as usize;
+        let max = buf.len().min(remaining);
+        let read = self.file.read(&mut buf[..max])?;
+        self.pos += read as u64;
+        if let Some(read_bytes) = &self.read_bytes {
+            read_bytes.fetch_add(read as u64, Ordering::Relaxed);
+        }
+        Ok(read)
+    }
+}
+
+impl Seek for MIDIStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.len as i64 + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+
+        if target < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative position",
+            ));
+        }
+
+        self.file
+            .seek(SeekFrom::Start(self.start + target as u64))?;
+        self.pos = target as u64;
+        Ok(self.pos)
+    }
+}
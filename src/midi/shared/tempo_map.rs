@@ -0,0 +1,284 @@
+use std::{io::Write, path::Path, sync::OnceLock};
+
+use midi_toolkit::{
+    events::{Event, MIDIEventEnum},
+    sequence::event::{Delta, EventBatch, Track},
+};
+use serde_derive::Serialize;
+
+use crate::error::WasabiError;
+
+/// The tempo of a MIDI without tempo events, in microseconds per quarter note
+pub const DEFAULT_TEMPO: u32 = 500000;
+
+enum TempoMapEvent {
+    Tempo(u32),
+    TimeSignature { numerator: u8, denominator: u8 },
+}
+
+/// A beat of the MIDI, in seconds
+#[derive(Debug, Clone, Copy)]
+pub struct Beat {
+    pub time: f64,
+    /// Whether the beat is the first one of its bar
+    pub downbeat: bool,
+}
+
+/// A tempo or time signature change, with the tempo and time signature that
+/// apply from there on
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct TempoChange {
+    pub time_seconds: f64,
+    pub tick: u64,
+    pub bpm: f64,
+    pub numerator: u8,
+    pub denominator: u8,
+}
+
+/// Collects the tempo and time signature changes of a MIDI while it's being
+/// parsed. The events have to be pushed before the tempo events get cancelled,
+/// because the times are needed in ticks.
+pub struct TempoMapBuilder {
+    ticks: f64,
+    events: Vec<(f64, TempoMapEvent)>,
+}
+
+impl TempoMapBuilder {
+    pub fn new() -> Self {
+        Self {
+            ticks: 0.0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn push_batch(&mut self, batch: &Delta<f64, Track<EventBatch<Event>>>) {
+        self.ticks += batch.delta;
+
+        for event in batch.iter_events() {
+            match event.as_event() {
+                Event::Tempo(e) => self
+                    .events
+                    .push((self.ticks, TempoMapEvent::Tempo(e.tempo))),
+                Event::TimeSignature(e) => self.events.push((
+                    self.ticks,
+                    TempoMapEvent::TimeSignature {
+                        numerator: e.numerator,
+                        denominator: e.denominator,
+                    },
+                )),
+                _ => {}
+            }
+        }
+    }
+
+    /// Computes the tempo and time signature changes. The beats are only
+    /// computed once needed, see [`TempoMap::beats`].
+    pub fn build(self, ppq: u16) -> TempoMap {
+        let ppq = ppq as f64;
+
+        let mut tempo = DEFAULT_TEMPO;
+        let mut numerator = 4;
+        let mut denominator = 4;
+        let mut tick = 0.0;
+        let mut seconds = 0.0;
+
+        let mut changes = vec![TempoChange::new(0.0, 0.0, tempo, numerator, denominator)];
+        for (time, event) in self.events.iter() {
+            advance(&mut seconds, &mut tick, *time, tempo, ppq);
+            match *event {
+                TempoMapEvent::Tempo(t) => tempo = t,
+                TempoMapEvent::TimeSignature {
+                    numerator: num,
+                    denominator: denom,
+                } => {
+                    numerator = num.max(1);
+                    denominator = 1 << denom.min(6);
+                }
+            }
+
+            // Changes on the same tick get merged into one
+            let change = TempoChange::new(seconds, tick, tempo, numerator, denominator);
+            match changes.last_mut() {
+                Some(last) if last.tick == change.tick => *last = change,
+                _ => changes.push(change),
+            }
+        }
+
+        TempoMap {
+            events: self.events,
+            ticks: self.ticks,
+            ppq,
+            changes,
+            beats: OnceLock::new(),
+        }
+    }
+}
+
+impl TempoChange {
+    fn new(seconds: f64, tick: f64, tempo: u32, numerator: u8, denominator: u8) -> Self {
+        TempoChange {
+            time_seconds: seconds,
+            tick: tick as u64,
+            bpm: 60_000_000.0 / tempo.max(1) as f64,
+            numerator,
+            denominator,
+        }
+    }
+}
+
+fn advance(seconds: &mut f64, tick: &mut f64, to: f64, tempo: u32, ppq: f64) {
+    *seconds += (to - *tick) * tempo as f64 / 1_000_000.0 / ppq;
+    *tick = to;
+}
+
+/// The tempo map of a MIDI, computed from its tempo and time signature events
+pub struct TempoMap {
+    events: Vec<(f64, TempoMapEvent)>,
+    /// The length of the MIDI in ticks
+    ticks: f64,
+    ppq: f64,
+    changes: Vec<TempoChange>,
+    beats: OnceLock<Vec<Beat>>,
+}
+
+impl TempoMap {
+    /// The beats until the end of the MIDI, computed on the first call since
+    /// only the metronome and the bar grid need them
+    pub fn beats(&self) -> &[Beat] {
+        self.beats.get_or_init(|| self.compute_beats())
+    }
+
+    /// The tempo and time signature changes, starting with the ones at tick 0
+    pub fn changes(&self) -> &[TempoChange] {
+        &self.changes
+    }
+
+    /// A time signature change always starts a new bar
+    fn compute_beats(&self) -> Vec<Beat> {
+        let ppq = self.ppq;
+
+        let mut tempo = DEFAULT_TEMPO;
+        let mut numerator = 4;
+        let mut beat_ticks = ppq;
+
+        let mut tick = 0.0;
+        let mut seconds = 0.0;
+        let mut next_beat = 0.0;
+        let mut beat_in_bar = 0;
+
+        let mut beats = Vec::new();
+        let mut events = self.events.iter().peekable();
+
+        while next_beat <= self.ticks {
+            while let Some((time, event)) = events.next_if(|(time, _)| *time <= next_beat) {
+                advance(&mut seconds, &mut tick, *time, tempo, ppq);
+                match *event {
+                    TempoMapEvent::Tempo(t) => tempo = t,
+                    TempoMapEvent::TimeSignature {
+                        numerator: num,
+                        denominator: denom,
+                    } => {
+                        numerator = num.max(1);
+                        // The denominator is stored as a power of two
+                        let denominator = 1u32 << denom.min(6);
+                        beat_ticks = ppq * 4.0 / denominator as f64;
+                        beat_in_bar = 0;
+                        next_beat = *time;
+                    }
+                }
+            }
+
+            advance(&mut seconds, &mut tick, next_beat, tempo, ppq);
+            beats.push(Beat {
+                time: seconds,
+                downbeat: beat_in_bar == 0,
+            });
+
+            beat_in_bar = (beat_in_bar + 1) % numerator;
+            next_beat += beat_ticks;
+        }
+
+        beats
+    }
+}
+
+/// The change that applies at `time` (in seconds), which is the first one
+/// for times before the start
+pub fn tempo_at(changes: &[TempoChange], time: f64) -> Option<&TempoChange> {
+    let index = changes.partition_point(|change| change.time_seconds <= time);
+    changes.get(index.saturating_sub(1))
+}
+
+/// Writes the changes to `path` for editing along with a DAW. Paths ending in
+/// `.json` get a JSON array, all others a CSV file.
+pub fn export_tempo_changes(changes: &[TempoChange], path: &Path) -> Result<(), WasabiError> {
+    let file = std::fs::File::create(path).map_err(WasabiError::FilesystemError)?;
+    let mut writer = std::io::BufWriter::new(file);
+
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    if is_json {
+        serde_json::to_writer_pretty(&mut writer, changes)
+            .map_err(|e| WasabiError::Other(e.to_string()))?;
+    } else {
+        writeln!(writer, "time_seconds,tick,bpm,numerator,denominator")
+            .map_err(WasabiError::FilesystemError)?;
+        for change in changes {
+            writeln!(
+                writer,
+                "{:.6},{},{:.3},{},{}",
+                change.time_seconds, change.tick, change.bpm, change.numerator, change.denominator
+            )
+            .map_err(WasabiError::FilesystemError)?;
+        }
+    }
+
+    writer.flush().map_err(WasabiError::FilesystemError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TempoMapBuilder, TempoMapEvent};
+
+    #[test]
+    fn beats_follow_the_changes() {
+        // Two beats of 4/4 at 120 BPM, then 3/4 at 60 BPM until tick 480
+        let builder = TempoMapBuilder {
+            ticks: 480.0,
+            events: vec![
+                (192.0, TempoMapEvent::Tempo(1_000_000)),
+                (
+                    192.0,
+                    TempoMapEvent::TimeSignature {
+                        numerator: 3,
+                        denominator: 2,
+                    },
+                ),
+            ],
+        };
+        let tempo_map = builder.build(96);
+
+        let changes = tempo_map.changes();
+        assert_eq!(changes.len(), 2);
+        assert_eq!((changes[1].tick, changes[1].time_seconds), (192, 1.0));
+        assert_eq!((changes[1].bpm, changes[1].numerator), (60.0, 3));
+
+        let beats: Vec<_> = tempo_map
+            .beats()
+            .iter()
+            .map(|beat| (beat.time, beat.downbeat))
+            .collect();
+        assert_eq!(
+            beats,
+            [
+                (0.0, true),
+                (0.5, false),
+                (1.0, true),
+                (2.0, false),
+                (3.0, false),
+                (4.0, true)
+            ]
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Small generated MIDI files for the tests
+
+use std::path::PathBuf;
+
+pub const PPQ: u16 = 96;
+/// There are no tempo events, so the default tempo of 120 BPM is used
+pub const TICKS_PER_SECOND: u32 = PPQ as u32 * 2;
+
+/// Writes a single track MIDI from (delta ticks, event) pairs to the temp
+/// directory. `name` has to be different for each test, as the tests run
+/// in parallel.
+pub fn write_midi(name: &str, events: &[(u32, [u8; 3])]) -> PathBuf {
+    write_midi_claiming_tracks(name, 1, events)
+}
+
+/// Like [`write_midi`], but the header claims `track_count` tracks, while
+/// the file still contains a single one
+pub fn write_midi_claiming_tracks(
+    name: &str,
+    track_count: u16,
+    events: &[(u32, [u8; 3])],
+) -> PathBuf {
+    let mut track = Vec::new();
+    for (delta, event) in events {
+        write_variable_length(&mut track, *delta);
+        track.extend_from_slice(event);
+    }
+    // End of track
+    track.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+    let mut file = Vec::new();
+    file.extend_from_slice(b"MThd");
+    file.extend_from_slice(&6u32.to_be_bytes());
+    // Format 1
+    file.extend_from_slice(&1u16.to_be_bytes());
+    file.extend_from_slice(&track_count.to_be_bytes());
+    file.extend_from_slice(&PPQ.to_be_bytes());
+    file.extend_from_slice(b"MTrk");
+    file.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    file.extend_from_slice(&track);
+
+    let path = std::env::temp_dir().join(format!("wasabi_test_{name}_{}.mid", std::process::id()));
+    std::fs::write(&path, file).unwrap();
+    path
+}
+
+/// `count` notes on key 60 one after another, starting at 0, every
+/// `spacing` ticks and `length` ticks long
+pub fn note_sequence(count: u32, spacing: u32, length: u32) -> Vec<(u32, [u8; 3])> {
+    let mut events = Vec::new();
+    for i in 0..count {
+        let delta = if i == 0 { 0 } else { spacing - length };
+        events.push((delta, [0x90, 60, 100]));
+        events.push((length, [0x80, 60, 0]));
+    }
+    events
+}
+
+fn write_variable_length(out: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
@@ -3,6 +3,13 @@
 use std::time::Instant;
 use time::Duration;
 
+use crate::settings::{CatchUp, EaseCurve};
+
+/// How far [`CatchUp::Smooth`] moves the time per frame at most, in frames
+/// of real time. Being relative to the frame time, the notes still keep up
+/// with the clock at low frame rates.
+const MAX_SMOOTH_STEP_FRAMES: f64 = 2.0;
+
 struct NotifySignal {
     new_state: TimerState,
     has_seeked: bool,
@@ -13,6 +20,7 @@ enum TimerState {
     Running {
         continue_time: Instant,
         time_offset: Duration,
+        speed: f64,
     },
     Paused {
         time_offset: Duration,
@@ -25,37 +33,178 @@ impl TimerState {
             TimerState::Running {
                 continue_time,
                 time_offset,
-            } => continue_time.elapsed() + *time_offset,
+                speed,
+            } => {
+                Duration::seconds_f64(continue_time.elapsed().as_secs_f64() * speed) + *time_offset
+            }
             TimerState::Paused { time_offset } => *time_offset,
         }
     }
 
+    /// How fast the time passes compared to the wall clock
+    fn speed(&self) -> f64 {
+        match self {
+            TimerState::Running { speed, .. } => *speed,
+            TimerState::Paused { .. } => 1.0,
+        }
+    }
+
     fn is_paused(&self) -> bool {
         matches!(self, TimerState::Paused { .. })
     }
 }
 
+/// An eased seek that is in progress, see [`TimeKeeper::set_seek_ease`]
+#[derive(Debug, Clone)]
+struct SeekEase {
+    from: Duration,
+    started: Instant,
+    duration: f64,
+    curve: EaseCurve,
+}
+
+/// Keeps track of the playback time of a MIDI.
+///
+/// The time is not advanced in ticks, it is derived from a monotonic clock
+/// ([`Instant`]) every time it is requested.
 #[derive(Debug)]
 pub struct TimeKeeper {
     current_state: TimerState,
     listeners: Vec<crossbeam_channel::Sender<NotifySignal>>,
+    first_played: Option<Instant>,
+    global_offset: Duration,
+    /// See [`TimeKeeper::start_time`]
+    start_time: Duration,
+    end_clamp: Option<Duration>,
+    speed: f64,
+    seek_ease: Option<(f64, EaseCurve)>,
+    current_ease: Option<SeekEase>,
+    catchup: CatchUp,
+    /// The time of the current frame with [`CatchUp::Smooth`]
+    frame_time: Option<Duration>,
 }
 
 impl TimeKeeper {
-    pub fn new(start_delay: f64) -> Self {
-        let start_delay = Duration::seconds_f64(start_delay);
+    /// Creates a paused timer.
+    ///
+    /// A positive `global_offset` makes the whole MIDI (audio and visuals)
+    /// play later, a negative one makes it play earlier, which can start it
+    /// past its beginning. The listeners follow the same shifted time, and
+    /// [`TimeKeeper::position`] adds the offset back, so the displayed
+    /// position always starts at `-start_delay`.
+    pub fn new(start_delay: f64, global_offset: Duration) -> Self {
+        let start_time = -Duration::seconds_f64(start_delay) - global_offset;
         Self {
             current_state: TimerState::Paused {
-                time_offset: -start_delay,
+                time_offset: start_time,
             },
             listeners: Vec::new(),
+            first_played: None,
+            global_offset,
+            start_time,
+            end_clamp: None,
+            speed: 1.0,
+            seek_ease: None,
+            current_ease: None,
+            catchup: CatchUp::RealTime,
+            frame_time: None,
         }
     }
 
+    /// Returns the current playback time, computed from the wall clock at the
+    /// moment of the call. While running, consecutive calls never go backwards
+    /// unless the timer gets seeked.
+    ///
+    /// Renderers should sample this once per frame and use it directly, instead
+    /// of rounding it to MIDI ticks, so that motion stays smooth at any refresh rate.
     pub fn get_time(&self) -> Duration {
+        let mut time = self
+            .frame_time
+            .unwrap_or_else(|| self.current_state.get_time());
+        if let Some(ease) = self.current_ease.as_ref() {
+            let progress = ease.started.elapsed().as_secs_f64() / ease.duration;
+            if progress < 1.0 {
+                time = ease.from + (time - ease.from) * ease.curve.apply(progress);
+            }
+        }
+        match self.end_clamp {
+            Some(end) => time.min(end),
+            None => time,
+        }
+    }
+
+    /// Returns the playback time without the clamp of
+    /// [`TimeKeeper::set_end_clamp`] or the seek ease, so it keeps going
+    /// past the end. This is the time that the audio follows.
+    pub fn unclamped_time(&self) -> Duration {
         self.current_state.get_time()
     }
 
+    /// Makes [`TimeKeeper::get_time`] move to the new time over `duration`
+    /// seconds when seeking, instead of jumping there. The listeners still
+    /// jump right away, so the audio doesn't glitch.
+    pub fn set_seek_ease(&mut self, ease: Option<(f64, EaseCurve)>) {
+        self.seek_ease = ease.filter(|(duration, _)| *duration > 0.0);
+        if self.seek_ease.is_none() {
+            self.current_ease = None;
+        }
+    }
+
+    /// Sets how [`TimeKeeper::get_time`] follows the clock when frames take
+    /// too long. The listeners always follow the clock, so with
+    /// [`CatchUp::Smooth`] the audio can be ahead of the frames for a moment.
+    pub fn set_catchup(&mut self, catchup: CatchUp) {
+        self.catchup = catchup;
+        if catchup == CatchUp::RealTime {
+            self.frame_time = None;
+        }
+    }
+
+    /// Moves the time of [`CatchUp::Smooth`] towards the clock, by at most
+    /// [`MAX_SMOOTH_STEP_FRAMES`] times `frame_dt`, the usual time of a frame
+    /// in seconds. Has to be called once at the start of each frame.
+    pub fn next_frame(&mut self, frame_dt: f64) {
+        if self.catchup != CatchUp::Smooth || self.current_state.is_paused() {
+            self.frame_time = None;
+            return;
+        }
+
+        let now = self.current_state.get_time();
+        let max_step = Duration::seconds_f64(MAX_SMOOTH_STEP_FRAMES * frame_dt * self.speed);
+        self.frame_time = Some(match self.frame_time {
+            Some(previous) if now > previous => now.min(previous + max_step),
+            _ => now,
+        });
+    }
+
+    /// Makes [`TimeKeeper::get_time`] stop at `end`, so the last frame of a
+    /// MIDI stays on screen while the timer keeps running. The listeners
+    /// aren't clamped, but there are no events left to play past the end.
+    pub fn set_end_clamp(&mut self, end: Option<Duration>) {
+        self.end_clamp = end;
+    }
+
+    /// Returns the playback position that should be displayed, which is the
+    /// MIDI time shifted by the global offset
+    pub fn position(&self) -> Duration {
+        self.get_time() + self.global_offset
+    }
+
+    /// The MIDI time the timer starts at: the start delay before the MIDI,
+    /// shifted by the global offset. Seeking back to the start should go
+    /// here, so the offset stays the same on every playthrough.
+    pub fn start_time(&self) -> Duration {
+        self.start_time
+    }
+
+    /// Returns the real time that has passed since the timer was first started,
+    /// regardless of any pauses or seeks. Useful for diagnosing timing drift.
+    pub fn wall_elapsed(&self) -> Duration {
+        self.first_played
+            .map(|start| Duration::try_from(start.elapsed()).unwrap_or(Duration::ZERO))
+            .unwrap_or(Duration::ZERO)
+    }
+
     pub fn is_paused(&self) -> bool {
         self.current_state.is_paused()
     }
@@ -90,12 +239,15 @@ impl TimeKeeper {
     }
 
     pub fn toggle_pause(&mut self) {
-        let now = self.get_time();
+        let now = self.current_state.get_time();
         match self.current_state {
             TimerState::Paused { .. } => {
+                let continue_time = Instant::now();
+                self.first_played.get_or_insert(continue_time);
                 self.current_state = TimerState::Running {
-                    continue_time: Instant::now(),
+                    continue_time,
                     time_offset: now,
+                    speed: self.speed,
                 };
             }
             TimerState::Running { .. } => {
@@ -107,31 +259,75 @@ impl TimeKeeper {
     }
 
     pub fn pause(&mut self) {
-        let now = self.get_time();
+        let now = self.current_state.get_time();
         self.current_state = TimerState::Paused { time_offset: now };
         self.notify_listeners(false);
     }
 
     pub fn play(&mut self) {
-        let now = self.get_time();
+        let now = self.current_state.get_time();
+        let continue_time = Instant::now();
+        self.first_played.get_or_insert(continue_time);
         self.current_state = TimerState::Running {
-            continue_time: Instant::now(),
+            continue_time,
             time_offset: now,
+            speed: self.speed,
         };
         self.notify_listeners(false);
     }
 
     pub fn seek(&mut self, time: Duration) {
+        if let Some((duration, curve)) = self.seek_ease {
+            self.current_ease = Some(SeekEase {
+                from: self.get_time(),
+                started: Instant::now(),
+                duration,
+                curve,
+            });
+        }
+        self.frame_time = None;
+
         if self.current_state.is_paused() {
             self.current_state = TimerState::Paused { time_offset: time };
         } else {
             self.current_state = TimerState::Running {
                 continue_time: Instant::now(),
                 time_offset: time,
+                speed: self.speed,
             };
         }
         self.notify_listeners(true);
     }
+
+    /// How fast the MIDI plays, where 1.0 is the normal speed
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Changes how fast the MIDI plays from the current time on, without
+    /// seeking. The listeners wait accordingly, so the audio follows.
+    pub fn set_speed(&mut self, speed: f64) {
+        let now = self.current_state.get_time();
+        self.speed = speed.max(0.01);
+        if !self.current_state.is_paused() {
+            self.current_state = TimerState::Running {
+                continue_time: Instant::now(),
+                time_offset: now,
+                speed: self.speed,
+            };
+            self.notify_listeners(false);
+        }
+    }
+
+    /// Pauses the timer at exactly the given time. Unlike [`TimeKeeper::seek`]
+    /// on a running timer, the time stays fixed until it is set again, which
+    /// allows stepping through a MIDI deterministically (e.g. for frame capture).
+    pub fn set_time(&mut self, time: Duration) {
+        self.current_ease = None;
+        self.frame_time = None;
+        self.current_state = TimerState::Paused { time_offset: time };
+        self.notify_listeners(true);
+    }
 }
 
 pub struct TimeListener {
@@ -165,32 +361,38 @@ impl TimeListener {
         self.current.is_paused()
     }
 
+    /// Waits until `time` is reached. The wait ends at exactly `time`, so
+    /// events get sent at the same moment their notes reach the keyboard
+    /// (see [`crate::midi::note_started`]).
     pub fn wait_until(&mut self, time: Duration) -> WaitResult {
-        let curr_time = self.current.get_time();
-        if curr_time >= time {
-            return WaitResult::Ok;
-        }
-
-        // TODO: Maybe find a more reliable way to wait while still reading?
-        let result = self
-            .reciever
-            .recv_timeout((time - curr_time).unsigned_abs());
-
-        match result {
-            Ok(signal) => {
-                self.current = signal.new_state;
-                if signal.has_seeked {
-                    WaitResult::Seeked(self.current.get_time())
-                } else if self.current.is_paused() {
-                    WaitResult::Paused
-                } else {
-                    WaitResult::Ok
+        loop {
+            let curr_time = self.current.get_time();
+            if curr_time >= time {
+                return WaitResult::Ok;
+            }
+
+            // TODO: Maybe find a more reliable way to wait while still reading?
+            let result = self.reciever.recv_timeout(
+                (time - curr_time)
+                    .unsigned_abs()
+                    .div_f64(self.current.speed()),
+            );
+
+            match result {
+                Ok(signal) => {
+                    self.current = signal.new_state;
+                    if signal.has_seeked {
+                        return WaitResult::Seeked(self.current.get_time());
+                    } else if self.current.is_paused() {
+                        return WaitResult::Paused;
+                    }
+                    // The speed changed, so the wait gets computed again
                 }
+                Err(error) => match error {
+                    crossbeam_channel::RecvTimeoutError::Timeout => return WaitResult::Ok,
+                    crossbeam_channel::RecvTimeoutError::Disconnected => return WaitResult::Killed,
+                },
             }
-            Err(error) => match error {
-                crossbeam_channel::RecvTimeoutError::Timeout => WaitResult::Ok,
-                crossbeam_channel::RecvTimeoutError::Disconnected => WaitResult::Killed,
-            },
         }
     }
 
@@ -249,3 +451,59 @@ impl TimeListener {
         self.current.get_time()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use time::Duration;
+
+    use crate::settings::CatchUp;
+
+    use super::TimeKeeper;
+
+    #[test]
+    fn running_time_is_monotonic() {
+        let mut timer = TimeKeeper::new(0.0, Duration::ZERO);
+        timer.play();
+
+        // Far more samples than a frame would take
+        let mut previous = timer.get_time();
+        for _ in 0..10_000 {
+            let time = timer.get_time();
+            assert!(time >= previous, "{time} came after {previous}");
+            previous = time;
+        }
+
+        thread::sleep(std::time::Duration::from_millis(2));
+        assert!(timer.get_time() > previous);
+    }
+
+    #[test]
+    fn smooth_time_keeps_up_at_low_frame_rates() {
+        let mut timer = TimeKeeper::new(0.0, Duration::ZERO);
+        timer.set_catchup(CatchUp::Smooth);
+        timer.play();
+        timer.next_frame(0.05);
+
+        // A frame at 20 FPS doesn't fall behind the clock
+        thread::sleep(std::time::Duration::from_millis(50));
+        timer.next_frame(0.05);
+        assert!(timer.get_time() >= Duration::milliseconds(50));
+
+        // A slow frame at 100 FPS only moves two frames further
+        let previous = timer.get_time();
+        thread::sleep(std::time::Duration::from_millis(50));
+        timer.next_frame(0.01);
+        assert!(timer.get_time() <= previous + Duration::milliseconds(20));
+    }
+
+    #[test]
+    fn paused_time_stays() {
+        let mut timer = TimeKeeper::new(1.0, Duration::ZERO);
+        let start = timer.get_time();
+        thread::sleep(std::time::Duration::from_millis(2));
+
+        assert_eq!(timer.get_time(), start);
+        assert_eq!(start, Duration::seconds(-1));
+    }
+}
@@ -3,9 +3,14 @@
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct TrackAndChannel(u32);
 
+/// The highest track that can be told apart, the later ones share its value
+pub const MAX_TRACK: u32 = u32::MAX / 16;
+
 impl TrackAndChannel {
+    /// Tracks past [`MAX_TRACK`] are stored as [`MAX_TRACK`], instead of
+    /// overflowing into the other tracks
     pub fn new(track: u32, channel: u8) -> Self {
-        TrackAndChannel(track * 16 + channel as u32)
+        TrackAndChannel(track.min(MAX_TRACK) * 16 + (channel & 0x0F) as u32)
     }
 
     pub fn track(&self) -> u32 {
@@ -24,3 +29,24 @@ impl TrackAndChannel {
         self.0 as usize
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{TrackAndChannel, MAX_TRACK};
+
+    #[test]
+    fn track_and_channel_round_trip() {
+        for (track, channel) in [(0, 0), (3, 9), (65535, 15), (MAX_TRACK, 15)] {
+            let track_chan = TrackAndChannel::new(track, channel);
+            assert_eq!((track_chan.track(), track_chan.channel()), (track, channel));
+        }
+    }
+
+    #[test]
+    fn huge_tracks_dont_overflow() {
+        let track_chan = TrackAndChannel::new(u32::MAX, 15);
+        assert_eq!(track_chan.track(), MAX_TRACK);
+        assert_eq!(track_chan.channel(), 15);
+        assert_eq!(track_chan.as_u32(), u32::MAX);
+    }
+}
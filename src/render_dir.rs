@@ -0,0 +1,105 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
+};
+
+use xsynth_core::soundfont::SoundfontBase;
+
+use crate::{
+    error::WasabiError,
+    midi::{load_soundfonts, read_audio_blocks, OfflineRenderer},
+    settings::WasabiSettings,
+    utils,
+};
+
+const USAGE: &str = "Usage: wasabi render-dir <folder> --out <folder> [--jobs N]";
+
+/// Renders every MIDI of a folder to a WAV file of the same name, using
+/// the XSynth settings and the soundfonts of the config. Files that fail
+/// are reported and skipped. Returns the exit code of the program.
+pub fn run(args: &[String]) -> i32 {
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    let (Some(input), Some(output)) = (args.first(), arg_value("--out")) else {
+        eprintln!("{USAGE}");
+        return 2;
+    };
+    let jobs = arg_value("--jobs")
+        .and_then(|jobs| jobs.parse::<usize>().ok())
+        .filter(|jobs| *jobs > 0)
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+
+    match render_dir(Path::new(input), Path::new(output), jobs) {
+        Ok(0) => 0,
+        Ok(_) => 1,
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+/// Returns the number of files that failed
+fn render_dir(input: &Path, output: &Path, jobs: usize) -> Result<usize, WasabiError> {
+    let settings = WasabiSettings::new_or_load()?;
+    let midis = utils::list_midis(input).map_err(WasabiError::FilesystemError)?;
+    std::fs::create_dir_all(output).map_err(WasabiError::FilesystemError)?;
+
+    // Loaded once, every file gets its own synth but shares the samples
+    let soundfonts = load_soundfonts(&settings.synth)?;
+
+    let total = midis.len();
+    let queue = Mutex::new(midis.into_iter().enumerate());
+    let failures = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(total) {
+            scope.spawn(|| loop {
+                let Some((i, midi)) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let name = midi.file_name().unwrap_or_default().to_string_lossy();
+                let wav = output.join(midi.with_extension("wav").file_name().unwrap_or_default());
+
+                let start = Instant::now();
+                match render_file(&midi, &wav, &settings, &soundfonts) {
+                    Ok(()) => println!(
+                        "[{}/{total}] {name}: done in {:.1}s",
+                        i + 1,
+                        start.elapsed().as_secs_f64()
+                    ),
+                    Err(e) => {
+                        println!("[{}/{total}] {name}: failed: {e}", i + 1);
+                        failures
+                            .lock()
+                            .unwrap()
+                            .push((name.to_string(), e.to_string()));
+                    }
+                }
+            });
+        }
+    });
+
+    let failures = failures.into_inner().unwrap();
+    println!("Rendered {} of {total} files", total - failures.len());
+    for (name, error) in failures.iter() {
+        println!("  {name}: {error}");
+    }
+    Ok(failures.len())
+}
+
+fn render_file(
+    midi: &Path,
+    wav: &Path,
+    settings: &WasabiSettings,
+    soundfonts: &[Arc<dyn SoundfontBase>],
+) -> Result<(), WasabiError> {
+    let blocks = read_audio_blocks(midi, &settings.midi)?;
+    OfflineRenderer::new(&settings.synth, soundfonts.to_vec()).render_to_wav(&blocks, wav, 0.0)
+}
@@ -43,7 +43,9 @@ impl SceneSwapchain {
             let create_info = ImageCreateInfo {
                 format: image_state.format,
                 extent: [size[0], size[1], 1],
-                usage: ImageUsage::SAMPLED | ImageUsage::COLOR_ATTACHMENT,
+                usage: ImageUsage::SAMPLED
+                    | ImageUsage::COLOR_ATTACHMENT
+                    | ImageUsage::TRANSFER_SRC,
                 ..Default::default()
             };
 
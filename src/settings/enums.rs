@@ -10,6 +10,9 @@ pub enum MidiParsing {
     Ram = 0,
     Live = 1,
     Cake = 2,
+    /// Cake for large MIDIs and Standard (RAM) for the rest, see
+    /// [`super::MidiSettings::auto_cake_threshold`]
+    Auto = 3,
 }
 
 impl MidiParsing {
@@ -18,6 +21,7 @@ impl MidiParsing {
             MidiParsing::Ram => "Standard (RAM)",
             MidiParsing::Live => "Standard (Live)",
             MidiParsing::Cake => "Cake",
+            MidiParsing::Auto => "Auto",
         }
     }
 }
@@ -30,8 +34,9 @@ impl FromStr for MidiParsing {
             "ram" => Ok(MidiParsing::Ram),
             "live" => Ok(MidiParsing::Live),
             "cake" => Ok(MidiParsing::Cake),
+            "auto" => Ok(MidiParsing::Auto),
             s => Err(format!(
-                "{} was not expected. Expected one of `ram`, `live` or `cake`",
+                "{} was not expected. Expected one of `ram`, `live`, `cake` or `auto`",
                 s
             )),
         }
@@ -89,6 +94,11 @@ pub enum Statistics {
     VoiceCount = 2,
     Rendered = 3,
     NoteCount = 4,
+    RenderLoad = 5,
+    Nps = 6,
+    Upcoming = 7,
+    Drawn = 8,
+    Remaining = 9,
 }
 
 impl Statistics {
@@ -100,16 +110,26 @@ impl Statistics {
             Statistics::VoiceCount => "Voice Count",
             Statistics::Rendered => "Rendered",
             Statistics::NoteCount => "Note Count",
+            Statistics::RenderLoad => "Render Load",
+            Statistics::Nps => "NPS",
+            Statistics::Upcoming => "Upcoming Notes",
+            Statistics::Drawn => "Notes Drawn",
+            Statistics::Remaining => "Remaining Notes",
         }
     }
 
     pub fn iter() -> Iter<'static, Statistics> {
-        static STATISTICS: [Statistics; 5] = [
+        static STATISTICS: [Statistics; 10] = [
             Statistics::Time,
             Statistics::Fps,
             Statistics::VoiceCount,
             Statistics::Rendered,
             Statistics::NoteCount,
+            Statistics::RenderLoad,
+            Statistics::Nps,
+            Statistics::Upcoming,
+            Statistics::Drawn,
+            Statistics::Remaining,
         ];
         STATISTICS.iter()
     }
@@ -125,6 +145,11 @@ impl FromStr for Statistics {
             "voicecount" => Ok(Statistics::VoiceCount),
             "rendered" => Ok(Statistics::Rendered),
             "notecount" => Ok(Statistics::NoteCount),
+            "renderload" => Ok(Statistics::RenderLoad),
+            "nps" => Ok(Statistics::Nps),
+            "upcoming" => Ok(Statistics::Upcoming),
+            "drawn" => Ok(Statistics::Drawn),
+            "remaining" => Ok(Statistics::Remaining),
             s => Err(format!("{} was not expected.", s)),
         }
     }
@@ -138,6 +163,12 @@ pub enum Colors {
     Rainbow = 0,
     Random = 1,
     Palette = 2,
+    /// The hue comes from the track and the brightness from the key, see
+    /// [`crate::midi::MIDIColor::new_track_pitch`]
+    TrackPitch = 3,
+    /// The hue comes from the key, spread once over all the keys, see
+    /// [`crate::midi::MIDIColor::new_spectrum`]
+    Spectrum = 4,
 }
 
 impl Colors {
@@ -147,6 +178,8 @@ impl Colors {
             Colors::Rainbow => "Rainbow",
             Colors::Random => "Random",
             Colors::Palette => "Palette",
+            Colors::TrackPitch => "Track Hue, Pitch Shade",
+            Colors::Spectrum => "Pitch Spectrum",
         }
     }
 }
@@ -159,8 +192,242 @@ impl FromStr for Colors {
             "rainbow" => Ok(Colors::Rainbow),
             "random" => Ok(Colors::Random),
             "palette" => Ok(Colors::Palette),
+            "trackpitch" => Ok(Colors::TrackPitch),
+            "spectrum" => Ok(Colors::Spectrum),
             s => Err(format!(
-                "{} was not expected. Expected one of `ranbow`, `random` or `palette`",
+                "{} was not expected. Expected one of `ranbow`, `random`, `palette`, `trackpitch` or `spectrum`",
+                s
+            )),
+        }
+    }
+}
+
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum EndBehavior {
+    #[default]
+    Stop = 0,
+    Loop = 1,
+    Next = 2,
+    Quit = 3,
+}
+
+impl EndBehavior {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            EndBehavior::Stop => "Stop",
+            EndBehavior::Loop => "Loop",
+            EndBehavior::Next => "Play Next MIDI",
+            EndBehavior::Quit => "Quit",
+        }
+    }
+}
+
+impl FromStr for EndBehavior {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stop" => Ok(EndBehavior::Stop),
+            "loop" => Ok(EndBehavior::Loop),
+            "next" => Ok(EndBehavior::Next),
+            "quit" => Ok(EndBehavior::Quit),
+            s => Err(format!(
+                "{} was not expected. Expected one of `stop`, `loop`, `next` or `quit`",
+                s
+            )),
+        }
+    }
+}
+
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteOverlap {
+    #[default]
+    Newest = 0,
+    Oldest = 1,
+    /// The newest note is on top, mixed halfway with the note it covers
+    Blend = 2,
+}
+
+impl NoteOverlap {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            NoteOverlap::Newest => "Newest on Top",
+            NoteOverlap::Oldest => "Oldest on Top",
+            NoteOverlap::Blend => "Blend",
+        }
+    }
+}
+
+impl FromStr for NoteOverlap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "newest" => Ok(NoteOverlap::Newest),
+            "oldest" => Ok(NoteOverlap::Oldest),
+            "blend" => Ok(NoteOverlap::Blend),
+            s => Err(format!(
+                "{} was not expected. Expected one of `newest`, `oldest` or `blend`",
+                s
+            )),
+        }
+    }
+}
+
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum MidiRouting {
+    #[default]
+    Duplicate = 0,
+    Channels = 1,
+}
+
+impl MidiRouting {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            MidiRouting::Duplicate => "Send to All",
+            MidiRouting::Channels => "Route by Channel",
+        }
+    }
+}
+
+impl FromStr for MidiRouting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "duplicate" => Ok(MidiRouting::Duplicate),
+            "channels" => Ok(MidiRouting::Channels),
+            s => Err(format!(
+                "{} was not expected. Expected one of `duplicate` or `channels`",
+                s
+            )),
+        }
+    }
+}
+
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum GridMode {
+    #[default]
+    Seconds = 0,
+    Bars = 1,
+}
+
+impl GridMode {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            GridMode::Seconds => "Seconds",
+            GridMode::Bars => "Bars",
+        }
+    }
+}
+
+impl FromStr for GridMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "seconds" => Ok(GridMode::Seconds),
+            "bars" => Ok(GridMode::Bars),
+            s => Err(format!(
+                "{} was not expected. Expected one of `seconds` or `bars`",
+                s
+            )),
+        }
+    }
+}
+
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum EaseCurve {
+    Linear = 0,
+    #[default]
+    Out = 1,
+    InOut = 2,
+}
+
+impl EaseCurve {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            EaseCurve::Linear => "Linear",
+            EaseCurve::Out => "Ease Out",
+            EaseCurve::InOut => "Ease In and Out",
+        }
+    }
+
+    /// Maps the progress of the ease (0 to 1) to the progress of the value
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            EaseCurve::Linear => t,
+            EaseCurve::Out => 1.0 - (1.0 - t).powi(3),
+            EaseCurve::InOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+impl FromStr for EaseCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "linear" => Ok(EaseCurve::Linear),
+            "out" => Ok(EaseCurve::Out),
+            "inout" => Ok(EaseCurve::InOut),
+            s => Err(format!(
+                "{} was not expected. Expected one of `linear`, `out` or `inout`",
+                s
+            )),
+        }
+    }
+}
+
+/// How the notes follow the timer when a frame takes too long
+#[repr(usize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum CatchUp {
+    /// The notes always show the real time, so they stay in sync with the
+    /// audio but jump ahead after a slow frame
+    #[default]
+    RealTime = 0,
+    /// The notes move at most a little per frame, so they stay smooth but
+    /// fall behind the audio for a moment after a slow frame
+    Smooth = 1,
+}
+
+impl CatchUp {
+    #[inline]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            CatchUp::RealTime => "Real Time",
+            CatchUp::Smooth => "Smooth",
+        }
+    }
+}
+
+impl FromStr for CatchUp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "realtime" => Ok(CatchUp::RealTime),
+            "smooth" => Ok(CatchUp::Smooth),
+            s => Err(format!(
+                "{} was not expected. Expected one of `realtime` or `smooth`",
                 s
             )),
         }
@@ -16,7 +16,7 @@ mod migrations;
 
 pub use enums::*;
 
-use crate::gui::window::WasabiError;
+use crate::error::WasabiError;
 
 // region: gui
 
@@ -27,6 +27,39 @@ pub struct GuiSettings {
     pub vsync: bool,
     pub skip_control: f64,
     pub speed_control: f64,
+    pub on_end: EndBehavior,
+    /// How long the last frame stays on screen when the MIDI ends, in
+    /// seconds, before the end behavior is applied
+    pub end_hold_seconds: f64,
+    pub auto_pause_on_unfocus: bool,
+    /// Opening the MIDI that is already loaded again (e.g. to apply new
+    /// loading settings) keeps the playback position, as long as the file
+    /// didn't change in the meantime
+    pub keep_position_on_reload: bool,
+    /// Restores the loop markers, the note speed and the keyboard range that
+    /// were saved with Save View State when a MIDI gets opened again
+    pub restore_view_state: bool,
+    /// Mutes the audio while dragging the progress bar of a MIDI loaded with
+    /// the Standard or Cake algorithm. It continues from the new position
+    /// once the bar is released.
+    pub mute_while_scrubbing: bool,
+    /// How long the notes take to move to the new position when seeking, in
+    /// seconds. The audio jumps there right away.
+    pub seek_ease: Option<f64>,
+    pub seek_ease_curve: EaseCurve,
+    /// What the notes do when a frame takes longer than usual
+    pub catchup: CatchUp,
+    /// The framerate cap while nothing is playing or the window is unfocused.
+    /// Input still gets handled right away.
+    pub idle_fps: Option<u32>,
+    /// The size of the images saved by Export Poster, in pixels
+    pub poster_size: [u32; 2],
+    /// The inner size of the window in physical pixels, saved on exit
+    pub window_size: Option<[u32; 2]>,
+    /// The position of the window in physical pixels, saved on exit
+    pub window_position: Option<[i32; 2]>,
+    pub last_midi_location: PathBuf,
+    pub last_sf_location: PathBuf,
 }
 
 impl Default for GuiSettings {
@@ -36,6 +69,21 @@ impl Default for GuiSettings {
             vsync: true,
             skip_control: 1.0,
             speed_control: 0.05,
+            on_end: EndBehavior::Stop,
+            end_hold_seconds: 0.0,
+            auto_pause_on_unfocus: false,
+            keep_position_on_reload: true,
+            restore_view_state: false,
+            mute_while_scrubbing: true,
+            seek_ease: None,
+            seek_ease_curve: EaseCurve::Out,
+            catchup: CatchUp::RealTime,
+            idle_fps: Some(30),
+            poster_size: [1920, 1080],
+            window_size: None,
+            window_position: None,
+            last_midi_location: PathBuf::new(),
+            last_sf_location: PathBuf::new(),
         }
     }
 }
@@ -47,6 +95,9 @@ pub struct StatisticsSettings {
     pub floating: bool,
     pub opacity: f32,
     pub order: Vec<(Statistics, bool)>,
+    pub update_interval: f32,
+    pub smoothing: f32,
+    pub preroll_seconds: f64,
 }
 
 impl Default for StatisticsSettings {
@@ -56,6 +107,21 @@ impl Default for StatisticsSettings {
             floating: true,
             opacity: 0.5,
             order: Statistics::iter().map(|i| (*i, true)).collect(),
+            update_interval: 0.1,
+            smoothing: 0.5,
+            preroll_seconds: 1.0,
+        }
+    }
+}
+
+impl StatisticsSettings {
+    /// Appends the statistics that are missing from the order (for example
+    /// ones added after the config was saved), disabled by default
+    fn add_missing(&mut self) {
+        for stat in Statistics::iter() {
+            if !self.order.iter().any(|i| i.0 == *stat) {
+                self.order.push((*stat, false));
+            }
         }
     }
 }
@@ -68,6 +134,61 @@ pub struct SceneSettings {
     pub statistics: StatisticsSettings,
     pub note_speed: f64,
     pub key_range: RangeInclusive<u8>,
+    /// Sets the keyboard range to the keys used by each loaded MIDI, plus
+    /// this many keys on both sides. The range can still be changed by hand
+    /// until the next MIDI is loaded.
+    pub auto_zoom_keyboard: Option<u8>,
+    pub msaa_samples: u32,
+    pub color_gamma: f32,
+    /// Dithers the note colors, so gradients don't show bands. Off by
+    /// default, as the output is no longer pixel exact.
+    pub dither: bool,
+    pub note_alpha: f32,
+    pub black_key_width_frac: f32,
+    pub black_key_height_frac: f32,
+    pub glow: bool,
+    pub glow_intensity: f32,
+    /// The height of the cap as a fraction of the note, and the cap color.
+    /// The alpha of the color is how much it replaces the lightened note color.
+    pub note_cap: Option<(f32, [f32; 4])>,
+    /// The number of notes playing at once on a key at which its playing
+    /// notes become fully saturated. Not supported by the Cake renderer.
+    pub polyphony_saturation: Option<u32>,
+    /// Makes the notes fade in as they enter the top of the screen. The
+    /// notes also grow in from the middle of their key, except with Cake.
+    pub spawn_animation: bool,
+    /// How long the spawn animation takes, in seconds
+    pub spawn_duration: f32,
+    /// Notes with a lower velocity are hidden, but still audible
+    pub min_visible_velocity: u8,
+    /// Above this many notes on screen, only every Nth note of each key gets
+    /// drawn to keep the renderer responsive. Not supported by the Cake renderer.
+    pub max_visible_notes: Option<u64>,
+    /// The lane that the notes of each key are drawn on, which also lights
+    /// up that key on the keyboard. Only affects rendering.
+    #[serde(with = "key_remap_serde")]
+    pub key_remap: Option<[u8; 128]>,
+    /// How long the keys take to fade in when pressed, in seconds
+    pub key_attack: f32,
+    /// How long the keys take to fade out when released, in seconds
+    pub key_release: f32,
+    /// How long the released keys keep a tint of their last note color,
+    /// in seconds
+    pub key_retain: f32,
+    /// Draws lines behind the notes at the left edge of every C key
+    pub octave_lines: bool,
+    pub octave_line_color: Color32,
+    /// Keys that get a bolder line when the octave lines are enabled
+    pub marked_keys: Vec<u8>,
+    /// The color mixed into the notes of the overlay MIDI
+    pub overlay_tint: Color32,
+    /// The opacity of the notes of each track, indexed by track. Tracks past
+    /// the end are fully opaque. Not supported by the Cake renderer.
+    pub track_opacity: Vec<f32>,
+    /// Draws horizontal lines with time labels behind the notes
+    pub time_grid: Option<GridConfig>,
+    /// Draws a line over the notes where they hit the keyboard
+    pub hit_line: Option<HitLineConfig>,
 }
 
 impl Default for SceneSettings {
@@ -78,10 +199,125 @@ impl Default for SceneSettings {
             statistics: Default::default(),
             note_speed: 0.25,
             key_range: 0..=127,
+            auto_zoom_keyboard: None,
+            msaa_samples: 1,
+            color_gamma: 2.0,
+            dither: false,
+            note_alpha: 1.0,
+            black_key_width_frac: 0.74,
+            black_key_height_frac: 0.66,
+            glow: false,
+            glow_intensity: 0.5,
+            note_cap: None,
+            polyphony_saturation: None,
+            spawn_animation: false,
+            spawn_duration: 0.05,
+            min_visible_velocity: 0,
+            max_visible_notes: None,
+            key_remap: None,
+            key_attack: 0.0,
+            key_release: 0.0,
+            key_retain: 0.0,
+            octave_lines: false,
+            octave_line_color: Color32::from_rgba_unmultiplied(255, 255, 255, 24),
+            marked_keys: Vec::new(),
+            overlay_tint: Color32::from_rgb(255, 255, 255),
+            track_opacity: Vec::new(),
+            time_grid: None,
+            hit_line: None,
+        }
+    }
+}
+
+impl SceneSettings {
+    pub fn track_opacity(&self, track: u32) -> f32 {
+        self.track_opacity
+            .get(track as usize)
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// Whether any notes can be partly transparent, which needs the
+    /// transparent pipelines. Cake doesn't support the track opacity.
+    pub fn has_transparent_notes(&self, cake: bool) -> bool {
+        self.note_alpha < 1.0 || (!cake && self.track_opacity.iter().any(|o| *o < 1.0))
+    }
+
+    /// The lane that the notes of `key` are drawn on, see [`SceneSettings::key_remap`]
+    pub fn key_lane(&self, key: usize) -> usize {
+        match &self.key_remap {
+            Some(remap) if key < remap.len() => remap[key].min(127) as usize,
+            _ => key,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GridConfig {
+    pub mode: GridMode,
+    /// The distance between the lines, in seconds or in bars depending on
+    /// the mode
+    pub interval: f64,
+    pub color: Color32,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            mode: GridMode::Seconds,
+            interval: 1.0,
+            color: Color32::from_rgba_unmultiplied(255, 255, 255, 32),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HitLineConfig {
+    pub color: Color32,
+    /// The thickness of the line in points
+    pub thickness: f32,
+    /// Fades the color out above the line, so it looks like it glows
+    pub glow: bool,
+}
+
+impl Default for HitLineConfig {
+    fn default() -> Self {
+        Self {
+            color: Color32::from_rgb(255, 255, 255),
+            thickness: 2.0,
+            glow: false,
         }
     }
 }
 
+/// Serde only supports arrays of up to 32 items, so the remap is stored as a list
+mod key_remap_serde {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(remap: &Option<[u8; 128]>, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        remap.as_ref().map(|remap| remap.as_slice()).serialize(ser)
+    }
+
+    pub fn deserialize<'de, D>(de: D) -> Result<Option<[u8; 128]>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Some(keys) = Option::<Vec<u8>>::deserialize(de)? else {
+            return Ok(None);
+        };
+
+        let len = keys.len();
+        keys.try_into()
+            .map(Some)
+            .map_err(|_| de::Error::invalid_length(len, &"a list of 128 keys"))
+    }
+}
+
 // endregion
 
 // region: midi
@@ -90,20 +326,82 @@ impl Default for SceneSettings {
 #[serde(default)]
 pub struct MidiSettings {
     pub parsing: MidiParsing,
+    /// The estimated note count from which the Auto algorithm loads a MIDI
+    /// with Cake instead of Standard (RAM)
+    pub auto_cake_threshold: u64,
+    /// The time resolution of the Cake trees. Higher values make seeking
+    /// and short notes more precise, but add more tree nodes, so the block
+    /// buffers in [`crate::midi::CakeSignature`] get larger. They also
+    /// lower the longest MIDI that can be loaded. Clamped to
+    /// [`crate::midi::CAKE_TICKS_PER_SECOND_RANGE`].
+    pub cake_ticks_per_second: u32,
+    /// Stores the trees built by the Cake algorithm on disk and reads them
+    /// back when the same MIDI gets loaded with the same settings, see
+    /// [`crate::midi::CakeMIDIFile::cache_dir`]
+    pub cake_disk_cache: bool,
+    /// How large the Cake caches can get in total, in gigabytes. The least
+    /// recently used ones get removed first.
+    pub cake_cache_max_gb: f64,
     pub start_delay: f64,
+    /// Starts the playback `start_delay` seconds before the first note
+    /// instead of at the start of the MIDI. Not used by the Live algorithm.
+    pub skip_leading_silence: bool,
     pub colors: Colors,
     pub randomize_palette: bool,
     pub palette_path: PathBuf,
+    /// How far apart the hues of neighboring tracks are with the Track Hue,
+    /// Pitch Shade colors, in degrees
+    pub track_hue_spread: f64,
+    /// The brightness of the lowest and the highest key with the Track Hue,
+    /// Pitch Shade colors
+    pub pitch_shade_range: [f32; 2],
+    /// The hues of the lowest and the highest key with the Pitch Spectrum
+    /// colors, in degrees
+    pub spectrum_hues: [f64; 2],
+    pub note_overlap: NoteOverlap,
+    pub ppq_override: Option<u16>,
+    pub global_time_offset: f64,
+    pub split_long_notes: Option<f64>,
+    /// Moves the percussion notes onto their own lane above the keyboard,
+    /// see [`crate::midi::DRUM_LANE_KEYS`]
+    pub drum_lane: bool,
+    /// Plays a click on every beat, see [`crate::midi::audio::metronome`]
+    pub metronome: bool,
+    pub metronome_volume: f32,
+    /// The channels whose events get skipped when loading, so they are
+    /// neither displayed nor played. Not used by the Live algorithm.
+    pub channel_filter: [bool; 16],
+    /// Shifts the notes of each channel by this many semitones, both on
+    /// screen and in the audio. Notes shifted past the MIDI keys are
+    /// dropped. Not used by the Live algorithm.
+    pub transpose: [i8; 16],
 }
 
 impl Default for MidiSettings {
     fn default() -> Self {
         Self {
             parsing: MidiParsing::Cake,
+            auto_cake_threshold: 10_000_000,
+            cake_ticks_per_second: 10000,
+            cake_disk_cache: false,
+            cake_cache_max_gb: 20.0,
             start_delay: 2.0,
+            skip_leading_silence: false,
             colors: Colors::Rainbow,
             randomize_palette: false,
             palette_path: PathBuf::new(),
+            track_hue_spread: 37.5,
+            pitch_shade_range: [0.35, 1.0],
+            spectrum_hues: [0.0, 270.0],
+            note_overlap: NoteOverlap::Newest,
+            ppq_override: None,
+            global_time_offset: 0.0,
+            split_long_notes: None,
+            drum_lane: false,
+            metronome: false,
+            metronome_volume: 0.8,
+            channel_filter: [false; 16],
+            transpose: [0; 16],
         }
     }
 }
@@ -144,25 +442,66 @@ pub struct WasabiSoundfont {
     pub options: SoundfontInitOptions,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MidiDeviceOutput {
+    pub name: String,
+    /// The channels (1-16) sent to this device when routing by channel
+    pub channels: RangeInclusive<u8>,
+}
+
+impl Default for MidiDeviceOutput {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            channels: 1..=16,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(default)]
 pub struct SynthSettings {
+    pub audio_enabled: bool,
     pub synth: Synth,
     pub soundfonts: Vec<WasabiSoundfont>,
 
     pub xsynth: XSynthSettings,
     pub kdmapi: KdmapiSettings,
-    pub midi_device: String,
+    /// The single device of older configs, moved to `midi_devices` on load
+    #[serde(skip_serializing)]
+    midi_device: String,
+    pub midi_devices: Vec<MidiDeviceOutput>,
+    pub midi_routing: MidiRouting,
+    pub audio_thread_priority: bool,
+    pub audio_thread_core: Option<usize>,
 }
 
 impl Default for SynthSettings {
     fn default() -> Self {
         Self {
+            audio_enabled: true,
             synth: Synth::XSynth,
             soundfonts: Vec::new(),
             xsynth: Default::default(),
             kdmapi: Default::default(),
             midi_device: String::new(),
+            midi_devices: Vec::new(),
+            midi_routing: MidiRouting::Duplicate,
+            audio_thread_priority: false,
+            audio_thread_core: None,
+        }
+    }
+}
+
+impl SynthSettings {
+    fn migrate_midi_device(&mut self) {
+        let name = std::mem::take(&mut self.midi_device);
+        if !name.is_empty() && self.midi_devices.is_empty() {
+            self.midi_devices.push(MidiDeviceOutput {
+                name,
+                ..Default::default()
+            });
         }
     }
 }
@@ -198,8 +537,12 @@ impl WasabiSettings {
         } else if let Ok(config) = fs::read_to_string(&config_path) {
             if config.starts_with(Self::VERSION_TEXT) {
                 let offset = Self::VERSION_TEXT.len();
-                match serde_json::from_str(&config[offset..]) {
-                    Ok(config) => return Ok(config),
+                match serde_json::from_str::<Self>(&config[offset..]) {
+                    Ok(mut config) => {
+                        config.scene.statistics.add_missing();
+                        config.synth.migrate_midi_device();
+                        return Ok(config);
+                    }
                     Err(e) => err = WasabiError::SettingsError(e.to_string()),
                 }
             } else if config.starts_with("# DON'T EDIT THIS LINE; Version: 1") {
@@ -238,6 +581,17 @@ impl WasabiSettings {
         Ok(())
     }
 
+    /// Saves the window geometry and the last opened locations, while keeping
+    /// the rest of the settings as they were last saved by the user
+    pub fn save_session(&self) -> Result<(), WasabiError> {
+        let mut saved = Self::new_or_load()?;
+        saved.gui.window_size = self.gui.window_size;
+        saved.gui.window_position = self.gui.window_position;
+        saved.gui.last_midi_location = self.gui.last_midi_location.clone();
+        saved.gui.last_sf_location = self.gui.last_sf_location.clone();
+        saved.save_to_file()
+    }
+
     fn load_and_save_defaults() -> Result<Self, WasabiError> {
         let cfg = Self::default();
         Self::save_to_file(&cfg)?;
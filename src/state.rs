@@ -2,7 +2,7 @@ use std::{path::PathBuf, sync::Arc};
 
 use crate::{
     audio_playback::WasabiAudioPlayer,
-    gui::window::{GuiMessageSystem, LoadingStatus},
+    gui::window::{ActiveNotesCallback, GuiMessageSystem, LoadingStatus},
 };
 
 #[derive(Default, PartialEq)]
@@ -14,10 +14,19 @@ pub enum SettingsTab {
     SoundFonts,
 }
 
+/// Renders the MIDI at a fixed framerate instead of in real time, saving
+/// every frame as a numbered PNG in `dir`, along with the audio of the
+/// captured part as `audio.wav`
+pub struct CaptureOptions {
+    pub dir: PathBuf,
+    pub fps: u32,
+}
+
 pub struct WasabiState {
     pub synth: Arc<WasabiAudioPlayer>,
 
     pub fullscreen: bool,
+    pub quit_requested: bool,
 
     pub errors: Arc<GuiMessageSystem>,
     pub loading_status: Arc<LoadingStatus>,
@@ -30,11 +39,16 @@ pub struct WasabiState {
     pub show_settings: bool,
     pub show_shortcuts: bool,
     pub show_about: bool,
+    pub show_file_info: bool,
+    pub show_debug: bool,
 
     pub settings_tab: SettingsTab,
 
-    pub last_midi_location: PathBuf,
-    pub last_sf_location: PathBuf,
+    pub capture: Option<CaptureOptions>,
+
+    /// Gets called with the pressed keys whenever they change, see
+    /// [`crate::run_with_active_notes`]
+    pub on_active_notes: Option<ActiveNotesCallback>,
 }
 
 impl WasabiState {
@@ -46,6 +60,7 @@ impl WasabiState {
             synth: WasabiAudioPlayer::empty(),
 
             fullscreen: false,
+            quit_requested: false,
 
             errors,
             loading_status,
@@ -58,11 +73,14 @@ impl WasabiState {
             show_settings: false,
             show_shortcuts: false,
             show_about: false,
+            show_file_info: false,
+            show_debug: false,
 
             settings_tab: SettingsTab::default(),
 
-            last_midi_location: PathBuf::default(),
-            last_sf_location: PathBuf::default(),
+            capture: None,
+
+            on_active_notes: None,
         }
     }
 }
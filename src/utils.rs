@@ -1,10 +1,14 @@
 use reqwest::blocking::ClientBuilder;
 use serde_json::Value;
 use std::thread;
-use std::{collections::HashMap, ops::RangeInclusive};
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
 
 use crate::settings::WasabiSoundfont;
-use crate::{gui::window::WasabiError, state::WasabiState};
+use crate::{error::WasabiError, state::WasabiState};
 
 pub const WIN_MARGIN: egui::Margin = egui::Margin::same(12.0);
 pub const NOTE_SPEED_RANGE: RangeInclusive<f64> = 8.0..=0.05;
@@ -32,6 +36,15 @@ pub fn convert_seconds_to_time_string(sec: f64) -> String {
     )
 }
 
+/// The digit of a number key, used for the seek shortcuts
+pub fn number_key_digit(key: egui::Key) -> Option<u8> {
+    use egui::Key::*;
+    [Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9]
+        .iter()
+        .position(|k| *k == key)
+        .map(|digit| digit as u8)
+}
+
 pub fn create_window_frame(ctx: &egui::Context) -> egui::Frame {
     egui::Frame::inner_margin(egui::Frame::window(ctx.style().as_ref()), WIN_MARGIN)
 }
@@ -116,3 +129,72 @@ pub fn create_reset_midi_messages() -> Vec<u32> {
 
     out
 }
+
+/// Messages that bring every channel back to its initial state: all sound
+/// and notes off, controllers reset, pitch bend centered and the first
+/// program of the first bank selected
+pub fn create_full_reset_midi_messages() -> Vec<u32> {
+    let mut out = Vec::new();
+
+    for ch in 0..16 {
+        let cc = |cc: u32, value: u32| 0xB0 | ch | cc << 8 | value << 16;
+        out.extend([cc(120, 0), cc(123, 0), cc(121, 0), cc(0, 0), cc(32, 0)]);
+        // Program change to 0, then the pitch bend centered at 0x2000
+        out.push(0xC0 | ch);
+        out.push(0xE0 | ch | 0x40 << 16);
+    }
+
+    out
+}
+
+/// Finds the MIDI file that comes after the given one (in alphabetical
+/// order) in the same directory.
+pub fn find_next_midi(current: &Path) -> Option<PathBuf> {
+    let midis = list_midis(current.parent()?).ok()?;
+    midis.into_iter().find(|path| path.as_path() > current)
+}
+
+/// The MIDI files of a directory, in alphabetical order
+pub fn list_midis(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut midis: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .map(|ext| ext.eq_ignore_ascii_case("mid") || ext.eq_ignore_ascii_case("rmi"))
+                    .unwrap_or(false)
+        })
+        .collect();
+    midis.sort();
+    Ok(midis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::create_full_reset_midi_messages;
+
+    #[test]
+    fn full_reset_covers_every_channel() {
+        let messages = create_full_reset_midi_messages();
+        assert_eq!(messages.len(), 16 * 7);
+
+        for (ch, channel) in messages.chunks(7).enumerate() {
+            let ch = ch as u32;
+            let cc = |cc: u32| 0xB0 | ch | (cc << 8);
+            assert_eq!(
+                channel,
+                [
+                    cc(120),
+                    cc(123),
+                    cc(121),
+                    cc(0),
+                    cc(32),
+                    0xC0 | ch,
+                    // 0x2000 split into 7 bit halves
+                    0xE0 | ch | (0x40 << 16),
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Loads a dense MIDI into Cake and checks how far sealing the trees raises
+//! the memory. This is its own test binary, so the counting allocator only
+//! sees this load.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use wasabi::engine::{CakeMIDIFile, MIDIFileBase, MidiSettings};
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+            PEAK.fetch_max(allocated + layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const NOTES_PER_KEY: u32 = 5_000;
+const PPQ: u16 = 96;
+
+fn write_variable_length(out: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+/// A note every 10 ticks on each of the 128 keys, all in one track
+fn write_dense_midi() -> std::path::PathBuf {
+    let mut track = Vec::new();
+    for i in 0..NOTES_PER_KEY {
+        let on_delta = if i == 0 { 0 } else { 5 };
+        for (delta, status) in [(on_delta, 0x90), (5, 0x80)] {
+            for key in 0..128u8 {
+                write_variable_length(&mut track, if key == 0 { delta } else { 0 });
+                track.extend_from_slice(&[status, key, 100]);
+            }
+        }
+    }
+    track.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+    let mut file = Vec::new();
+    file.extend_from_slice(b"MThd");
+    file.extend_from_slice(&6u32.to_be_bytes());
+    file.extend_from_slice(&1u16.to_be_bytes());
+    file.extend_from_slice(&1u16.to_be_bytes());
+    file.extend_from_slice(&PPQ.to_be_bytes());
+    file.extend_from_slice(b"MTrk");
+    file.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    file.extend_from_slice(&track);
+
+    let path = std::env::temp_dir().join(format!("wasabi_dense_{}.mid", std::process::id()));
+    std::fs::write(&path, file).unwrap();
+    path
+}
+
+#[test]
+fn sealing_dense_keys_keeps_the_peak_memory_low() {
+    let path = write_dense_midi();
+    // The disk cache is off by default, so the trees always get built
+    let settings = MidiSettings::default();
+
+    PEAK.store(ALLOCATED.load(Ordering::Relaxed), Ordering::Relaxed);
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let midi = CakeMIDIFile::load_from_file(path.clone(), None, &settings, None).unwrap();
+    let peak = PEAK.load(Ordering::Relaxed) - before;
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(midi.stats().total_notes, Some(NOTES_PER_KEY as u64 * 128));
+
+    // Besides the trees, the load only keeps the buffered events of the
+    // keys around, so sealing all of them at once must not pile up copies
+    let tree_bytes: usize = midi
+        .key_blocks()
+        .iter()
+        .map(|block| std::mem::size_of_val(block.tree.as_slice()))
+        .sum();
+    let slack = 128 * 1024 * 1024;
+    assert!(
+        peak <= tree_bytes * 3 + slack,
+        "the load peaked at {peak} bytes for {tree_bytes} bytes of trees"
+    );
+}